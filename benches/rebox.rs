@@ -0,0 +1,51 @@
+//! Boxing with an arena, when it has a free box and when it has none, compared to `Box::new`,
+//! for values of several sizes.
+
+use boxing_arena::BoxingArena;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn sizes<const N: usize>(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rebox");
+    let v = [0u8; N];
+
+    group.bench_function(BenchmarkId::new("Box::new", N), |b| {
+        b.iter(|| drop(black_box(Box::new(black_box(v)))))
+    });
+
+    group.bench_function(BenchmarkId::new("hit", N), |b| {
+        let mut ba = BoxingArena::with_capacity(1);
+        b.iter(|| {
+            let boxed = ba.rebox(black_box(v));
+            ba.unbox(black_box(boxed));
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("miss", N), |b| {
+        let mut ba = BoxingArena::new();
+        b.iter(|| drop(black_box(ba.rebox(black_box(v)))))
+    });
+
+    // Many boxes alive at once, given back in the order they were handed out.
+    group.bench_function(BenchmarkId::new("hit-batch", N), |b| {
+        let mut ba = BoxingArena::with_capacity(64);
+        let mut boxes = Vec::with_capacity(64);
+        b.iter(|| {
+            boxes.extend((0..64).map(|_| ba.rebox(black_box(v))));
+            for boxed in boxes.drain(..) {
+                ba.unbox(boxed);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn rebox(c: &mut Criterion) {
+    sizes::<8>(c);
+    sizes::<64>(c);
+    sizes::<512>(c);
+    sizes::<4096>(c);
+}
+
+criterion_group!(benches, rebox);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! The `Pooled` derive macro of `boxing-arena`, enabled there by the `derive` feature.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Error, Expr};
+
+/// Implement `boxing_arena::Pooled` with an arena local to each thread. The attribute
+/// `#[pooled(capacity = N)]` sets the most free boxes each of these arenas keeps.
+#[proc_macro_derive(Pooled, attributes(pooled))]
+pub fn derive_pooled(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Pooled` cannot be derived for generic types, which have no single arena",
+        ));
+    }
+
+    let mut capacity: Option<Expr> = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("pooled")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("capacity") {
+                capacity = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown `pooled` attribute, expected `capacity`"))
+            }
+        })?;
+    }
+
+    let name = &input.ident;
+    let arena = match capacity {
+        Some(capacity) => quote!(::boxing_arena::BoxingArena::with_max_capacity(#capacity)),
+        None => quote!(::boxing_arena::BoxingArena::new()),
+    };
+    Ok(quote! {
+        impl ::boxing_arena::Pooled for #name {
+            fn local_arena() -> ::std::rc::Rc<
+                ::std::cell::RefCell<::boxing_arena::BoxingArena<Self>>,
+            > {
+                ::std::thread_local! {
+                    static ARENA: ::std::rc::Rc<
+                        ::std::cell::RefCell<::boxing_arena::BoxingArena<#name>>,
+                    > = ::std::rc::Rc::new(::std::cell::RefCell::new(#arena));
+                }
+                ARENA.with(::std::rc::Rc::clone)
+            }
+        }
+    })
+}
@@ -0,0 +1,77 @@
+//! Arbitrary interleavings of the operations of `BoxingArena`, checking that values are
+//! neither leaked nor dropped twice, and that reused boxes never alias live ones.
+
+#![no_main]
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use boxing_arena::BoxingArena;
+use libfuzzer_sys::fuzz_target;
+
+struct Counted {
+    id: usize,
+    alive: Rc<Cell<usize>>,
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.alive.set(self.alive.get() - 1);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let alive = Rc::new(Cell::new(0));
+    let mut ba = BoxingArena::new();
+    let mut live: Vec<Box<Counted>> = vec![];
+    let mut capacity = 0usize;
+
+    for (id, &byte) in data.iter().enumerate() {
+        let mut v = Some(Counted {
+            id,
+            alive: alive.clone(),
+        });
+        alive.set(alive.get() + 1);
+        let arg = (byte >> 3) as usize;
+        match byte & 7 {
+            0 | 1 => {
+                let b = if byte & 7 == 0 {
+                    Some(ba.rebox(v.take().unwrap()))
+                } else {
+                    ba.try_rebox(&mut v)
+                };
+                if let Some(b) = b {
+                    capacity = capacity.saturating_sub(1);
+                    assert_eq!(b.id, id);
+                    assert!(live.iter().all(|l| !std::ptr::eq(&**l, &*b)));
+                    live.push(b);
+                }
+            }
+            2 | 3 if !live.is_empty() => {
+                let b = live.swap_remove(arg % live.len());
+                let expected = b.id;
+                if byte & 7 == 2 {
+                    assert_eq!(ba.unbox(b).id, expected);
+                } else {
+                    ba.recycle(b);
+                }
+                capacity += 1;
+            }
+            4 => {
+                ba.resize_capacity(arg);
+                capacity = arg;
+            }
+            5 => {
+                ba.trim(arg);
+                capacity = capacity.min(arg);
+            }
+            _ => {}
+        }
+        drop(v);
+        assert_eq!(ba.capacity(), capacity);
+        assert_eq!(alive.get(), live.len());
+    }
+    drop(live);
+    drop(ba);
+    assert_eq!(alive.get(), 0);
+});
@@ -0,0 +1,74 @@
+//! Wrappers that raise the alignment of the boxes of an arena.
+//!
+//! An arena cannot carry a larger alignment than that of `T` on its own: the boxes it hands
+//! out are plain `Box<T>`, which are freed with the layout of `T` when dropped outside of the
+//! arena. Raising the alignment of the type itself keeps every box sound instead.
+
+use core::ops::{Deref, DerefMut};
+
+macro_rules! aligned {
+    ($(#[$doc:meta])* $name:ident, $align:literal) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #[repr(align($align))]
+        pub struct $name<T>(pub T);
+
+        impl<T> $name<T> {
+            /// The alignment of the wrapper, in bytes.
+            pub const ALIGN: usize = $align;
+
+            /// Return the value.
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T> From<T> for $name<T> {
+            fn from(v: T) -> Self {
+                Self(v)
+            }
+        }
+
+        impl<T> Deref for $name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $name<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+    };
+}
+
+aligned! {
+    /// A value aligned to a 64-byte cache line, as in `BoxingArena<CacheAligned<T>>`, so that
+    /// pooled values suit aligned SIMD loads, and two boxes never share a cache line.
+    CacheAligned, 64
+}
+
+aligned! {
+    /// A value aligned to a page, see `PAGE_SIZE`, as in `BoxingArena<PageAligned<T>>`.
+    PageAligned, 4096
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoxingArena, PAGE_SIZE};
+
+    #[test]
+    fn aligned() {
+        assert_eq!(PageAligned::<u8>::ALIGN, PAGE_SIZE);
+        let mut ba = BoxingArena::<CacheAligned<[f32; 4]>>::with_capacity(2);
+        assert_eq!(BoxingArena::<CacheAligned<[f32; 4]>>::slot_layout().align(), 64);
+        let b = ba.rebox(CacheAligned([1.0; 4]));
+        assert_eq!(&*b as *const _ as usize % 64, 0);
+        assert_eq!(b[3], 1.0);
+        assert_eq!(ba.unbox(b).into_inner(), [1.0; 4]);
+    }
+}
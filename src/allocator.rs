@@ -0,0 +1,157 @@
+//! An arena usable as an `Allocator`, on nightly or through `allocator-api2`.
+
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::mem::MaybeUninit;
+use std::ptr::NonNull;
+
+use crate::BoxingArena;
+
+/// A `BoxingArena` usable as the allocator of standard containers, as in
+/// `Box::new_in(v, &allocator)`. Allocations with the layout of `T` are taken from, and given
+/// back to, the arena; any other allocation goes to the global allocator.
+///
+/// It implements the `Allocator` trait of the standard library with the `allocator-api`
+/// feature, on nightly, and the one of the `allocator-api2` crate, for its containers on
+/// stable, with the `allocator-api2` feature.
+///
+/// Like `RefCell`, it is meant for use within a single thread.
+pub struct ArenaAllocator<T> {
+    arena: RefCell<BoxingArena<T>>,
+}
+
+impl<T> ArenaAllocator<T> {
+    /// Create an allocator over an empty arena.
+    pub fn new() -> Self {
+        Self::from(BoxingArena::new())
+    }
+
+    /// Return the arena, for the operations it provides.
+    pub fn arena(&self) -> &RefCell<BoxingArena<T>> {
+        &self.arena
+    }
+
+    /// Return the arena.
+    pub fn into_inner(self) -> BoxingArena<T> {
+        self.arena.into_inner()
+    }
+
+    fn reuses(layout: Layout) -> bool {
+        layout == Layout::new::<T>() && layout.size() != 0
+    }
+
+    /// Take a box from the arena, if it has the layout and is not in use.
+    fn allocate_slot(&self, layout: Layout) -> Option<NonNull<[u8]>> {
+        if !Self::reuses(layout) {
+            return None;
+        }
+        let mut arena = self.arena.try_borrow_mut().ok()?;
+        let p = Box::into_raw(arena.rebox_uninit()) as *mut u8;
+        let p = unsafe { NonNull::new_unchecked(p) };
+        Some(NonNull::slice_from_raw_parts(p, layout.size()))
+    }
+
+    /// Give a box back to the arena, if it has the layout and is not in use.
+    unsafe fn deallocate_slot(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        if !Self::reuses(layout) {
+            return false;
+        }
+        match self.arena.try_borrow_mut() {
+            Ok(mut arena) => {
+                arena.unbox_uninit(Box::from_raw(ptr.as_ptr() as *mut MaybeUninit<T>));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl<T> From<BoxingArena<T>> for ArenaAllocator<T> {
+    fn from(arena: BoxingArena<T>) -> Self {
+        Self {
+            arena: RefCell::new(arena),
+        }
+    }
+}
+
+impl<T> Default for ArenaAllocator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+unsafe impl<T> std::alloc::Allocator for ArenaAllocator<T> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        use std::alloc::Global;
+        match self.allocate_slot(layout) {
+            Some(p) => Ok(p),
+            None => Global.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        use std::alloc::Global;
+        if !self.deallocate_slot(ptr, layout) {
+            Global.deallocate(ptr, layout)
+        }
+    }
+}
+
+#[cfg(feature = "allocator-api2")]
+unsafe impl<T> allocator_api2::alloc::Allocator for ArenaAllocator<T> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, allocator_api2::alloc::AllocError> {
+        use allocator_api2::alloc::Global;
+        match self.allocate_slot(layout) {
+            Some(p) => Ok(p),
+            None => Global.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        use allocator_api2::alloc::Global;
+        if !self.deallocate_slot(ptr, layout) {
+            Global.deallocate(ptr, layout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "allocator-api")]
+    #[test]
+    fn allocator() {
+        let allocator = ArenaAllocator::<[u64; 4]>::new();
+        let a = Box::new_in([1u64; 4], &allocator);
+        let addr = &*a as *const [u64; 4];
+        drop(a);
+        assert_eq!(allocator.arena().borrow().capacity(), 1);
+
+        let b = Box::new_in([2u64; 4], &allocator);
+        assert_eq!(&*b as *const [u64; 4], addr);
+
+        // Other layouts are not kept.
+        let mut v = Vec::with_capacity_in(3, &allocator);
+        v.extend([[3u64; 4]; 3]);
+        drop(v);
+        drop(b);
+        assert_eq!(allocator.into_inner().stats().hits, 1);
+    }
+
+    #[cfg(feature = "allocator-api2")]
+    #[test]
+    fn allocator_api2() {
+        use allocator_api2::boxed::Box;
+
+        let allocator = ArenaAllocator::<u64>::new();
+        let a = Box::new_in(1u64, &allocator);
+        let addr = &*a as *const u64;
+        drop(a);
+        let b = Box::new_in(2u64, &allocator);
+        assert_eq!(&*b as *const u64, addr);
+        drop(b);
+        assert_eq!(allocator.into_inner().capacity(), 1);
+    }
+}
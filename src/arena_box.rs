@@ -0,0 +1,124 @@
+//! A box that goes back to its arena when dropped.
+
+use alloc::boxed::Box;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+use crate::{BoxingArena, SharedPool};
+
+enum PoolRef<'a, T> {
+    Exclusive(&'a mut BoxingArena<T>),
+    Shared(&'a dyn SharedPool<T>),
+}
+
+/// A box borrowed from an arena, which gives its allocation back to the arena when dropped
+/// instead of deallocating it, so that forgetting to `unbox` cannot happen.
+///
+/// It is returned by `BoxingArena::rebox_guarded`, which borrows the arena for as long as
+/// the guard lives, and by `SharedPool::rebox_guarded`, for which several guards may be
+/// alive at once, for example with a `RefCell<BoxingArena<T>>`.
+pub struct ArenaBox<'a, T> {
+    value: ManuallyDrop<Box<T>>,
+    pool: PoolRef<'a, T>,
+}
+
+impl<'a, T> ArenaBox<'a, T> {
+    pub(crate) fn shared(b: Box<T>, pool: &'a dyn SharedPool<T>) -> Self {
+        Self {
+            value: ManuallyDrop::new(b),
+            pool: PoolRef::Shared(pool),
+        }
+    }
+
+    fn into_parts(self) -> (Box<T>, PoolRef<'a, T>) {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { (ManuallyDrop::take(&mut this.value), core::ptr::read(&this.pool)) }
+    }
+
+    /// Take the value out, giving the allocation back to the arena.
+    #[track_caller]
+    pub fn into_inner(this: Self) -> T {
+        match this.into_parts() {
+            (b, PoolRef::Exclusive(arena)) => arena.unbox(b),
+            (b, PoolRef::Shared(pool)) => pool.unbox(b),
+        }
+    }
+
+    /// Detach the box from the arena, which will not get the allocation back.
+    pub fn into_box(this: Self) -> Box<T> {
+        this.into_parts().0
+    }
+}
+
+impl<'a, T> Deref for ArenaBox<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'a, T> DerefMut for ArenaBox<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl_borrow!(['a, T] ArenaBox<'a, T>, mut);
+impl_cmp!(['a, T] ArenaBox<'a, T>);
+
+// The value is in a box, which stays in place until the `ArenaBox` is dropped.
+#[cfg(feature = "stable-deref")]
+unsafe impl<'a, T> stable_deref_trait::StableDeref for ArenaBox<'a, T> {}
+
+impl<'a, T> Drop for ArenaBox<'a, T> {
+    fn drop(&mut self) {
+        let b = unsafe { ManuallyDrop::take(&mut self.value) };
+        let v = match &mut self.pool {
+            PoolRef::Exclusive(arena) => arena.unbox(b),
+            PoolRef::Shared(pool) => pool.unbox(b),
+        };
+        // Outside of the pool's lock, if any.
+        drop(v);
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Like `rebox`, but the box gives its allocation back to the arena when dropped. The
+    /// arena stays borrowed meanwhile; see `SharedPool` for having several guards at once.
+    #[track_caller]
+    pub fn rebox_guarded(&mut self, v: T) -> ArenaBox<'_, T> {
+        let b = self.rebox(v);
+        ArenaBox {
+            value: ManuallyDrop::new(b),
+            pool: PoolRef::Exclusive(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn arena_box() {
+        let mut ba = BoxingArena::new();
+        let mut b = ba.rebox_guarded(vec![1u8; 16]);
+        b.push(2);
+        drop(b);
+        assert_eq!(ba.capacity(), 1);
+        assert!(ArenaBox::into_inner(ba.rebox_guarded(vec![])).is_empty());
+
+        let shared = RefCell::new(ba);
+        let a = shared.rebox_guarded(vec![3u8]);
+        let b = shared.rebox_guarded(vec![4u8]);
+        assert!(a < b);
+        drop((a, b));
+        assert_eq!(shared.borrow().capacity(), 2);
+
+        let detached = ArenaBox::into_box(shared.rebox_guarded(vec![5u8]));
+        assert_eq!(*detached, [5]);
+        assert_eq!(shared.borrow().capacity(), 1);
+    }
+}
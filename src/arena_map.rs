@@ -0,0 +1,181 @@
+//! Slab-like storage of boxed values, addressed by keys.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::BoxingArena;
+
+/// The key of a value in an `ArenaMap`. Keys of removed values are never valid again, even
+/// once their place holds another value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArenaKey {
+    index: u32,
+    generation: u32,
+}
+
+struct Entry<T> {
+    generation: u32,
+    value: Option<Box<T>>,
+}
+
+/// A map handing out small copyable keys for the values inserted into it, as a slab does,
+/// for graphs and entity storage. Values are boxed by an arena, so that their addresses do
+/// not change as the map grows, and the boxes of removed values are reused for later ones.
+pub struct ArenaMap<T> {
+    arena: BoxingArena<T>,
+    entries: Vec<Entry<T>>,
+    /// Indices of the entries without a value.
+    vacant: Vec<u32>,
+    len: usize,
+}
+
+impl<T> ArenaMap<T> {
+    /// Create an empty map. No allocation is made by this function.
+    pub const fn new() -> Self {
+        Self {
+            arena: BoxingArena::new(),
+            entries: Vec::new(),
+            vacant: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Insert a value, returning its key.
+    #[track_caller]
+    pub fn insert(&mut self, v: T) -> ArenaKey {
+        let value = Some(self.arena.rebox(v));
+        self.len += 1;
+        match self.vacant.pop() {
+            Some(index) => {
+                let entry = &mut self.entries[index as usize];
+                entry.value = value;
+                ArenaKey {
+                    index,
+                    generation: entry.generation,
+                }
+            }
+            None => {
+                let index = u32::try_from(self.entries.len()).expect("too many entries");
+                self.entries.push(Entry {
+                    generation: 0,
+                    value,
+                });
+                ArenaKey {
+                    index,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    fn entry(&self, key: ArenaKey) -> Option<&Entry<T>> {
+        self.entries.get(key.index as usize).filter(|e| e.generation == key.generation)
+    }
+
+    /// Return the value of the key, if it was not removed.
+    pub fn get(&self, key: ArenaKey) -> Option<&T> {
+        self.entry(key)?.value.as_deref()
+    }
+
+    /// Return the value of the key mutably, if it was not removed.
+    pub fn get_mut(&mut self, key: ArenaKey) -> Option<&mut T> {
+        let entry = self.entries.get_mut(key.index as usize)?;
+        if entry.generation != key.generation {
+            return None;
+        }
+        entry.value.as_deref_mut()
+    }
+
+    /// Return whether the key has a value.
+    pub fn contains_key(&self, key: ArenaKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value of the key, keeping its box for later insertions.
+    #[track_caller]
+    pub fn remove(&mut self, key: ArenaKey) -> Option<T> {
+        let entry = self.entries.get_mut(key.index as usize)?;
+        if entry.generation != key.generation {
+            return None;
+        }
+        let b = entry.value.take()?;
+        // Once all generations were used, the entry is retired rather than reused.
+        if let Some(generation) = entry.generation.checked_add(1) {
+            entry.generation = generation;
+            self.vacant.push(key.index);
+        }
+        self.len -= 1;
+        Some(self.arena.unbox(b))
+    }
+
+    /// Return the number of values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return whether the map holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the keys and values.
+    pub fn iter(&self) -> impl Iterator<Item = (ArenaKey, &T)> {
+        self.entries.iter().enumerate().filter_map(|(index, e)| {
+            let key = ArenaKey {
+                index: index as u32,
+                generation: e.generation,
+            };
+            Some((key, e.value.as_deref()?))
+        })
+    }
+
+    /// Return the arena boxing the values, for example to trim the boxes it keeps.
+    pub fn arena(&mut self) -> &mut BoxingArena<T> {
+        &mut self.arena
+    }
+}
+
+impl<T> Default for ArenaMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> core::ops::Index<ArenaKey> for ArenaMap<T> {
+    type Output = T;
+
+    fn index(&self, key: ArenaKey) -> &T {
+        self.get(key).expect("no value for the key")
+    }
+}
+
+impl<T> core::ops::IndexMut<ArenaKey> for ArenaMap<T> {
+    fn index_mut(&mut self, key: ArenaKey) -> &mut T {
+        self.get_mut(key).expect("no value for the key")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_map() {
+        let mut map = ArenaMap::new();
+        let a = map.insert(String::from("a"));
+        let b = map.insert(String::from("b"));
+        map[b].push('!');
+        let addr = &map[a] as *const String;
+        assert_eq!(map.remove(a).as_deref(), Some("a"));
+        assert_eq!((map.get(a), map.len()), (None, 1));
+        assert_eq!(map.remove(a), None);
+
+        // The key of the new value differs, but its box is reused.
+        let c = map.insert(String::from("c"));
+        assert_ne!(a, c);
+        assert_eq!(&map[c] as *const String, addr);
+        let values: Vec<_> = map.iter().map(|(_, v)| v.as_str()).collect();
+        assert_eq!(values, ["c", "b!"]);
+    }
+}
@@ -0,0 +1,288 @@
+//! Support for using arenas from async code.
+
+use std::future::Future;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::task::{Context, Poll, Waker};
+
+use crate::{ArenaSender, BoxingArena};
+
+/// Number of boxes allocated or deallocated between yields of `resize_capacity_async`.
+pub const RESIZE_CHUNK: usize = 1024;
+
+/// A future that is pending once, so the executor can run other tasks.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Like `resize_capacity`, but allocating (or deallocating) `RESIZE_CHUNK` boxes at a time
+    /// and yielding to the executor in between, so that warming up a large pool from a task
+    /// does not starve the other tasks of its worker thread.
+    pub async fn resize_capacity_async(&mut self, size: usize) {
+        while !self.resize_step(size) {
+            YieldNow(false).await;
+        }
+    }
+
+    /// Resize by at most `RESIZE_CHUNK` boxes towards `size`, returning whether there is
+    /// nothing left to do. Boxes of zero-sized types have no allocation, so none are kept.
+    fn resize_step(&mut self, size: usize) -> bool {
+        if core::mem::size_of::<T>() == 0 {
+            return true;
+        }
+        let n = self.capacity();
+        let step = if size > n {
+            n + (size - n).min(RESIZE_CHUNK)
+        } else {
+            n - (n - size).min(RESIZE_CHUNK)
+        };
+        self.resize_capacity(step);
+        // Done also if the step made no progress, rather than yielding forever.
+        step == size || self.capacity() == n
+    }
+}
+
+/// An arena shared by the tasks of an async executor, whose operations never block the
+/// thread they run on.
+///
+/// The arena is behind a mutex that is never waited for: `rebox` and `lock` are pending
+/// while another task holds it, and are woken when it is released. Boxes given back by
+/// `unbox` do not take the mutex at all; they are queued, and kept by the arena in a batch
+/// the next time it is locked. The guard of `lock` should not be held across an `.await`, or
+/// the tasks waiting for it wait that long too. The futures are `Send` for `T: Send`, so the
+/// arena can be shared by the tasks of a multi-threaded executor.
+pub struct AsyncBoxingArena<T> {
+    arena: Mutex<BoxingArena<T>>,
+    /// The tasks waiting for the arena. Only held to add or take wakers, never while waiting.
+    waiters: Mutex<Vec<Waker>>,
+    sender: ArenaSender<T>,
+}
+
+impl<T> AsyncBoxingArena<T> {
+    /// Create an empty arena.
+    pub fn new() -> Self {
+        Self::from(BoxingArena::new())
+    }
+
+    /// Create an arena with `size` free boxes.
+    pub fn with_capacity(size: usize) -> Self {
+        Self::from(BoxingArena::with_capacity(size))
+    }
+
+    /// Lock the arena, once no other task holds it, for the operations that are not provided
+    /// here. The boxes given back since it was last locked are kept first.
+    pub fn lock(&self) -> impl Future<Output = AsyncArenaGuard<'_, T>> + '_ {
+        Lock { arena: self }
+    }
+
+    fn try_lock(&self) -> Option<AsyncArenaGuard<'_, T>> {
+        let mut guard = match self.arena.try_lock() {
+            Ok(guard) => guard,
+            // A panic while the lock was held leaves the arena consistent.
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return None,
+        };
+        guard.collect_returned();
+        Some(AsyncArenaGuard {
+            guard: ManuallyDrop::new(guard),
+            waiters: &self.waiters,
+        })
+    }
+
+    /// Box a value, see `BoxingArena::rebox`.
+    pub async fn rebox(&self, v: T) -> Box<T> {
+        self.lock().await.rebox(v)
+    }
+
+    /// Like `BoxingArena::resize_capacity_async`, but locking the arena for each step only,
+    /// so that other tasks can use it in between.
+    pub async fn resize_capacity_async(&self, size: usize) {
+        while !self.lock().await.resize_step(size) {
+            YieldNow(false).await;
+        }
+    }
+
+    /// Take the value out of the box, and queue the allocation to be kept by the arena. This
+    /// takes no lock, and need not be awaited, so that it can also be done from `Drop`.
+    pub fn unbox(&self, b: Box<T>) -> T {
+        self.sender.unbox(b)
+    }
+
+    /// Drop the value in place, and queue the allocation to be kept by the arena, as `unbox`
+    /// does.
+    pub fn recycle(&self, b: Box<T>) {
+        self.sender.recycle(b)
+    }
+}
+
+impl<T> From<BoxingArena<T>> for AsyncBoxingArena<T> {
+    fn from(mut arena: BoxingArena<T>) -> Self {
+        Self {
+            sender: arena.sender(),
+            arena: Mutex::new(arena),
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// The future of `AsyncBoxingArena::lock`.
+struct Lock<'a, T> {
+    arena: &'a AsyncBoxingArena<T>,
+}
+
+impl<'a, T> Future for Lock<'a, T> {
+    type Output = AsyncArenaGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(guard) = self.arena.try_lock() {
+            return Poll::Ready(guard);
+        }
+        {
+            let mut waiters = self.arena.waiters.lock().unwrap_or_else(|e| e.into_inner());
+            if !waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                waiters.push(cx.waker().clone());
+            }
+        }
+        // The arena may have been released before the waker was added.
+        match self.arena.try_lock() {
+            Some(guard) => Poll::Ready(guard),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// The lock of an `AsyncBoxingArena`, which wakes the tasks waiting for it when dropped.
+pub struct AsyncArenaGuard<'a, T> {
+    guard: ManuallyDrop<MutexGuard<'a, BoxingArena<T>>>,
+    waiters: &'a Mutex<Vec<Waker>>,
+}
+
+impl<'a, T> Deref for AsyncArenaGuard<'a, T> {
+    type Target = BoxingArena<T>;
+
+    fn deref(&self) -> &BoxingArena<T> {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for AsyncArenaGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut BoxingArena<T> {
+        &mut self.guard
+    }
+}
+
+impl<'a, T> Drop for AsyncArenaGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        // All of them, as some may already have taken the lock on their own.
+        let waiters = {
+            let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut *waiters)
+        };
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Default for AsyncBoxingArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn assert_send<F: Send>(_: F) {}
+
+    /// Run a future to completion, returning how many times it yielded.
+    fn block_on<F: Future>(f: F) -> usize {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+        let mut yields = 0;
+        while f.as_mut().poll(&mut cx).is_pending() {
+            yields += 1;
+        }
+        yields
+    }
+
+    #[test]
+    fn resize_capacity_async() {
+        let mut ba = BoxingArena::<u8>::new();
+        assert_eq!(block_on(ba.resize_capacity_async(RESIZE_CHUNK * 2 + 1)), 2);
+        assert_eq!(ba.capacity(), RESIZE_CHUNK * 2 + 1);
+        assert_eq!(block_on(ba.resize_capacity_async(1)), 1);
+        assert_eq!(ba.capacity(), 1);
+        assert_eq!(block_on(ba.resize_capacity_async(1)), 0);
+
+        let mut ba = BoxingArena::<()>::new();
+        assert_eq!(block_on(ba.resize_capacity_async(RESIZE_CHUNK * 5)), 0);
+        assert_eq!(ba.capacity(), 0);
+    }
+
+    #[test]
+    fn async_arena() {
+        let arena = AsyncBoxingArena::with_capacity(1);
+        let mut b = None;
+        assert_eq!(block_on(async { b = Some(arena.rebox(1u64).await) }), 0);
+
+        let guard = arena.try_lock().unwrap();
+        let wakes = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut rebox = Box::pin(arena.rebox(2));
+        assert!(rebox.as_mut().poll(&mut cx).is_pending());
+        assert!(rebox.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(arena.unbox(b.unwrap()), 1);
+        assert_eq!(wakes.0.load(Ordering::Relaxed), 0);
+        drop(guard);
+        assert!(wakes.0.load(Ordering::Relaxed) > 0);
+        assert!(rebox.as_mut().poll(&mut cx).is_ready());
+        assert_send(arena.rebox(3));
+        assert_send(arena.lock());
+        assert_send(arena.resize_capacity_async(1));
+
+        assert_eq!(block_on(arena.resize_capacity_async(RESIZE_CHUNK + 1)), 1);
+        let mut capacity = 0;
+        block_on(async { capacity = arena.lock().await.capacity() });
+        assert_eq!(capacity, RESIZE_CHUNK + 1);
+
+        let mut hits = 0;
+        block_on(async { hits = arena.lock().await.stats().hits });
+        assert_eq!(hits, 2);
+    }
+}
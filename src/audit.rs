@@ -0,0 +1,166 @@
+//! Auditing of the boxes that an arena handed out and did not get back.
+
+#[cfg(debug_assertions)]
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::panic::Location;
+
+use crate::BoxingArena;
+
+/// What an arena does about the boxes it handed out and did not get back, as set by
+/// `BoxingArena::set_audit`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Audit {
+    /// Nothing, the default.
+    #[default]
+    Off,
+    /// Count them, see `BoxingArena::outstanding`.
+    Count,
+    /// Count them, and write a warning to stderr if some are left when the arena is dropped.
+    Warn,
+    /// Count them, and panic if some are left when the arena is dropped, unless it is dropped
+    /// while unwinding.
+    Panic,
+}
+
+pub(crate) struct Outstanding {
+    audit: Audit,
+    count: usize,
+    /// Where the first outstanding box at each address was handed out, and how many are
+    /// outstanding there, as all the boxes of a zero-sized type share one address.
+    #[cfg(debug_assertions)]
+    live: BTreeMap<usize, (&'static Location<'static>, usize)>,
+}
+
+impl Outstanding {
+    pub(crate) const fn new() -> Self {
+        Self {
+            audit: Audit::Off,
+            count: 0,
+            #[cfg(debug_assertions)]
+            live: BTreeMap::new(),
+        }
+    }
+
+    /// None outstanding, with the same mode.
+    pub(crate) fn fork_empty(&self) -> Self {
+        Self {
+            audit: self.audit,
+            ..Self::new()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn handed_out(&mut self, addr: usize, caller: &'static Location<'static>) {
+        if self.audit == Audit::Off {
+            return;
+        }
+        #[cfg(debug_assertions)]
+        {
+            self.live.entry(addr).or_insert((caller, 0)).1 += 1;
+        }
+        let _ = (addr, caller);
+        self.count += 1;
+    }
+
+    #[inline]
+    pub(crate) fn given_back(&mut self, addr: usize) {
+        if self.audit == Audit::Off {
+            return;
+        }
+        // Boxes that were not handed out by the arena are not counted.
+        #[cfg(debug_assertions)]
+        match self.live.get_mut(&addr) {
+            Some((_, 1)) => {
+                self.live.remove(&addr);
+            }
+            Some((_, count)) => *count -= 1,
+            None => return,
+        }
+        let _ = addr;
+        self.count = self.count.saturating_sub(1);
+    }
+
+    /// Warn or panic as the mode says, if some boxes are still outstanding.
+    pub(crate) fn report(&self, type_name: &str) {
+        if self.count == 0 || self.audit == Audit::Off || self.audit == Audit::Count {
+            return;
+        }
+        let msg = format!(
+            "arena of {} dropped with {} boxes outstanding",
+            type_name, self.count
+        );
+        #[cfg(debug_assertions)]
+        let msg = match self.live.values().next() {
+            Some((caller, _)) => format!("{}, one of them handed out at {}", msg, caller),
+            None => msg,
+        };
+        match self.audit {
+            Audit::Panic if !std::thread::panicking() => panic!("{}", msg),
+            _ => {
+                let _ = writeln!(std::io::stderr(), "boxing-arena: {}", msg);
+            }
+        }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Set whether the arena keeps track of the boxes it handed out and did not get back, for
+    /// finding code that drops them instead of giving them back, slowly starving the arena.
+    /// In debug builds, the arena also records where each was handed out, and reports one of
+    /// them on drop. In release builds, boxes given back that the arena did not hand out
+    /// still lower the count. Switching modes starts counting over.
+    pub fn set_audit(&mut self, audit: Audit) {
+        self.outstanding = Outstanding {
+            audit,
+            ..Outstanding::new()
+        };
+    }
+
+    /// Return the mode set by `set_audit`.
+    pub fn audit(&self) -> Audit {
+        self.outstanding.audit
+    }
+
+    /// Return the number of boxes handed out by the arena since auditing started, and not
+    /// given back, see `set_audit`.
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit() {
+        let mut ba = BoxingArena::new();
+        ba.set_audit(Audit::Panic);
+        assert_eq!(ba.audit(), Audit::Panic);
+        let a = ba.rebox(1u8);
+        let b = ba.rebox(2u8);
+        ba.unbox(a);
+        // Before dropping `b`, whose address the allocator might reuse for the foreign box.
+        ba.unbox(Box::new(3));
+        drop(b);
+        assert_eq!(ba.outstanding(), if cfg!(debug_assertions) { 1 } else { 0 });
+
+        let mut fork = ba.fork_empty();
+        ba.set_audit(Audit::Off);
+        let c = fork.rebox(4u8);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(fork)));
+        assert!(r.is_err());
+        drop(c);
+    }
+
+    #[test]
+    fn audit_zero_sized() {
+        let mut ba = BoxingArena::new();
+        ba.set_audit(Audit::Panic);
+        let boxes = vec![ba.rebox(()), ba.rebox(())];
+        assert_eq!(ba.outstanding(), 2);
+        boxes.into_iter().for_each(|b| ba.unbox(b));
+        assert_eq!(ba.outstanding(), 0);
+    }
+}
@@ -0,0 +1,111 @@
+//! Growing or trimming the free boxes toward the observed working set.
+
+use alloc::collections::VecDeque;
+
+use crate::free_list::LowMark;
+use crate::BoxingArena;
+
+/// The demand for free boxes observed between the last calls to `maintain`.
+pub(crate) struct AutoTune {
+    window: usize,
+    /// The most free boxes needed at once in each period, newest at the back.
+    demands: VecDeque<usize>,
+    /// The free boxes, and the misses so far, at the end of the previous period.
+    start: usize,
+    misses: u64,
+}
+
+impl<T> BoxingArena<T> {
+    /// Let `maintain` tune the number of free boxes, or stop doing so with `None`, the
+    /// default. Each call to `maintain` ends a period, in which the arena tracks how many of
+    /// its free boxes were taken out at once, and how many boxes it missed. Over the last
+    /// `window` periods, the most boxes needed at once is the working set: the arena
+    /// allocates ahead when it missed boxes, so that the next burst hits, and trims the free
+    /// boxes that the working set never reached.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    pub fn set_auto_tune(&mut self, window: Option<usize>) {
+        self.tune = window.map(|window| {
+            assert!(window > 0, "auto-tune window is empty");
+            self.items.take_low(LowMark::Tune);
+            AutoTune {
+                window,
+                demands: VecDeque::with_capacity(window),
+                start: self.items.len(),
+                misses: self.counters.misses,
+            }
+        });
+    }
+
+    /// Return the window set by `set_auto_tune`.
+    pub fn auto_tune(&self) -> Option<usize> {
+        self.tune.as_ref().map(|tune| tune.window)
+    }
+
+    /// End the current period, and resize toward the working set.
+    pub(crate) fn run_auto_tune(&mut self) {
+        let tune = match self.tune.as_mut() {
+            Some(tune) => tune,
+            None => return,
+        };
+        let low = self.items.take_low(LowMark::Tune);
+        let misses = self.counters.misses - tune.misses;
+        let demand = (tune.start - low).saturating_add(misses as usize);
+        if tune.demands.len() == tune.window {
+            tune.demands.pop_front();
+        }
+        tune.demands.push_back(demand);
+        let target = tune.demands.iter().copied().max().unwrap_or(0);
+
+        let capacity = self.items.len();
+        if target > capacity {
+            // Past the limits, or out of memory, the arena keeps what it has.
+            let _ = self.try_reserve(target - capacity);
+        } else {
+            self.trim(target);
+        }
+        self.items.take_low(LowMark::Tune);
+        if let Some(tune) = self.tune.as_mut() {
+            tune.start = self.items.len();
+            tune.misses = self.counters.misses;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_tune() {
+        let mut ba = BoxingArena::<u64>::with_capacity(8);
+        ba.set_auto_tune(Some(2));
+        assert_eq!(ba.auto_tune(), Some(2));
+
+        // A burst of three boxes, then one of five, two of them misses.
+        let boxes: Vec<_> = (0..3).map(|i| ba.rebox(i)).collect();
+        for b in boxes {
+            ba.unbox(b);
+        }
+        ba.maintain();
+        assert_eq!(ba.capacity(), 3);
+
+        let boxes: Vec<_> = (0..5).map(|i| ba.rebox(i)).collect();
+        for b in boxes {
+            ba.unbox(b);
+        }
+        ba.maintain();
+        assert_eq!(ba.capacity(), 5);
+
+        // Once the burst leaves the window, the quiet periods trim down to what they used.
+        let b = ba.rebox(1);
+        ba.unbox(b);
+        ba.maintain();
+        assert_eq!(ba.capacity(), 5);
+        ba.maintain();
+        assert_eq!(ba.capacity(), 1);
+        assert_eq!(ba.fork_empty().auto_tune(), Some(2));
+    }
+}
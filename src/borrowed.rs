@@ -0,0 +1,82 @@
+//! Lending a bounded part of an arena's capacity to a callee.
+
+use alloc::boxed::Box;
+
+use crate::BoxingArena;
+
+/// A view of a parent `BoxingArena` that may use at most a fixed number of its free boxes,
+/// as returned by `BoxingArena::lend`.
+///
+/// When the budget is used up, or the parent has no free boxes left, boxes are allocated
+/// normally and the parent does not grow because of them. Boxes given back through `unbox`
+/// return to the parent only up to the number that were taken from it.
+pub struct BorrowedArena<'a, T> {
+    parent: &'a mut BoxingArena<T>,
+    available: usize,
+    limit: usize,
+}
+
+impl<'a, T> BorrowedArena<'a, T> {
+    pub(crate) fn new(parent: &'a mut BoxingArena<T>, limit: usize) -> Self {
+        Self {
+            parent,
+            available: limit,
+            limit,
+        }
+    }
+
+    /// Box a value, using one of the parent's free boxes if the budget allows it.
+    #[track_caller]
+    pub fn rebox(&mut self, v: T) -> Box<T> {
+        if self.available > 0 && self.parent.capacity() > 0 {
+            self.available -= 1;
+            self.parent.rebox(v)
+        } else {
+            self.parent.counters.misses += 1;
+            Box::new(v)
+        }
+    }
+
+    /// Unbox the value. The allocation goes back to the parent if it makes up for a box
+    /// taken from it, and is deallocated otherwise.
+    #[track_caller]
+    pub fn unbox(&mut self, v: Box<T>) -> T {
+        if self.available < self.limit {
+            self.available += 1;
+            self.parent.unbox(v)
+        } else {
+            *v
+        }
+    }
+
+    /// Return how many more of the parent's free boxes may still be used.
+    pub fn available(&self) -> usize {
+        self.available
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lend() {
+        let mut ba = BoxingArena::with_capacity(4);
+        {
+            let mut lent = ba.lend(2);
+            let a = lent.rebox(1u32);
+            let b = lent.rebox(2u32);
+            assert_eq!(lent.available(), 0);
+
+            // Over budget: allocated normally.
+            let c = lent.rebox(3u32);
+            assert_eq!(lent.parent.capacity(), 2);
+
+            assert_eq!(lent.unbox(a), 1);
+            assert_eq!(lent.unbox(b), 2);
+            // Does not grow the parent.
+            assert_eq!(lent.unbox(c), 3);
+        }
+        assert_eq!(ba.capacity(), 4);
+    }
+}
@@ -0,0 +1,335 @@
+//! An arena for async code that bounds the number of boxes handed out at once.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::BoxingArena;
+
+struct Waiter {
+    id: u64,
+    waker: Waker,
+    /// Whether a box that was given back has been handed over to this waiter.
+    granted: bool,
+}
+
+struct State<T> {
+    arena: BoxingArena<T>,
+    outstanding: usize,
+    limit: usize,
+    /// In arrival order.
+    waiters: VecDeque<Waiter>,
+    next_id: u64,
+}
+
+impl<T> State<T> {
+    /// Account for a box that was given back, handing its place over to the first waiter
+    /// that did not get one yet.
+    fn release(&mut self) {
+        match self.waiters.iter_mut().find(|w| !w.granted) {
+            Some(waiter) => {
+                waiter.granted = true;
+                waiter.waker.wake_by_ref();
+            }
+            None => self.outstanding -= 1,
+        }
+    }
+}
+
+/// A shared arena that hands out at most a fixed number of boxes at once. Tasks asking for
+/// more wait until boxes are given back, and are served in the order in which they started
+/// waiting, so that none of them starves under sustained contention.
+///
+/// Clones refer to the same pool.
+pub struct BoundedPool<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> BoundedPool<T> {
+    /// Hand out boxes from `arena`, at most `limit` of them at once.
+    pub fn new(arena: BoxingArena<T>, limit: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                arena,
+                outstanding: 0,
+                limit,
+                waiters: VecDeque::new(),
+                next_id: 0,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Box a value once fewer than the limit of boxes are out.
+    ///
+    /// This is cancellation-safe: dropping the future before it completes, for example in a
+    /// branch of `select!` that lost, gives up its place in the queue without keeping a box
+    /// from other tasks.
+    pub async fn rebox(&self, v: T) -> BoundedBox<T> {
+        Acquire { pool: self, id: None }.await;
+        self.hand_out(v)
+    }
+
+    /// Box a value if fewer than the limit of boxes are out and no task is waiting, or give
+    /// it back otherwise.
+    #[track_caller]
+    pub fn try_rebox(&self, v: T) -> Result<BoundedBox<T>, T> {
+        {
+            let mut state = self.lock();
+            if state.outstanding >= state.limit || !state.waiters.is_empty() {
+                return Err(v);
+            }
+            state.outstanding += 1;
+        }
+        Ok(self.hand_out(v))
+    }
+
+    /// Box a value for a place that was already accounted for.
+    #[track_caller]
+    fn hand_out(&self, v: T) -> BoundedBox<T> {
+        let b = self.lock().arena.rebox(v);
+        BoundedBox {
+            value: Some(b),
+            pool: self.clone(),
+        }
+    }
+
+    /// Return the number of boxes out, counting those handed over to waiting tasks.
+    pub fn outstanding(&self) -> usize {
+        self.lock().outstanding
+    }
+
+    /// Return the number of tasks waiting for a box.
+    pub fn waiters(&self) -> usize {
+        self.lock().waiters.len()
+    }
+
+    /// Run `f` on the underlying arena.
+    pub fn with_arena<R>(&self, f: impl FnOnce(&mut BoxingArena<T>) -> R) -> R {
+        f(&mut self.lock().arena)
+    }
+}
+
+impl<T> Clone for BoundedPool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Waits for a place among the boxes out. Dropping it before it completes is safe.
+struct Acquire<'a, T> {
+    pool: &'a BoundedPool<T>,
+    /// Set while in the queue of waiters.
+    id: Option<u64>,
+}
+
+impl<'a, T> Future for Acquire<'a, T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.pool.lock();
+        let id = match self.id {
+            None if state.outstanding < state.limit && state.waiters.is_empty() => {
+                state.outstanding += 1;
+                return Poll::Ready(());
+            }
+            None => {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.waiters.push_back(Waiter {
+                    id,
+                    waker: cx.waker().clone(),
+                    granted: false,
+                });
+                drop(state);
+                self.id = Some(id);
+                return Poll::Pending;
+            }
+            Some(id) => id,
+        };
+
+        let pos = state.waiters.iter().position(|w| w.id == id).unwrap();
+        if state.waiters[pos].granted {
+            state.waiters.remove(pos);
+            drop(state);
+            self.id = None;
+            return Poll::Ready(());
+        }
+        state.waiters[pos].waker.clone_from(cx.waker());
+        Poll::Pending
+    }
+}
+
+// Cancellation leaves the queue, and hands a place that was already granted over to the
+// next waiter, so that no box is stranded.
+impl<'a, T> Drop for Acquire<'a, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            let mut state = self.pool.lock();
+            let pos = state.waiters.iter().position(|w| w.id == id).unwrap();
+            if state.waiters.remove(pos).unwrap().granted {
+                state.release();
+            }
+        }
+    }
+}
+
+/// A box handed out by a `BoundedPool`. It goes back to the pool when dropped, making room
+/// for a waiting task.
+pub struct BoundedBox<T> {
+    value: Option<Box<T>>,
+    pool: BoundedPool<T>,
+}
+
+impl<T> BoundedBox<T> {
+    /// Take the value out, giving the box back to the pool.
+    pub fn into_inner(mut self) -> T {
+        let b = self.value.take().unwrap();
+        let mut state = self.pool.lock();
+        let v = state.arena.unbox(b);
+        state.release();
+        v
+    }
+}
+
+impl<T> Deref for BoundedBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for BoundedBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl_borrow!([T] BoundedBox<T>, mut);
+impl_cmp!([T] BoundedBox<T>);
+
+// The value is in a box, which stays in place until the `BoundedBox` is dropped.
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::StableDeref for BoundedBox<T> {}
+
+impl<T> Drop for BoundedBox<T> {
+    fn drop(&mut self) {
+        if let Some(b) = self.value.take() {
+            let v = {
+                let mut state = self.pool.lock();
+                let v = state.arena.unbox(b);
+                state.release();
+                v
+            };
+            // Outside of the lock.
+            drop(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Wake;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn poll<F: Future + Unpin>(f: &mut F) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::new(Noop));
+        Pin::new(f).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn fifo() {
+        let pool = BoundedPool::new(BoxingArena::new(), 1);
+        let first = pool.try_rebox(0u32).ok().unwrap();
+        assert!(pool.try_rebox(0).is_err());
+
+        let mut waiting: Vec<_> = (1..=3).map(|i| Box::pin(pool.rebox(i))).collect();
+        for w in &mut waiting {
+            assert!(poll(w).is_pending());
+        }
+        assert_eq!(pool.waiters(), 3);
+
+        // The box goes to the first waiter, even if another one polls before it.
+        drop(first);
+        assert!(poll(&mut waiting[2]).is_pending());
+        assert!(poll(&mut waiting[1]).is_pending());
+        let b = match poll(&mut waiting[0]) {
+            Poll::Ready(b) => b,
+            Poll::Pending => panic!("first waiter not served"),
+        };
+        assert_eq!(*b, 1);
+        assert_eq!(pool.outstanding(), 1);
+
+        // A newcomer does not jump the queue.
+        assert_eq!(b.into_inner(), 1);
+        assert!(pool.try_rebox(9).is_err());
+        assert!(poll(&mut waiting[2]).is_pending());
+        match poll(&mut waiting[1]) {
+            Poll::Ready(b) => assert_eq!(*b, 2),
+            Poll::Pending => panic!("second waiter not served"),
+        }
+    }
+
+    #[test]
+    fn cancellation() {
+        let pool = BoundedPool::new(BoxingArena::new(), 1);
+        let first = pool.try_rebox(0u32).ok().unwrap();
+        let mut a = Box::pin(pool.rebox(1));
+        let mut b = Box::pin(pool.rebox(2));
+        assert!(poll(&mut a).is_pending());
+        assert!(poll(&mut b).is_pending());
+
+        // A waiter cancelled while the box is still out leaves the queue.
+        drop(a);
+        assert_eq!(pool.waiters(), 1);
+
+        // A waiter cancelled after being handed the box passes it to the next one.
+        let mut c = Box::pin(pool.rebox(3));
+        assert!(poll(&mut c).is_pending());
+        drop(first);
+        drop(b);
+        match poll(&mut c) {
+            Poll::Ready(c) => assert_eq!(*c, 3),
+            Poll::Pending => panic!("box stranded by a cancelled waiter"),
+        }
+        assert_eq!(pool.outstanding(), 0);
+        assert_eq!(pool.with_arena(|arena| arena.capacity()), 1);
+    }
+
+    #[tokio::test]
+    async fn select_timeout() {
+        use std::time::Duration;
+
+        let pool = BoundedPool::new(BoxingArena::new(), 1);
+        let first = pool.rebox(0u32).await;
+        tokio::select! {
+            _ = pool.rebox(1) => panic!("limit not enforced"),
+            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+        }
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), pool.rebox(2)).await;
+        assert!(timed_out.is_err());
+        assert_eq!(pool.waiters(), 0);
+
+        let p = pool.clone();
+        let waiter = tokio::spawn(async move { *p.rebox(3).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(first);
+        assert_eq!(waiter.await.unwrap(), 3);
+        assert_eq!(pool.outstanding(), 0);
+    }
+}
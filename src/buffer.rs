@@ -0,0 +1,126 @@
+//! An arena of byte buffers.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// An arena of `Vec<u8>` buffers, such as network buffers, that are checked out empty with at
+/// least a requested capacity and keep their capacity once checked back in.
+///
+/// New buffers get a capacity rounded up to a power of two, so that requests of close sizes
+/// share buffers. For boxed byte slices of exact lengths, see `SliceArena`.
+#[derive(Default)]
+pub struct BufferArena {
+    /// Free buffers by capacity.
+    buffers: BTreeMap<usize, Vec<Vec<u8>>>,
+    zero_on_checkin: bool,
+}
+
+impl BufferArena {
+    /// Create an empty arena. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return an empty buffer with a capacity of at least `min_capacity`, reusing the smallest
+    /// free buffer that is large enough, if there is one.
+    pub fn checkout(&mut self, min_capacity: usize) -> Vec<u8> {
+        let capacity = match self.buffers.range(min_capacity..).next() {
+            Some((&capacity, _)) => capacity,
+            None => {
+                let capacity = min_capacity.checked_next_power_of_two().unwrap_or(min_capacity);
+                return Vec::with_capacity(capacity);
+            }
+        };
+        let bucket = self.buffers.get_mut(&capacity).unwrap();
+        let buffer = bucket.pop().unwrap();
+        if bucket.is_empty() {
+            self.buffers.remove(&capacity);
+        }
+        buffer
+    }
+
+    /// Like `checkout`, but return a buffer of `len` zero bytes.
+    pub fn checkout_zeroed(&mut self, len: usize) -> Vec<u8> {
+        let mut buffer = self.checkout(len);
+        buffer.resize(len, 0);
+        buffer
+    }
+
+    /// Clear the buffer and keep it for reuse. Buffers without capacity are dropped.
+    pub fn checkin(&mut self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+        if self.zero_on_checkin {
+            buffer.fill(0);
+        }
+        buffer.clear();
+        self.buffers.entry(buffer.capacity()).or_default().push(buffer);
+    }
+
+    /// Set whether `checkin` overwrites the contents of buffers with zeros, so that data such
+    /// as keys or user payloads does not linger in free buffers.
+    pub fn set_zero_on_checkin(&mut self, zero: bool) {
+        self.zero_on_checkin = zero;
+    }
+
+    /// Return whether `checkin` overwrites the contents of buffers with zeros.
+    pub fn zeroes_on_checkin(&self) -> bool {
+        self.zero_on_checkin
+    }
+
+    /// Return the number of free buffers.
+    pub fn capacity(&self) -> usize {
+        self.buffers.values().map(Vec::len).sum()
+    }
+
+    /// Return the number of bytes held by the free buffers.
+    pub fn capacity_bytes(&self) -> usize {
+        self.buffers.iter().map(|(capacity, buffers)| capacity * buffers.len()).sum()
+    }
+
+    /// Release free buffers, largest first, so that the free buffers hold at most `bytes`.
+    pub fn trim_bytes(&mut self, bytes: usize) {
+        let mut held = self.capacity_bytes();
+        while held > bytes {
+            let mut entry = match self.buffers.last_entry() {
+                Some(entry) => entry,
+                None => break,
+            };
+            held -= *entry.key();
+            entry.get_mut().pop();
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers() {
+        let mut arena = BufferArena::new();
+        let mut a = arena.checkout(1000);
+        assert_eq!(a.capacity(), 1024);
+        a.extend_from_slice(b"secret");
+        let addr = a.as_ptr();
+        arena.set_zero_on_checkin(true);
+        arena.checkin(a);
+        arena.checkin(Vec::with_capacity(4096));
+        assert_eq!(arena.capacity_bytes(), 1024 + 4096);
+
+        let b = arena.checkout_zeroed(600);
+        assert_eq!((b.as_ptr(), b.len()), (addr, 600));
+        assert!(b.iter().all(|&byte| byte == 0));
+        assert_eq!(arena.checkout(2000).capacity(), 4096);
+        assert_eq!(arena.capacity(), 0);
+
+        arena.checkin(b);
+        arena.checkin(Vec::with_capacity(64));
+        arena.trim_bytes(100);
+        assert_eq!(arena.capacity_bytes(), 64);
+    }
+}
@@ -0,0 +1,108 @@
+//! An arena of the buffers of the `bytes` crate.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use bytes::{Bytes, BytesMut};
+
+/// An arena of `BytesMut` buffers, like `BufferArena` is for `Vec<u8>`, for networking code
+/// built on the `bytes` crate. Buffers are checked out empty with at least a requested
+/// capacity, and checked back in once done with.
+///
+/// A buffer checked back in reclaims the whole of its storage if it is the only one using
+/// it. A buffer that still shares its storage with parts split off from it is kept with the
+/// capacity of its own part, and the storage is freed once all parts are gone.
+#[derive(Default)]
+pub struct BytesArena {
+    /// Free buffers by capacity.
+    buffers: BTreeMap<usize, Vec<BytesMut>>,
+}
+
+impl BytesArena {
+    /// Create an empty arena. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return an empty buffer with a capacity of at least `min_capacity`, reusing the smallest
+    /// free buffer that is large enough, if there is one. New buffers get a capacity rounded
+    /// up to a power of two.
+    pub fn checkout(&mut self, min_capacity: usize) -> BytesMut {
+        let capacity = match self.buffers.range(min_capacity..).next() {
+            Some((&capacity, _)) => capacity,
+            None => {
+                let capacity = min_capacity.checked_next_power_of_two().unwrap_or(min_capacity);
+                return BytesMut::with_capacity(capacity);
+            }
+        };
+        let bucket = self.buffers.get_mut(&capacity).unwrap();
+        let buffer = bucket.pop().unwrap();
+        if bucket.is_empty() {
+            self.buffers.remove(&capacity);
+        }
+        buffer
+    }
+
+    /// Clear the buffer and keep it for reuse. Buffers without capacity are dropped.
+    pub fn checkin(&mut self, mut buffer: BytesMut) {
+        buffer.clear();
+        // Moves the buffer to the start of its storage if it is the only one using it.
+        let _ = buffer.try_reclaim(buffer.capacity() + 1);
+        if buffer.capacity() == 0 {
+            return;
+        }
+        self.buffers.entry(buffer.capacity()).or_default().push(buffer);
+    }
+
+    /// Keep the storage of a frozen buffer for reuse if nothing else refers to it, such as
+    /// clones or slices of it.
+    pub fn checkin_bytes(&mut self, bytes: Bytes) {
+        if let Ok(buffer) = bytes.try_into_mut() {
+            self.checkin(buffer);
+        }
+    }
+
+    /// Return the number of free buffers.
+    pub fn capacity(&self) -> usize {
+        self.buffers.values().map(Vec::len).sum()
+    }
+
+    /// Return the number of bytes held by the free buffers.
+    pub fn capacity_bytes(&self) -> usize {
+        self.buffers.iter().map(|(capacity, buffers)| capacity * buffers.len()).sum()
+    }
+
+    /// Release all free buffers.
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_arena() {
+        let mut arena = BytesArena::new();
+        let mut a = arena.checkout(1000);
+        assert_eq!(a.capacity(), 1024);
+        a.extend_from_slice(b"header and body");
+        let addr = a.as_ptr();
+
+        // Once the split off header is gone, the whole storage is reclaimed.
+        let header = a.split_to(6).freeze();
+        drop(header);
+        arena.checkin(a);
+        let b = arena.checkout(1024);
+        assert_eq!((b.as_ptr(), b.len()), (addr, 0));
+
+        // Frozen buffers come back too, unless shared.
+        let bytes = b.freeze();
+        let shared = bytes.clone();
+        arena.checkin_bytes(bytes);
+        assert_eq!(arena.capacity(), 0);
+        arena.checkin_bytes(shared);
+        assert_eq!(arena.capacity_bytes(), 1024);
+    }
+}
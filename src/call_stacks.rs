@@ -0,0 +1,105 @@
+//! Counting of hits and misses by call stack, enabled by the `call-stacks` feature.
+
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::io::{self, Write};
+
+use crate::BoxingArena;
+
+/// Hit and miss counts, by the instruction pointers of the stack that asked for the box. They
+/// are kept as pointers rather than addresses, so that they can be resolved under Miri.
+pub(crate) struct CallStacks {
+    counts: BTreeMap<Box<[*mut c_void]>, [u64; 2]>,
+}
+
+impl CallStacks {
+    pub(crate) const fn new() -> Self {
+        Self {
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Count a box handed out from the current stack. Symbols are only resolved on export.
+    #[inline(never)]
+    pub(crate) fn record(&mut self, hit: bool) {
+        let mut ips = vec![];
+        backtrace::trace(|frame| {
+            ips.push(frame.ip());
+            true
+        });
+        self.counts.entry(ips.into_boxed_slice()).or_insert([0; 2])[hit as usize] += 1;
+    }
+}
+
+/// Return the names of the functions at `ips`, root first, without the frames of the
+/// capture itself.
+fn resolve(ips: &[*mut c_void]) -> Vec<String> {
+    let mut names = vec![];
+    for &ip in ips {
+        let mut resolved = false;
+        backtrace::resolve(ip, |symbol| {
+            resolved = true;
+            names.push(match symbol.name() {
+                Some(name) => format!("{:#}", name).replace(';', ":"),
+                None => format!("{:p}", ip),
+            });
+        });
+        if !resolved {
+            names.push(format!("{:p}", ip));
+        }
+    }
+
+    let internal = |name: &String| {
+        name.starts_with("backtrace::")
+            || name.starts_with("boxing_arena::call_stacks::CallStacks")
+            || name.ends_with("BoxingArena<T>::hand_out")
+    };
+    let start = names.iter().rposition(internal).map_or(0, |i| i + 1);
+    names.drain(..start);
+    names.reverse();
+    names
+}
+
+impl<T> BoxingArena<T> {
+    /// Write the hit and miss counts of each call stack that asked the arena for boxes, in
+    /// the folded format read by flamegraph tools: one line per stack, with the frames from
+    /// the root separated by `;`, ending with a `[hit]` or `[miss]` frame and the count.
+    pub fn write_folded_stacks(&self, w: &mut dyn Write) -> io::Result<()> {
+        for (ips, counts) in &self.call_stacks.counts {
+            let stack = resolve(ips).join(";");
+            for (kind, &count) in ["[miss]", "[hit]"].iter().zip(counts) {
+                if count > 0 {
+                    writeln!(w, "{};{} {}", stack, kind, count)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn churn(ba: &mut BoxingArena<u32>) {
+        for i in 0..3 {
+            let b = ba.rebox(i);
+            ba.unbox(b);
+        }
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let mut ba = BoxingArena::new();
+        churn(&mut ba);
+
+        let mut out = vec![];
+        ba.write_folded_stacks(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().filter(|l| l.contains("churn")).collect();
+        assert!(lines.iter().any(|l| l.ends_with(";[miss] 1")));
+        assert!(lines.iter().any(|l| l.ends_with(";[hit] 2")));
+        assert!(!out.contains("hand_out"));
+    }
+}
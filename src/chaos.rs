@@ -0,0 +1,80 @@
+//! Random refusal of reuse, enabled by the `chaos` feature.
+
+use crate::BoxingArena;
+
+/// The state of chaos mode: a probability, and a xorshift generator.
+#[derive(Clone)]
+pub(crate) struct Chaos {
+    probability: f64,
+    state: u64,
+}
+
+impl Chaos {
+    pub(crate) const fn new() -> Self {
+        Self {
+            probability: 0.0,
+            state: 1,
+        }
+    }
+
+    /// Return `true` with the configured probability.
+    pub(crate) fn roll(&mut self) -> bool {
+        if self.probability <= 0.0 {
+            return false;
+        }
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        ((self.state >> 11) as f64 / (1u64 << 53) as f64) < self.probability
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// With `probability`, make each `rebox` act as if there were no free boxes, and each
+    /// `unbox` deallocate the box instead of keeping it. This flushes out code that depends
+    /// on addresses being reused, or on the arena being warm. The choices are random but
+    /// repeatable for a given `seed`. A probability of 0, the default, disables chaos mode.
+    pub fn set_chaos(&mut self, probability: f64, seed: u64) {
+        self.chaos = Chaos {
+            probability,
+            // Xorshift gets stuck at zero.
+            state: seed | 1,
+        };
+    }
+
+    /// Return the probability set by `set_chaos`.
+    pub fn chaos_probability(&self) -> f64 {
+        self.chaos.probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chaos() {
+        let mut ba = BoxingArena::with_capacity(100);
+        ba.set_chaos(1.0, 0);
+        let b = ba.rebox(1u32);
+        assert_eq!(ba.stats().misses, 1);
+        assert!(ba.try_rebox(&mut Some(2)).is_none());
+        ba.unbox(b);
+        assert_eq!(ba.capacity(), 100);
+
+        ba.set_chaos(0.5, 42);
+        assert_eq!(ba.fork_empty().chaos_probability(), 0.5);
+        let boxes: Vec<_> = (0..100).map(|i| ba.rebox(i)).collect();
+        let misses = ba.stats().misses;
+        assert!(misses > 10 && misses < 90);
+        for b in boxes {
+            ba.unbox(b);
+        }
+        assert!(ba.capacity() < 100);
+
+        ba.set_chaos(0.0, 0);
+        let b = ba.rebox(1);
+        ba.unbox(b);
+        assert_eq!(ba.stats().misses, misses);
+    }
+}
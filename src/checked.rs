@@ -0,0 +1,169 @@
+//! Misuse detection, enabled by the `checked` feature.
+
+use std::collections::BTreeMap;
+use std::panic::Location;
+
+use crate::{provenance, BoxingArena};
+
+/// The boxes handed out by an arena and not given back yet, by address, with the location
+/// of the call that handed them out.
+pub(crate) struct Checkouts {
+    map: BTreeMap<usize, &'static Location<'static>>,
+    reject_foreign: bool,
+}
+
+impl Checkouts {
+    pub(crate) const fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            reject_foreign: false,
+        }
+    }
+
+    /// Empty, with the same settings.
+    pub(crate) fn fork_empty(&self) -> Self {
+        Self {
+            reject_foreign: self.reject_foreign,
+            ..Self::new()
+        }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Record that the box at `p` is handed out at `caller`.
+    pub(crate) fn check_out(&mut self, p: *const T, caller: &'static Location<'static>) {
+        self.checkouts.map.insert(provenance::expose(p), caller);
+    }
+
+    /// Record that the box at `p` is given back at `caller`, panicking if it is already free.
+    pub(crate) fn check_in(&mut self, p: *const T, caller: &'static Location<'static>) {
+        if self.items.iter().any(|&free| std::ptr::eq(free, p)) {
+            panic!(
+                "box at {:p} was given back to the arena while already free, at {}",
+                p, caller
+            );
+        }
+        let handed_out = self.checkouts.map.remove(&provenance::addr(p)).is_some();
+        if !handed_out && self.checkouts.reject_foreign {
+            panic!(
+                "box at {:p} was given back to the arena at {}, but was not handed out by it",
+                p, caller
+            );
+        }
+    }
+
+    /// Make the arena panic when given back a box that it did not hand out, or that it
+    /// already took back, such as a box of another arena or one made by `Box::new`. Off by
+    /// default, since giving an arena ordinary boxes is allowed.
+    pub fn set_reject_foreign(&mut self, reject: bool) {
+        self.checkouts.reject_foreign = reject;
+    }
+
+    /// Return whether the arena panics when given back a box that it did not hand out.
+    pub fn rejects_foreign(&self) -> bool {
+        self.checkouts.reject_foreign
+    }
+
+    /// Verify the consistency of the arena's internal state, panicking with the details of
+    /// the first problem found: free boxes that are null, misaligned for `T`, listed twice,
+    /// or also recorded as handed out, and a free list out of its configured order. This is
+    /// meant for debugging code that hands raw boxes to and from the arena.
+    pub fn check_invariants(&self) {
+        let align = std::mem::align_of::<T>();
+        let mut seen = BTreeMap::new();
+        for (i, &p) in self.items.iter().enumerate() {
+            assert!(!p.is_null(), "free box #{} is null", i);
+            assert!(
+                provenance::addr(p).is_multiple_of(align),
+                "free box #{} at {:p} is not aligned to {} bytes",
+                i,
+                p,
+                align
+            );
+            if let Some(j) = seen.insert(provenance::addr(p), i) {
+                panic!("free box at {:p} is listed twice, as #{} and #{}", p, j, i);
+            }
+            if let Some(loc) = self.checkouts.map.get(&provenance::addr(p)) {
+                panic!("free box #{} at {:p} is also handed out, at {}", i, p, loc);
+            }
+        }
+        self.items.check_order();
+    }
+
+    /// Iterate over the boxes handed out by the arena that were not given back to it yet,
+    /// with the location of the call that handed each of them out. Boxes that were dropped
+    /// instead of given back show up here.
+    pub fn checkouts(&self) -> impl Iterator<Item = (*const T, &'static Location<'static>)> + '_ {
+        self.checkouts.map.iter().map(|(&p, &loc)| (provenance::from_exposed::<T>(p) as *const T, loc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkouts() {
+        let mut ba = BoxingArena::new();
+        let line = line!() + 1;
+        let a = ba.rebox(1u32);
+        let b = ba.rebox(2u32);
+
+        let checkouts: Vec<_> = ba.checkouts().collect();
+        assert_eq!(checkouts.len(), 2);
+        let (p, loc) = checkouts.iter().find(|(p, _)| std::ptr::eq(*p, &*a)).unwrap();
+        assert_eq!(*p, &*a as *const u32);
+        assert_eq!((loc.file(), loc.line()), (file!(), line));
+
+        ba.unbox(a);
+        drop(b);
+        assert_eq!(ba.checkouts().count(), 1);
+    }
+
+    #[test]
+    fn invariants() {
+        let mut ba = BoxingArena::with_capacity(4);
+        ba.set_address_ordered(true);
+        ba.set_hot_capacity(2);
+        let a = ba.rebox(1u64);
+        ba.check_invariants();
+        ba.unbox(a);
+        ba.check_invariants();
+    }
+
+    #[test]
+    #[should_panic(expected = "is listed twice")]
+    fn invariants_duplicate() {
+        let mut ba = BoxingArena::<u64>::with_capacity(1);
+        let p = *ba.items.iter().next().unwrap();
+        ba.items.push(p);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ba.check_invariants()));
+        // Removed, so that the box is not freed twice.
+        ba.items.pop();
+        std::panic::resume_unwind(r.unwrap_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "was not handed out by it")]
+    #[cfg_attr(feature = "no-panic", ignore = "aborts with no-panic")]
+    fn foreign_box() {
+        let mut a = BoxingArena::new();
+        let mut b = BoxingArena::new();
+        b.set_reject_foreign(true);
+        let v = b.rebox(1u32);
+        b.unbox(v);
+        b.unbox(a.rebox(2u32));
+    }
+
+    #[test]
+    #[should_panic(expected = "while already free")]
+    #[cfg_attr(feature = "no-panic", ignore = "aborts with no-panic")]
+    fn double_return() {
+        let mut ba = BoxingArena::new();
+        let a = Box::into_raw(ba.rebox(1u32));
+        unsafe {
+            ba.unbox(Box::from_raw(a));
+            ba.unbox(Box::from_raw(a));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Restoring the number of free boxes after work that temporarily needs more.
+
+use crate::BoxingArena;
+
+/// The number of free boxes of an arena at some point, as returned by
+/// `BoxingArena::checkpoint`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArenaCheckpoint {
+    capacity: usize,
+}
+
+impl ArenaCheckpoint {
+    /// Return the number of free boxes at the checkpoint.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Note the current number of free boxes, for `rollback`.
+    pub fn checkpoint(&self) -> ArenaCheckpoint {
+        ArenaCheckpoint {
+            capacity: self.items.len(),
+        }
+    }
+
+    /// Trim the free boxes back to their number at the checkpoint, releasing those that work
+    /// since then added, such as a request handler that inflated the pool. The arena never
+    /// grows back to it.
+    pub fn rollback(&mut self, checkpoint: ArenaCheckpoint) {
+        self.trim(checkpoint.capacity)
+    }
+
+    /// Run `f` on the arena, and then roll back to the number of free boxes from before.
+    pub fn scope<R, F: FnOnce(&mut Self) -> R>(&mut self, f: F) -> R {
+        let checkpoint = self.checkpoint();
+        let r = f(self);
+        self.rollback(checkpoint);
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope() {
+        let mut ba = BoxingArena::<u64>::with_capacity(2);
+        let cp = ba.checkpoint();
+        let sum = ba.scope(|ba| {
+            let boxes: Vec<_> = (0..5).map(|i| ba.rebox(i)).collect();
+            boxes.into_iter().map(|b| ba.unbox(b)).sum::<u64>()
+        });
+        assert_eq!((sum, ba.capacity()), (10, 2));
+
+        ba.resize_capacity(1);
+        ba.rollback(cp);
+        assert_eq!((ba.capacity(), cp.capacity()), (1, 2));
+    }
+}
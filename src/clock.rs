@@ -0,0 +1,21 @@
+//! Access to the monotonic clock, which some targets lack.
+
+use std::time::Instant;
+
+/// Whether `Instant::now` is unsupported, and panics. This is the case of wasm32 in the
+/// browser, where the standard library has no access to a clock.
+const NO_CLOCK: bool = cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// Return the current time, or `None` if the target has no clock.
+pub(crate) fn now() -> Option<Instant> {
+    if NO_CLOCK {
+        None
+    } else {
+        Some(Instant::now())
+    }
+}
+
+/// Return the nanoseconds elapsed since `start`, or 0 without a clock.
+pub(crate) fn elapsed_ns(start: Option<Instant>) -> u64 {
+    start.map_or(0, |start| start.elapsed().as_nanos() as u64)
+}
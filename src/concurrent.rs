@@ -0,0 +1,470 @@
+//! An arena shared by threads without locking.
+//!
+//! Boxes can be given back without any read-modify-write through return lanes, see
+//! `ConcurrentBoxingArena::with_return_lanes`, and are reclaimed later by another thread.
+//! This is the deferred reclamation of epoch-based schemes, such as `crossbeam-epoch`, or
+//! QSBR, without their grace period: a grace period makes sure that no thread still reads
+//! memory before it is reused, but a box given back by value has no other reader, since
+//! the borrow checker ends every reference into it beforehand. Data structures that unlink
+//! nodes under concurrent readers still need such a scheme, to decide when a node can be
+//! turned back into a `Box`, after which it can be given back through a lane.
+
+use alloc::alloc::Layout;
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+
+use crate::sync::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize};
+use crate::{Pool, SharedPool};
+
+/// The index of no node, ending a stack.
+const NIL: u32 = u32::MAX;
+
+struct Node<T> {
+    next: AtomicU32,
+    ptr: AtomicPtr<T>,
+}
+
+/// A Treiber stack of node indices. The head is tagged with a counter bumped on every
+/// change, so that a node popped and pushed back in between does not fool a `pop`.
+struct Stack {
+    head: AtomicU64,
+}
+
+impl Stack {
+    fn new() -> Self {
+        Self {
+            head: AtomicU64::new(NIL as u64),
+        }
+    }
+
+    fn head(index: u32, prev: u64) -> u64 {
+        (((prev >> 32) as u32).wrapping_add(1) as u64) << 32 | index as u64
+    }
+
+    fn push<T>(&self, nodes: &[Node<T>], index: u32) {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            nodes[index as usize].next.store(head as u32, Ordering::Relaxed);
+            let new = Self::head(index, head);
+            match self.head.compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    fn pop<T>(&self, nodes: &[Node<T>]) -> Option<u32> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let index = head as u32;
+            if index == NIL {
+                return None;
+            }
+            // Nodes are never freed, so this is at worst stale, and then the exchange fails.
+            let next = nodes[index as usize].next.load(Ordering::Relaxed);
+            let new = Self::head(next, head);
+            match self.head.compare_exchange_weak(head, new, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => return Some(index),
+                Err(h) => head = h,
+            }
+        }
+    }
+}
+
+/// A ring of boxes given back through a `ReturnLane`, written by the thread holding the lane
+/// and read by whichever thread reclaims the boxes.
+struct Lane<T> {
+    slots: Box<[AtomicPtr<T>]>,
+    /// The counts of boxes ever written and read, only changed by the writer and the reader
+    /// respectively.
+    written: AtomicUsize,
+    read: AtomicUsize,
+    claimed: AtomicBool,
+    reading: AtomicBool,
+}
+
+impl<T> Lane<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| AtomicPtr::new(core::ptr::null_mut())).collect(),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            claimed: AtomicBool::new(false),
+            reading: AtomicBool::new(false),
+        }
+    }
+
+    /// Write a box, or give it back if the ring is full.
+    fn write(&self, p: *mut T) -> Result<(), *mut T> {
+        let written = self.written.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        if written.wrapping_sub(read) == self.slots.len() {
+            return Err(p);
+        }
+        self.slots[written % self.slots.len()].store(p, Ordering::Relaxed);
+        self.written.store(written.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Read the boxes written so far, unless another thread is reading them.
+    fn read(&self, mut f: impl FnMut(*mut T)) {
+        if self.reading.swap(true, Ordering::Acquire) {
+            return;
+        }
+        let mut read = self.read.load(Ordering::Relaxed);
+        let written = self.written.load(Ordering::Acquire);
+        while read != written {
+            f(self.slots[read % self.slots.len()].load(Ordering::Relaxed));
+            read = read.wrapping_add(1);
+            self.read.store(read, Ordering::Release);
+        }
+        self.reading.store(false, Ordering::Release);
+    }
+}
+
+/// A `BoxingArena` that threads share through `&self`, without locking.
+///
+/// The free boxes are kept in lock-free stacks over a fixed number of nodes, given when
+/// creating the arena: this is the most free boxes it holds, and boxes given back beyond
+/// that are deallocated. Unlike `BoxingArena`, it does not keep statistics, call stacks or
+/// logs, which would need synchronization.
+///
+/// Giving a box back takes a compare-and-swap, which retries while other threads contend for
+/// the stack. For threads that cannot afford that, such as latency-critical readers, the
+/// arena can also be created with return lanes, see `with_return_lanes`.
+pub struct ConcurrentBoxingArena<T> {
+    nodes: Box<[Node<T>]>,
+    /// Nodes holding a free box.
+    free: Stack,
+    /// Nodes without one.
+    spare: Stack,
+    len: AtomicUsize,
+    lanes: Box<[Lane<T>]>,
+}
+
+// The arena owns the free allocations it points to, and hands them to any thread.
+unsafe impl<T: Send> Send for ConcurrentBoxingArena<T> {}
+unsafe impl<T: Send> Sync for ConcurrentBoxingArena<T> {}
+
+impl<T> ConcurrentBoxingArena<T> {
+    /// Create an arena holding at most `max_capacity` free boxes, which must be less than
+    /// `u32::MAX`.
+    pub fn new(max_capacity: usize) -> Self {
+        Self::with_return_lanes(max_capacity, 0, 0)
+    }
+
+    /// Create an arena holding at most `max_capacity` free boxes, and `lanes` return lanes of
+    /// `lane_capacity` boxes each, see `return_lane`.
+    pub fn with_return_lanes(max_capacity: usize, lanes: usize, lane_capacity: usize) -> Self {
+        assert!(max_capacity < NIL as usize, "max_capacity is too large");
+        let nodes: Box<[Node<T>]> = (0..max_capacity)
+            .map(|_| Node {
+                next: AtomicU32::new(NIL),
+                ptr: AtomicPtr::new(core::ptr::null_mut()),
+            })
+            .collect();
+
+        let arena = Self {
+            nodes,
+            free: Stack::new(),
+            spare: Stack::new(),
+            len: AtomicUsize::new(0),
+            lanes: (0..lanes).map(|_| Lane::new(lane_capacity)).collect(),
+        };
+        for index in 0..max_capacity as u32 {
+            arena.spare.push(&arena.nodes, index);
+        }
+        arena
+    }
+
+    fn pop_free(&self) -> Option<*mut T> {
+        let index = self.free.pop(&self.nodes)?;
+        let p = self.nodes[index as usize].ptr.load(Ordering::Relaxed);
+        self.spare.push(&self.nodes, index);
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(p)
+    }
+
+    /// Box a value, reusing a free box if there is one, or else one given back through a
+    /// return lane.
+    pub fn rebox(&self, v: T) -> Box<T> {
+        let p = match self.pop_free() {
+            None if self.reclaim() > 0 => self.pop_free(),
+            p => p,
+        };
+        match p {
+            None => Box::new(v),
+            Some(p) => unsafe {
+                core::ptr::write(p, v);
+                Box::from_raw(p)
+            },
+        }
+    }
+
+    /// Take the value out of the box, keeping the allocation if there is room for it.
+    pub fn unbox(&self, b: Box<T>) -> T {
+        let p = Box::into_raw(b);
+        let v = unsafe { core::ptr::read(p) };
+        #[cfg(feature = "zeroize")]
+        unsafe {
+            crate::wipe::wipe(p)
+        };
+        self.keep(p);
+        v
+    }
+
+    /// Keep the allocation at `p`, whose value is gone, if there is room for it.
+    fn keep(&self, p: *mut T) {
+        match self.spare.pop(&self.nodes) {
+            Some(index) => {
+                self.nodes[index as usize].ptr.store(p, Ordering::Relaxed);
+                self.free.push(&self.nodes, index);
+                self.len.fetch_add(1, Ordering::Relaxed);
+            }
+            None => unsafe { Self::dealloc_slot(p) },
+        }
+    }
+
+    /// Claim one of the return lanes of the arena, or return `None` if they are all claimed.
+    /// The lane is released when dropped.
+    pub fn return_lane(&self) -> Option<ReturnLane<'_, T>> {
+        let lane = self.lanes.iter().find(|lane| !lane.claimed.swap(true, Ordering::Acquire))?;
+        Some(ReturnLane {
+            arena: self,
+            lane,
+            _not_sync: PhantomData,
+        })
+    }
+
+    /// Keep the boxes given back through the return lanes since the last call, as far as
+    /// there is room for them, and return how many were given back. `rebox` calls this when
+    /// there is no free box, so the boxes given back through lanes are reclaimed lazily,
+    /// unless this is called periodically.
+    pub fn reclaim(&self) -> usize {
+        let mut count = 0;
+        for lane in self.lanes.iter() {
+            lane.read(|p| {
+                self.keep(p);
+                count += 1;
+            });
+        }
+        count
+    }
+
+    /// Release free boxes so that at most `size` of them remain, unless other threads give
+    /// boxes back meanwhile.
+    pub fn trim(&self, size: usize) {
+        while self.capacity() > size {
+            match self.pop_free() {
+                Some(p) => unsafe { Self::dealloc_slot(p) },
+                None => break,
+            }
+        }
+    }
+
+    /// Return the number of free boxes, which other threads may be changing.
+    pub fn capacity(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Return the most free boxes the arena holds.
+    pub fn max_capacity(&self) -> usize {
+        self.nodes.len()
+    }
+
+    unsafe fn dealloc_slot(p: *mut T) {
+        let layout = Layout::new::<T>();
+        if layout.size() != 0 {
+            alloc::alloc::dealloc(p as *mut u8, layout);
+        }
+    }
+}
+
+impl<T> Pool<T> for ConcurrentBoxingArena<T> {
+    fn rebox(&mut self, v: T) -> Box<T> {
+        ConcurrentBoxingArena::rebox(self, v)
+    }
+
+    fn unbox(&mut self, b: Box<T>) -> T {
+        ConcurrentBoxingArena::unbox(self, b)
+    }
+
+    fn trim(&mut self, size: usize) {
+        ConcurrentBoxingArena::trim(self, size)
+    }
+
+    fn capacity(&self) -> usize {
+        ConcurrentBoxingArena::capacity(self)
+    }
+}
+
+impl<T> SharedPool<T> for ConcurrentBoxingArena<T> {
+    fn rebox(&self, v: T) -> Box<T> {
+        ConcurrentBoxingArena::rebox(self, v)
+    }
+
+    fn unbox(&self, b: Box<T>) -> T {
+        ConcurrentBoxingArena::unbox(self, b)
+    }
+}
+
+/// A handle for giving boxes back to a `ConcurrentBoxingArena` from one thread at a time,
+/// as returned by `ConcurrentBoxingArena::return_lane`. Giving a box back through a lane
+/// takes no compare-and-swap, nor any other read-modify-write: the box is written to a ring
+/// that only this lane writes to, and the arena reclaims it later, see `reclaim`. When the
+/// ring is full, the box is deallocated.
+pub struct ReturnLane<'a, T> {
+    arena: &'a ConcurrentBoxingArena<T>,
+    lane: &'a Lane<T>,
+    /// A lane has a single writer.
+    _not_sync: PhantomData<core::cell::Cell<()>>,
+}
+
+impl<'a, T> ReturnLane<'a, T> {
+    /// Take the value out of the box, and give the allocation back to the arena.
+    pub fn unbox(&self, b: Box<T>) -> T {
+        let p = Box::into_raw(b);
+        let v = unsafe { core::ptr::read(p) };
+        #[cfg(feature = "zeroize")]
+        unsafe {
+            crate::wipe::wipe(p)
+        };
+        if let Err(p) = self.lane.write(p) {
+            unsafe { ConcurrentBoxingArena::dealloc_slot(p) }
+        }
+        v
+    }
+
+    /// Return the arena of the lane.
+    pub fn arena(&self) -> &'a ConcurrentBoxingArena<T> {
+        self.arena
+    }
+}
+
+impl<'a, T> Drop for ReturnLane<'a, T> {
+    fn drop(&mut self) {
+        self.lane.claimed.store(false, Ordering::Release);
+    }
+}
+
+impl<T> Drop for ConcurrentBoxingArena<T> {
+    fn drop(&mut self) {
+        for lane in self.lanes.iter() {
+            lane.read(|p| unsafe { Self::dealloc_slot(p) });
+        }
+        while let Some(p) = self.pop_free() {
+            unsafe { Self::dealloc_slot(p) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "loom"))]
+    #[test]
+    fn concurrent() {
+        let arena = ConcurrentBoxingArena::new(2);
+        let a = arena.rebox(1u64);
+        let b = arena.rebox(2u64);
+        let c = arena.rebox(3u64);
+        let addr = &*c as *const u64;
+        arena.unbox(a);
+        arena.unbox(b);
+        // No room left.
+        arena.unbox(c);
+        assert_eq!(arena.capacity(), 2);
+
+        let d = arena.rebox(4);
+        assert_ne!(&*d as *const u64, addr);
+        arena.trim(0);
+        assert_eq!(arena.capacity(), 0);
+    }
+
+    #[cfg(not(feature = "loom"))]
+    #[test]
+    fn threads() {
+        let arena = ConcurrentBoxingArena::new(64);
+        std::thread::scope(|s| {
+            for t in 0..4u64 {
+                let arena = &arena;
+                s.spawn(move || {
+                    for i in 0..10_000 {
+                        let b = arena.rebox([t, i]);
+                        assert_eq!(arena.unbox(b), [t, i]);
+                    }
+                });
+            }
+        });
+        assert!(arena.capacity() <= 4);
+    }
+
+    #[cfg(not(feature = "loom"))]
+    #[test]
+    fn return_lanes() {
+        let arena = ConcurrentBoxingArena::with_return_lanes(4, 1, 2);
+        let lane = arena.return_lane().unwrap();
+        assert!(arena.return_lane().is_none());
+        let boxes: Vec<_> = (0..3u64).map(|i| arena.rebox(i)).collect();
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for (i, b) in boxes.into_iter().enumerate() {
+                    assert_eq!(lane.unbox(b), i as u64);
+                }
+            });
+        });
+        assert!(arena.return_lane().is_some());
+        // The third box did not fit in the lane.
+        assert_eq!(arena.capacity(), 0);
+        arena.rebox(3);
+        assert_eq!(arena.capacity(), 1);
+    }
+
+    #[cfg(feature = "loom")]
+    #[test]
+    fn loom() {
+        use loom::sync::Arc;
+
+        loom::model(|| {
+            let arena = Arc::new(ConcurrentBoxingArena::new(1));
+            arena.unbox(Box::new(0u64));
+            let threads: Vec<_> = (1..3u64)
+                .map(|t| {
+                    let arena = arena.clone();
+                    loom::thread::spawn(move || {
+                        let b = arena.rebox(t);
+                        assert_eq!(arena.unbox(b), t);
+                    })
+                })
+                .collect();
+            for t in threads {
+                t.join().unwrap();
+            }
+            assert_eq!(arena.capacity(), 1);
+        });
+    }
+
+    #[cfg(feature = "loom")]
+    #[test]
+    fn loom_return_lanes() {
+        use loom::sync::Arc;
+
+        loom::model(|| {
+            let arena = Arc::new(ConcurrentBoxingArena::with_return_lanes(2, 1, 1));
+            let b = arena.rebox(1u64);
+            let thread = {
+                let arena = arena.clone();
+                loom::thread::spawn(move || {
+                    let lane = arena.return_lane().unwrap();
+                    assert_eq!(lane.unbox(b), 1);
+                })
+            };
+            let reclaimed = arena.reclaim();
+            thread.join().unwrap();
+            assert_eq!(reclaimed + arena.reclaim(), 1);
+            assert_eq!(arena.capacity(), 1);
+        });
+    }
+}
@@ -0,0 +1,125 @@
+//! Arenas of `Arc` and `Rc` allocations.
+
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+use alloc::{rc::Rc, vec::Vec};
+use core::mem::MaybeUninit;
+
+/// Define an arena recycling the allocations of the reference-counted pointer `$ptr`, with
+/// the given doc comment, and a test named `$test`.
+macro_rules! counted_arena {
+    ($(#[$attr:meta])* $name:ident, $ptr:ident, $test:ident) => {
+        $(#[$attr])*
+        pub struct $name<T> {
+            items: Vec<$ptr<MaybeUninit<T>>>,
+        }
+
+        impl<T> $name<T> {
+            /// Create an empty arena. No allocation is made by this function.
+            pub fn new() -> Self {
+                Self { items: Vec::new() }
+            }
+
+            /// Create an arena with `size` free allocations.
+            pub fn with_capacity(size: usize) -> Self {
+                let mut arena = Self::new();
+                arena.resize_capacity(size);
+                arena
+            }
+
+            #[doc = concat!("Wrap a value in an `", stringify!($ptr), "`, reusing a free ")]
+            /// allocation if there is one.
+            pub fn rebox(&mut self, v: T) -> $ptr<T> {
+                let mut slot = match self.items.pop() {
+                    Some(slot) => slot,
+                    None => return $ptr::new(v),
+                };
+                match $ptr::get_mut(&mut slot) {
+                    // The counts of a free allocation are back at one.
+                    Some(uninit) => {
+                        uninit.write(v);
+                        unsafe { $ptr::from_raw($ptr::into_raw(slot) as *const T) }
+                    }
+                    None => $ptr::new(v),
+                }
+            }
+
+            /// Take the value out of `a` and keep its allocation, if `a` is the only reference
+            /// to it, strong or weak. Otherwise, give it back in `Err`.
+            pub fn unbox(&mut self, mut a: $ptr<T>) -> Result<T, $ptr<T>> {
+                if $ptr::get_mut(&mut a).is_none() {
+                    return Err(a);
+                }
+                unsafe {
+                    let p = $ptr::into_raw(a);
+                    let v = p.read();
+                    // `MaybeUninit<T>` has the layout of `T`.
+                    self.items.push($ptr::from_raw(p as *const MaybeUninit<T>));
+                    Ok(v)
+                }
+            }
+
+            /// Return the number of free allocations.
+            pub fn capacity(&self) -> usize {
+                self.items.len()
+            }
+
+            /// Allocate or free allocations so that `size` are free.
+            pub fn resize_capacity(&mut self, size: usize) {
+                self.items.truncate(size);
+                let n = self.items.len();
+                self.items.extend((n..size).map(|_| $ptr::new(MaybeUninit::uninit())));
+            }
+
+            /// Trims capacity to the given size if it is larger.
+            pub fn trim(&mut self, size: usize) {
+                self.items.truncate(size);
+            }
+        }
+
+        impl<T> Default for $name<T> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        #[cfg(test)]
+        #[test]
+        fn $test() {
+            let mut arena = $name::new();
+            let a = arena.rebox(alloc::string::String::from("a"));
+            let addr = $ptr::as_ptr(&a);
+            let b = a.clone();
+            let a = arena.unbox(a).unwrap_err();
+            drop(b);
+            assert_eq!(arena.unbox(a).unwrap(), "a");
+            assert_eq!(arena.capacity(), 1);
+
+            let c = arena.rebox(alloc::string::String::from("c"));
+            assert_eq!(($ptr::as_ptr(&c), $ptr::strong_count(&c)), (addr, 1));
+            let weak = $ptr::downgrade(&c);
+            let c = arena.unbox(c).unwrap_err();
+            drop(weak);
+            assert_eq!(arena.unbox(c).unwrap(), "c");
+        }
+    };
+}
+
+#[cfg(target_has_atomic = "ptr")]
+counted_arena!(
+    /// An arena that recycles the allocations of `Arc<T>`s, counts included, for code that
+    /// churns through shared values. An `Arc` is given back by `unbox` once it is the last
+    /// reference to its value, and its allocation is then reused by `rebox` for another value.
+    ArcArena,
+    Arc,
+    arc_arena
+);
+
+counted_arena!(
+    /// An arena that recycles the allocations of `Rc<T>`s, counts included, like `ArcArena`
+    /// does for `Arc<T>`s, for single-threaded code such as scene graphs churning through
+    /// nodes.
+    RcArena,
+    Rc,
+    rc_arena
+);
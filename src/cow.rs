@@ -0,0 +1,153 @@
+//! A shared, copy-on-write pointer whose allocations come from an arena.
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+use crate::BoxingArena;
+
+/// The allocation shared by clones of a `PooledCow`.
+pub struct CowSlot<T> {
+    refs: Cell<usize>,
+    value: T,
+}
+
+/// An arena providing the allocations of `PooledCow<T>`.
+pub type CowArena<T> = BoxingArena<CowSlot<T>>;
+
+/// A reference-counted pointer whose value is shared by clones until one of them is written
+/// to through `PooledCow::make_mut`. The writable copy's allocation is taken from a
+/// `CowArena`, and `PooledCow::recycle` gives retired values back to it.
+///
+/// Like `Rc`, it is meant for use within a single thread.
+pub struct PooledCow<T> {
+    ptr: NonNull<CowSlot<T>>,
+    _marker: PhantomData<CowSlot<T>>,
+}
+
+impl<T> PooledCow<T> {
+    /// Store `v` in a box from `arena`.
+    #[track_caller]
+    pub fn new_in(v: T, arena: &mut CowArena<T>) -> Self {
+        let slot = arena.rebox(CowSlot {
+            refs: Cell::new(1),
+            value: v,
+        });
+        Self {
+            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(slot)) },
+            _marker: PhantomData,
+        }
+    }
+
+    fn slot(&self) -> &CowSlot<T> {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Return whether no other clone shares the value.
+    pub fn is_unique(this: &Self) -> bool {
+        this.slot().refs.get() == 1
+    }
+
+    /// Return a mutable reference to the value, first copying it to a box from `arena` if it
+    /// is shared with other clones.
+    #[track_caller]
+    pub fn make_mut<'a>(this: &'a mut Self, arena: &mut CowArena<T>) -> &'a mut T
+    where
+        T: Clone,
+    {
+        if !Self::is_unique(this) {
+            *this = Self::new_in(this.slot().value.clone(), arena);
+        }
+        unsafe { &mut (*this.ptr.as_ptr()).value }
+    }
+
+    /// Take the value out if this is the last clone, giving its allocation to `arena`.
+    #[track_caller]
+    pub fn try_unwrap(this: Self, arena: &mut CowArena<T>) -> Result<T, Self> {
+        if !Self::is_unique(&this) {
+            return Err(this);
+        }
+        let this = std::mem::ManuallyDrop::new(this);
+        let slot = unsafe { Box::from_raw(this.ptr.as_ptr()) };
+        Ok(arena.unbox(slot).value)
+    }
+
+    /// Drop this clone. If it was the last one, drop the value and give its allocation to
+    /// `arena`.
+    #[track_caller]
+    pub fn recycle(this: Self, arena: &mut CowArena<T>) {
+        let _ = Self::try_unwrap(this, arena);
+    }
+}
+
+impl<T> Clone for PooledCow<T> {
+    fn clone(&self) -> Self {
+        let refs = &self.slot().refs;
+        match refs.get().checked_add(1) {
+            Some(n) => refs.set(n),
+            None => std::process::abort(),
+        }
+        Self {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for PooledCow<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.slot().value
+    }
+}
+
+impl_borrow!([T] PooledCow<T>);
+impl_cmp!([T] PooledCow<T>);
+
+// Like `Rc`, all the clones point to the same box, which only `make_mut` may replace.
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::StableDeref for PooledCow<T> {}
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::CloneStableDeref for PooledCow<T> {}
+
+impl<T> Drop for PooledCow<T> {
+    fn drop(&mut self) {
+        let refs = &self.slot().refs;
+        refs.set(refs.get() - 1);
+        if refs.get() == 0 {
+            unsafe { drop(Box::from_raw(self.ptr.as_ptr())) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cow() {
+        let mut arena = CowArena::new();
+        let a = PooledCow::new_in(vec![1, 2], &mut arena);
+        let mut b = a.clone();
+        assert!(!PooledCow::is_unique(&a));
+
+        PooledCow::make_mut(&mut b, &mut arena).push(3);
+        assert_eq!(*a, [1, 2]);
+        assert_eq!(*b, [1, 2, 3]);
+        assert!(PooledCow::is_unique(&a));
+
+        // Retire the old snapshot, the next copy reuses its allocation.
+        let a_addr = &*a as *const Vec<i32>;
+        PooledCow::recycle(a, &mut arena);
+        assert_eq!(arena.capacity(), 1);
+        let c = b.clone();
+        PooledCow::make_mut(&mut b, &mut arena).push(4);
+        assert_eq!(&*b as *const Vec<i32>, a_addr);
+
+        assert!(PooledCow::try_unwrap(c.clone(), &mut arena).is_err());
+        assert_eq!(PooledCow::try_unwrap(c, &mut arena).ok(), Some(vec![1, 2, 3]));
+        assert_eq!(arena.capacity(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+//! Process-wide registration of arenas whose stats are dumped for post-mortem debugging.
+//!
+//! Arenas shared between threads through `Arc<Mutex<BoxingArena<T>>>` can be registered by
+//! name, and their stats are then written to stderr by the panic hook installed with
+//! `install_panic_hook`, or on demand with `dump_stats`. With the `signal-dump` feature on
+//! unix, `dump_on_signal` writes them whenever the process receives a given signal. The
+//! arenas registered with `registry::register` are in the same registry.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, Weak};
+
+#[cfg(feature = "registry")]
+use crate::registry::Managed;
+use crate::{ArenaStats, BoxingArena, SyncBoxingArena};
+
+/// A shared arena that can report its stats from any thread.
+pub trait StatsSource: Send + Sync {
+    /// Return the arena's stats, or `None` if they cannot be obtained right now, for example
+    /// because the arena is locked.
+    fn stats(&self) -> Option<ArenaStats>;
+}
+
+impl<T: Send> StatsSource for Mutex<BoxingArena<T>> {
+    fn stats(&self) -> Option<ArenaStats> {
+        // Never block: this may run from a panic hook while the arena is locked.
+        self.try_lock().ok().map(|arena| arena.stats())
+    }
+}
+
+impl<T: Send> StatsSource for SyncBoxingArena<T> {
+    fn stats(&self) -> Option<ArenaStats> {
+        self.try_lock().map(|arena| arena.stats())
+    }
+}
+
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    pub(crate) source: Weak<dyn StatsSource>,
+    /// The same arena, if registered with `registry::register`.
+    #[cfg(feature = "registry")]
+    pub(crate) managed: Option<Weak<dyn Managed>>,
+}
+
+pub(crate) static REGISTRY: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+pub(crate) fn push(entry: Entry) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.retain(|e| e.source.strong_count() > 0);
+    registry.push(entry);
+}
+
+/// Register an arena under `name`. The registry does not keep the arena alive, and dropped
+/// arenas are forgotten.
+pub fn register<S: StatsSource + 'static>(name: impl Into<String>, source: &Arc<S>) {
+    let source: Arc<dyn StatsSource> = source.clone();
+    push(Entry {
+        name: name.into(),
+        source: Arc::downgrade(&source),
+        #[cfg(feature = "registry")]
+        managed: None,
+    });
+}
+
+/// Write one line of stats for each registered arena.
+pub fn dump_stats(w: &mut dyn Write) -> io::Result<()> {
+    let registry = match REGISTRY.try_lock() {
+        Ok(registry) => registry,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => {
+            return writeln!(w, "boxing-arena: registry busy, no stats");
+        }
+    };
+
+    for entry in registry.iter() {
+        let source = match entry.source.upgrade() {
+            Some(source) => source,
+            None => continue,
+        };
+        match source.stats() {
+            Some(s) => writeln!(
+                w,
+                "boxing-arena: {}: capacity={} hits={} misses={} unboxes={} outstanding={}",
+                entry.name,
+                s.capacity,
+                s.hits,
+                s.misses,
+                s.unboxes,
+                s.outstanding()
+            )?,
+            None => writeln!(w, "boxing-arena: {}: busy", entry.name)?,
+        }
+    }
+    Ok(())
+}
+
+/// Install a panic hook that dumps the stats of all registered arenas to stderr, and then
+/// runs the previously installed hook.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = dump_stats(&mut io::stderr());
+        previous(info);
+    }));
+}
+
+/// Spawn a thread that dumps the stats of all registered arenas whenever the process
+/// receives `signal`, for example `signal_hook::consts::SIGUSR1`. The stats are appended to
+/// the file at `path` if given, and written to stderr otherwise.
+#[cfg(all(unix, feature = "signal-dump"))]
+pub fn dump_on_signal(signal: i32, path: Option<std::path::PathBuf>) -> io::Result<()> {
+    let mut signals = signal_hook::iterator::Signals::new([signal])?;
+
+    std::thread::Builder::new()
+        .name("boxing-arena-dump".into())
+        .spawn(move || {
+            for _ in signals.forever() {
+                let _ = match &path {
+                    Some(path) => std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)
+                        .and_then(|mut f| dump_stats(&mut f)),
+                    None => dump_stats(&mut io::stderr()),
+                };
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump() {
+        let arena = Arc::new(Mutex::new(BoxingArena::<u32>::with_capacity(3)));
+        register("diagnostics-test", &arena);
+        let b = arena.lock().unwrap().rebox(1);
+
+        let mut out = vec![];
+        dump_stats(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("diagnostics-test: capacity=2 hits=1 misses=0 unboxes=0 outstanding=1"));
+
+        // A locked arena is reported as busy instead of blocking.
+        let guard = arena.lock().unwrap();
+        let mut out = vec![];
+        dump_stats(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("diagnostics-test: busy"));
+        drop(guard);
+
+        drop(b);
+        drop(arena);
+        let mut out = vec![];
+        dump_stats(&mut out).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("diagnostics-test"));
+    }
+
+    #[cfg(all(unix, feature = "signal-dump"))]
+    #[test]
+    #[cfg_attr(miri, ignore = "signal handlers are not supported by Miri")]
+    fn signal() {
+        use signal_hook::consts::SIGUSR1;
+
+        let arena = Arc::new(Mutex::new(BoxingArena::<u32>::with_capacity(5)));
+        register("signal-test", &arena);
+
+        let path = std::env::temp_dir().join(format!("boxing-arena-{}.txt", std::process::id()));
+        dump_on_signal(SIGUSR1, Some(path.clone())).unwrap();
+        signal_hook::low_level::raise(SIGUSR1).unwrap();
+
+        let mut dumped = String::new();
+        for _ in 0..100 {
+            dumped = std::fs::read_to_string(&path).unwrap_or_default();
+            if dumped.contains("signal-test") {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let _ = std::fs::remove_file(&path);
+        assert!(dumped.contains("signal-test: capacity=5"));
+    }
+}
@@ -0,0 +1,217 @@
+//! An arena for boxes of any type, such as trait objects.
+
+use std::alloc::Layout;
+use std::collections::HashMap;
+
+/// An arena that keeps the allocations of boxes of any type, reusing them for values with the
+/// same layout. This makes it possible to pool `Box<dyn Trait>`: values are boxed with their
+/// concrete type by `rebox`, then coerced, and `recycle` drops them in place and keeps the
+/// allocation under the layout of the concrete value.
+///
+/// Likewise, several types of the same size and alignment, such as the messages of a
+/// protocol, can share one arena, since a box given back by `unbox` as one type may be
+/// reused by `rebox` as another.
+///
+/// Its memory budget counts the bytes of each free allocation from its own layout.
+#[derive(Default)]
+pub struct DynBoxingArena {
+    slots: HashMap<Layout, Vec<*mut u8>>,
+    /// Bytes held by the free allocations.
+    bytes: usize,
+    memory_budget: Option<usize>,
+}
+
+// The arena only holds allocations without values.
+unsafe impl Send for DynBoxingArena {}
+
+impl DynBoxingArena {
+    /// Create an empty arena. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Box a value, reusing a free allocation of the same layout if there is one.
+    pub fn rebox<V>(&mut self, v: V) -> Box<V> {
+        match self.slots.get_mut(&Layout::new::<V>()).and_then(Vec::pop) {
+            None => Box::new(v),
+            Some(p) => unsafe {
+                self.bytes -= std::mem::size_of::<V>();
+                let p = p as *mut V;
+                std::ptr::write(p, v);
+                Box::from_raw(p)
+            },
+        }
+    }
+
+    /// Take the value out of the box, and keep the allocation for values of the same layout.
+    pub fn unbox<V>(&mut self, b: Box<V>) -> V {
+        let layout = Layout::new::<V>();
+        let p = Box::into_raw(b);
+        let v = unsafe { std::ptr::read(p) };
+        if layout.size() == 0 {
+            // Nothing allocated.
+            return v;
+        }
+        self.keep(p as *mut u8, layout);
+        v
+    }
+
+    /// Drop the value in place, and keep the allocation for values of the same layout.
+    pub fn recycle<U: ?Sized>(&mut self, b: Box<U>) {
+        if let Some((p, layout)) = Self::drop_value(b) {
+            self.keep(p, layout);
+        }
+    }
+
+    /// Drop the value in place, and return its allocation, unless nothing was allocated.
+    pub(crate) fn drop_value<U: ?Sized>(b: Box<U>) -> Option<(*mut u8, Layout)> {
+        let layout = Layout::for_value(&*b);
+        if layout.size() == 0 {
+            // Nothing allocated.
+            return None;
+        }
+        let p = Box::into_raw(b);
+        // Freed instead of kept if dropping the value panics.
+        struct Slot(*mut u8, Layout);
+        impl Drop for Slot {
+            fn drop(&mut self) {
+                unsafe { std::alloc::dealloc(self.0, self.1) }
+            }
+        }
+        let slot = Slot(p as *mut u8, layout);
+        unsafe { std::ptr::drop_in_place(p) };
+        let ptr = slot.0;
+        std::mem::forget(slot);
+        Some((ptr, layout))
+    }
+
+    /// Keep the allocation at `p` for reuse, or free it if that would exceed the memory
+    /// budget.
+    pub(crate) fn keep(&mut self, p: *mut u8, layout: Layout) {
+        #[cfg(feature = "zeroize")]
+        unsafe {
+            crate::wipe::wipe_bytes(p, layout.size())
+        };
+        if let Some(budget) = self.memory_budget {
+            if self.bytes + layout.size() > budget {
+                unsafe { std::alloc::dealloc(p, layout) };
+                return;
+            }
+        }
+        self.bytes += layout.size();
+        self.slots.entry(layout).or_default().push(p);
+    }
+
+    /// Set the number of bytes that free allocations may hold, past which the boxes given
+    /// back are freed instead of kept, or `None` for no limit, the default.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    /// Return the limit set by `set_memory_budget`.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// Return the number of bytes held by the free allocations, of all layouts.
+    pub fn capacity_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Return the number of free allocations, of all layouts.
+    pub fn capacity(&self) -> usize {
+        self.slots.values().map(Vec::len).sum()
+    }
+
+    /// Return the number of free allocations with the given layout.
+    pub fn capacity_for(&self, layout: Layout) -> usize {
+        self.slots.get(&layout).map_or(0, Vec::len)
+    }
+
+    /// Release all free allocations.
+    pub fn clear(&mut self) {
+        for (layout, slots) in self.slots.drain() {
+            for p in slots {
+                unsafe { std::alloc::dealloc(p, layout) }
+            }
+        }
+        self.bytes = 0;
+    }
+}
+
+impl Drop for DynBoxingArena {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    trait Event {
+        fn id(&self) -> u64;
+    }
+
+    struct Small(u64);
+    struct Large([u64; 8]);
+
+    impl Event for Small {
+        fn id(&self) -> u64 {
+            self.0
+        }
+    }
+
+    impl Event for Large {
+        fn id(&self) -> u64 {
+            self.0[7]
+        }
+    }
+
+    #[test]
+    fn dyn_arena() {
+        let mut arena = DynBoxingArena::new();
+        let events: Vec<Box<dyn Event>> = vec![arena.rebox(Small(1)), arena.rebox(Large([2; 8]))];
+        assert_eq!(events.iter().map(|e| e.id()).sum::<u64>(), 3);
+        let large = &*events[1] as *const dyn Event as *const u8;
+        for e in events {
+            arena.recycle(e);
+        }
+        assert_eq!(arena.capacity(), 2);
+        assert_eq!(arena.capacity_for(Layout::new::<Large>()), 1);
+        assert_eq!(arena.capacity_bytes(), 8 + 64);
+
+        let e: Box<dyn Event> = arena.rebox(Large([3; 8]));
+        assert_eq!(&*e as *const dyn Event as *const u8, large);
+        arena.recycle(Box::new(()));
+        assert_eq!(arena.capacity(), 1);
+        drop(e);
+    }
+
+    #[test]
+    fn same_layout() {
+        struct Ping(u64, u32);
+        struct Pong(u32, u64);
+
+        let mut arena = DynBoxingArena::new();
+        let ping = arena.rebox(Ping(1, 2));
+        let addr = &*ping as *const Ping as *const u8;
+        let Ping(a, b) = arena.unbox(ping);
+        let pong = arena.rebox(Pong(b + 1, a + 3));
+        assert_eq!((pong.0, pong.1), (3, 4));
+        assert_eq!(&*pong as *const Pong as *const u8, addr);
+        assert_eq!(arena.capacity(), 0);
+    }
+
+    #[test]
+    fn memory_budget() {
+        let mut arena = DynBoxingArena::new();
+        arena.set_memory_budget(Some(100));
+        arena.recycle(Box::new([0u64; 8]));
+        arena.recycle(Box::new([0u64; 8]));
+        arena.recycle(Box::new(0u64));
+        assert_eq!((arena.capacity(), arena.capacity_bytes()), (2, 72));
+        drop(arena.rebox([1u64; 8]));
+        assert_eq!(arena.capacity_bytes(), 8);
+    }
+}
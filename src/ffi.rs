@@ -0,0 +1,346 @@
+//! A shared arena usable across dynamic libraries, and a C interface to arenas, enabled by the
+//! `ffi` feature.
+
+use std::alloc::Layout;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use crate::BoxingArena;
+
+type Shared<T> = Mutex<BoxingArena<T>>;
+
+/// The operations of an `FfiPool`, as C functions of the library that created it.
+///
+/// Each library may be built by a different compiler, with its own allocator, so the boxes
+/// are only ever allocated, handed out and freed by these functions. A panic in them, such
+/// as from the `Drop` of `T`, aborts the process.
+#[repr(C)]
+pub struct FfiPoolVtable<T> {
+    /// Move the value at `v` into a box of the pool.
+    pub rebox: unsafe extern "C" fn(pool: *const c_void, v: *mut T) -> NonNull<T>,
+    /// Move the value of the box `b` to `out`, and keep its allocation in the pool.
+    pub unbox: unsafe extern "C" fn(pool: *const c_void, b: NonNull<T>, out: *mut T),
+    /// Drop the value of the box `b`, and keep its allocation in the pool.
+    pub recycle: unsafe extern "C" fn(pool: *const c_void, b: NonNull<T>),
+    /// Return the number of free boxes in the pool.
+    pub capacity: unsafe extern "C" fn(pool: *const c_void) -> usize,
+    /// Take one more reference to the pool.
+    pub retain: unsafe extern "C" fn(pool: *const c_void),
+    /// Drop a reference to the pool.
+    pub release: unsafe extern "C" fn(pool: *const c_void),
+}
+
+/// A reference to a shared arena with a stable layout, to be passed to plugins loaded as
+/// dynamic libraries, for example as a field of an `abi_stable` interface.
+///
+/// `T` itself must have the same layout on both sides, typically by being `#[repr(C)]`.
+#[repr(C)]
+pub struct FfiPool<T: 'static> {
+    pool: NonNull<c_void>,
+    vtable: &'static FfiPoolVtable<T>,
+}
+
+// The pool behind the pointer is an `Arc<Mutex<BoxingArena<T>>>`.
+unsafe impl<T: Send> Send for FfiPool<T> {}
+unsafe impl<T: Send> Sync for FfiPool<T> {}
+
+struct Vtable<T>(PhantomData<T>);
+
+impl<T> Vtable<T> {
+    const VTABLE: FfiPoolVtable<T> = FfiPoolVtable {
+        rebox: Self::rebox,
+        unbox: Self::unbox,
+        recycle: Self::recycle,
+        capacity: Self::capacity,
+        retain: Self::retain,
+        release: Self::release,
+    };
+
+    unsafe fn arena<'a>(pool: *const c_void) -> &'a Shared<T> {
+        &*(pool as *const Shared<T>)
+    }
+
+    unsafe extern "C" fn rebox(pool: *const c_void, v: *mut T) -> NonNull<T> {
+        let v = std::ptr::read(v);
+        let b = Self::arena(pool).lock().unwrap_or_else(|e| e.into_inner()).rebox(v);
+        NonNull::new_unchecked(Box::into_raw(b))
+    }
+
+    unsafe extern "C" fn unbox(pool: *const c_void, b: NonNull<T>, out: *mut T) {
+        let b = Box::from_raw(b.as_ptr());
+        let v = Self::arena(pool).lock().unwrap_or_else(|e| e.into_inner()).unbox(b);
+        std::ptr::write(out, v);
+    }
+
+    unsafe extern "C" fn recycle(pool: *const c_void, b: NonNull<T>) {
+        let b = Box::from_raw(b.as_ptr());
+        let v = Self::arena(pool).lock().unwrap_or_else(|e| e.into_inner()).unbox(b);
+        // Outside of the lock.
+        drop(v);
+    }
+
+    unsafe extern "C" fn capacity(pool: *const c_void) -> usize {
+        Self::arena(pool).lock().unwrap_or_else(|e| e.into_inner()).capacity()
+    }
+
+    unsafe extern "C" fn retain(pool: *const c_void) {
+        Arc::increment_strong_count(pool as *const Shared<T>);
+    }
+
+    unsafe extern "C" fn release(pool: *const c_void) {
+        Arc::decrement_strong_count(pool as *const Shared<T>);
+    }
+}
+
+impl<T: 'static> FfiPool<T> {
+    /// Share `arena` through the functions of the calling library.
+    pub fn new(arena: Arc<Mutex<BoxingArena<T>>>) -> Self {
+        let pool = Arc::into_raw(arena) as *mut c_void;
+        Self {
+            pool: unsafe { NonNull::new_unchecked(pool) },
+            vtable: &Vtable::<T>::VTABLE,
+        }
+    }
+
+    /// Box a value, reusing a free box of the pool if there is one.
+    pub fn rebox(&self, v: T) -> FfiBox<T> {
+        let mut v = MaybeUninit::new(v);
+        let ptr = unsafe { (self.vtable.rebox)(self.pool.as_ptr(), v.as_mut_ptr()) };
+        FfiBox {
+            ptr,
+            pool: self.clone(),
+        }
+    }
+
+    /// Return the number of free boxes in the pool.
+    pub fn capacity(&self) -> usize {
+        unsafe { (self.vtable.capacity)(self.pool.as_ptr()) }
+    }
+}
+
+impl<T: 'static> Clone for FfiPool<T> {
+    fn clone(&self) -> Self {
+        unsafe { (self.vtable.retain)(self.pool.as_ptr()) };
+        Self {
+            pool: self.pool,
+            vtable: self.vtable,
+        }
+    }
+}
+
+impl<T: 'static> Drop for FfiPool<T> {
+    fn drop(&mut self) {
+        unsafe { (self.vtable.release)(self.pool.as_ptr()) }
+    }
+}
+
+/// A box from an `FfiPool`, which goes back to the pool when dropped.
+#[repr(C)]
+pub struct FfiBox<T: 'static> {
+    ptr: NonNull<T>,
+    pool: FfiPool<T>,
+}
+
+unsafe impl<T: Send> Send for FfiBox<T> {}
+unsafe impl<T: Sync> Sync for FfiBox<T> {}
+
+impl<T: 'static> FfiBox<T> {
+    /// Take the value out, giving the box back to the pool.
+    pub fn into_inner(self) -> T {
+        let this = std::mem::ManuallyDrop::new(self);
+        let pool = unsafe { std::ptr::read(&this.pool) };
+        let mut out = MaybeUninit::uninit();
+        unsafe {
+            (pool.vtable.unbox)(pool.pool.as_ptr(), this.ptr, out.as_mut_ptr());
+            out.assume_init()
+        }
+    }
+}
+
+impl<T: 'static> Deref for FfiBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: 'static> DerefMut for FfiBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl_borrow!([T: 'static] FfiBox<T>, mut);
+impl_cmp!([T: 'static] FfiBox<T>);
+
+// The value is in a box of the pool, which stays in place until the `FfiBox` is dropped.
+#[cfg(feature = "stable-deref")]
+unsafe impl<T: 'static> stable_deref_trait::StableDeref for FfiBox<T> {}
+
+impl<T: 'static> Drop for FfiBox<T> {
+    fn drop(&mut self) {
+        unsafe { (self.pool.vtable.recycle)(self.pool.pool.as_ptr(), self.ptr) }
+    }
+}
+
+/// An arena of allocations of one size and alignment, for C and C++ code, through the
+/// `boxing_arena_*` functions. Its operations lock it, so that it can be used from any thread.
+pub struct ErasedArena {
+    /// The size of the values, which may be less than that of the allocations.
+    size: usize,
+    layout: Layout,
+    items: Mutex<Vec<NonNull<u8>>>,
+}
+
+// The arena owns the free allocations it points to, which hold no values.
+unsafe impl Send for ErasedArena {}
+unsafe impl Sync for ErasedArena {}
+
+impl ErasedArena {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<NonNull<u8>>> {
+        self.items.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for ErasedArena {
+    fn drop(&mut self) {
+        for p in self.lock().drain(..) {
+            unsafe { std::alloc::dealloc(p.as_ptr(), self.layout) }
+        }
+    }
+}
+
+/// Create an arena of allocations of `size` bytes aligned to `align`, which must be a power
+/// of two, or return null if it is not. An allocation of zero bytes takes one byte.
+#[no_mangle]
+pub extern "C" fn boxing_arena_new(size: usize, align: usize) -> *mut ErasedArena {
+    let layout = match Layout::from_size_align(size.max(1), align) {
+        Ok(layout) => layout,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let arena = ErasedArena {
+        size,
+        layout,
+        items: Mutex::new(Vec::new()),
+    };
+    Box::into_raw(Box::new(arena))
+}
+
+/// Return an allocation of the arena, reusing a free one if there is one, with the `size`
+/// bytes at `value` copied into it unless `value` is null. Returns null if memory runs out.
+///
+/// # Safety
+///
+/// `arena` must come from `boxing_arena_new` and not be freed, and `value`, unless null,
+/// must be valid for reading `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn boxing_arena_rebox(
+    arena: *const ErasedArena,
+    value: *const c_void,
+) -> *mut c_void {
+    let arena = &*arena;
+    let p = match arena.lock().pop() {
+        Some(p) => p.as_ptr(),
+        None => std::alloc::alloc(arena.layout),
+    };
+    if !p.is_null() && !value.is_null() {
+        std::ptr::copy_nonoverlapping(value as *const u8, p, arena.size);
+    }
+    p as *mut c_void
+}
+
+/// Copy the `size` bytes of the allocation `b` to `out`, unless `out` is null, and keep the
+/// allocation in the arena.
+///
+/// # Safety
+///
+/// `arena` must come from `boxing_arena_new` and not be freed, `b` must come from
+/// `boxing_arena_rebox` on the same arena and not be used afterwards, and `out`, unless
+/// null, must be valid for writing `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn boxing_arena_unbox(
+    arena: *const ErasedArena,
+    b: *mut c_void,
+    out: *mut c_void,
+) {
+    let arena = &*arena;
+    let b = match NonNull::new(b as *mut u8) {
+        Some(b) => b,
+        None => return,
+    };
+    if !out.is_null() {
+        std::ptr::copy_nonoverlapping(b.as_ptr(), out as *mut u8, arena.size);
+    }
+    arena.lock().push(b);
+}
+
+/// Return the number of free allocations of the arena.
+///
+/// # Safety
+///
+/// `arena` must come from `boxing_arena_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn boxing_arena_capacity(arena: *const ErasedArena) -> usize {
+    (*arena).lock().len()
+}
+
+/// Free the arena and its free allocations. The allocations it handed out must not be given
+/// back to it afterwards.
+///
+/// # Safety
+///
+/// `arena` must come from `boxing_arena_new`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn boxing_arena_free(arena: *mut ErasedArena) {
+    if !arena.is_null() {
+        drop(Box::from_raw(arena));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ffi_pool() {
+        let arena = Arc::new(Mutex::new(BoxingArena::new()));
+        let pool = FfiPool::new(arena.clone());
+
+        let mut b = pool.rebox([1u32; 8]);
+        b[0] = 2;
+        let addr = &*b as *const [u32; 8];
+        drop(b);
+        assert_eq!(pool.capacity(), 1);
+
+        // Plugins get their own handles to the same pool.
+        let plugin = pool.clone();
+        let b = std::thread::spawn(move || plugin.rebox([3; 8]).into_inner()).join().unwrap();
+        assert_eq!(b, [3; 8]);
+        let b = pool.rebox([4; 8]);
+        assert_eq!(&*b as *const [u32; 8], addr);
+
+        drop((b, pool));
+        assert_eq!(Arc::strong_count(&arena), 1);
+    }
+
+    #[test]
+    fn erased_arena() {
+        assert!(boxing_arena_new(8, 3).is_null());
+        let arena = boxing_arena_new(8, 8);
+        unsafe {
+            let a = boxing_arena_rebox(arena, &1u64 as *const u64 as *const c_void);
+            let mut out = 0u64;
+            boxing_arena_unbox(arena, a, &mut out as *mut u64 as *mut c_void);
+            assert_eq!((out, boxing_arena_capacity(arena)), (1, 1));
+            let b = boxing_arena_rebox(arena, std::ptr::null());
+            assert_eq!((b, boxing_arena_capacity(arena)), (a, 0));
+            boxing_arena_unbox(arena, b, std::ptr::null_mut());
+            boxing_arena_free(arena);
+        }
+    }
+}
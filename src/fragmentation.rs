@@ -0,0 +1,116 @@
+//! Reporting on how the free boxes are spread over memory pages, and compacting them.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+use crate::{provenance, BoxingArena};
+
+/// Page size assumed by the fragmentation report.
+pub const PAGE_SIZE: usize = 4096;
+
+/// How an arena's free boxes are spread over memory, as returned by
+/// `BoxingArena::fragmentation_report`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FragmentationReport {
+    /// Number of free boxes.
+    pub free_slots: usize,
+    /// Number of distinct pages that free boxes occupy.
+    pub pages_touched: usize,
+    /// Lowest address of a free box, or 0 if there are none.
+    pub lowest_address: usize,
+    /// Address just past the end of the highest free box, or 0 if there are none.
+    pub highest_address: usize,
+}
+
+impl FragmentationReport {
+    /// Return the number of bytes between the lowest and highest free box.
+    pub fn address_span(&self) -> usize {
+        self.highest_address - self.lowest_address
+    }
+
+    /// Return the average number of free boxes per page touched.
+    pub fn slots_per_page(&self) -> f64 {
+        if self.pages_touched == 0 {
+            return 0.0;
+        }
+        self.free_slots as f64 / self.pages_touched as f64
+    }
+}
+
+fn pages_of<T>(p: *const T) -> core::ops::RangeInclusive<usize> {
+    let start = provenance::addr(p);
+    let end = start + core::mem::size_of::<T>().max(1) - 1;
+    start / PAGE_SIZE..=end / PAGE_SIZE
+}
+
+impl<T> BoxingArena<T> {
+    /// Number of free boxes on each page, by page number.
+    fn page_occupancy(&self) -> BTreeMap<usize, usize> {
+        let mut pages = BTreeMap::new();
+        for &p in self.items.iter() {
+            for page in pages_of(p) {
+                *pages.entry(page).or_insert(0) += 1;
+            }
+        }
+        pages
+    }
+
+    /// Report how the free boxes are spread over memory pages.
+    pub fn fragmentation_report(&self) -> FragmentationReport {
+        let mut report = FragmentationReport {
+            free_slots: self.items.len(),
+            pages_touched: self.page_occupancy().len(),
+            ..Default::default()
+        };
+
+        let mut addresses = self.items.iter().map(|&p| provenance::addr(p));
+        if let Some(first) = addresses.next() {
+            let (low, high) = addresses.fold((first, first), |(l, h), a| (l.min(a), h.max(a)));
+            report.lowest_address = low;
+            report.highest_address = high + core::mem::size_of::<T>();
+        }
+        report
+    }
+
+    /// Shrink the pool to `size` free boxes, releasing first the ones on the pages that hold
+    /// the fewest free boxes, so that mostly empty pages are no longer pinned by the arena.
+    pub fn compact(&mut self, size: usize) {
+        let excess = match self.items.len().checked_sub(size) {
+            None | Some(0) => return,
+            Some(excess) => excess,
+        };
+
+        let occupancy = self.page_occupancy();
+        let mut slots: Vec<_> = self
+            .items
+            .iter()
+            .map(|&p| (occupancy[pages_of(p).start()], core::cmp::Reverse(provenance::addr(p))))
+            .collect();
+        slots.sort_unstable();
+
+        let release: BTreeSet<_> = slots[..excess].iter().map(|(_, p)| p.0).collect();
+        self.retain_addresses(|p| !release.contains(&provenance::addr(p)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact() {
+        let mut ba = BoxingArena::<[u8; 256]>::new();
+        assert_eq!(ba.fragmentation_report().free_slots, 0);
+
+        ba.resize_capacity(64);
+        let report = ba.fragmentation_report();
+        assert_eq!(report.free_slots, 64);
+        assert!(report.pages_touched >= 4);
+        assert!(report.address_span() >= 64 * 256);
+
+        ba.compact(16);
+        assert_eq!(ba.capacity(), 16);
+        assert!(ba.fragmentation_report().pages_touched < report.pages_touched);
+        assert!(ba.fragmentation_report().slots_per_page() > 0.0);
+    }
+}
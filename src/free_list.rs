@@ -0,0 +1,660 @@
+//! The list of free boxes kept by a `BoxingArena`, and the order in which they are reused.
+
+use alloc::vec::Vec;
+
+/// The order in which a `BoxingArena` reuses its free boxes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReuseOrder {
+    /// The most recently returned box first, while it is likely still in cache. The default.
+    #[default]
+    Lifo,
+    /// The least recently returned box first, which spreads writes across the free boxes, so
+    /// that a box given back by a consumer is not immediately written again by a producer on
+    /// another core.
+    Fifo,
+}
+
+/// Hardening of the reuse of free boxes against use-after-unbox bugs, as set by
+/// `BoxingArena::set_hardening`. By default, boxes are reused right away and in a
+/// predictable order, so that a dangling pointer to a box given back soon points to the next
+/// value boxed, which an attacker may control.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hardening {
+    /// Number of the most recently given back boxes that are kept in quarantine before they
+    /// can be reused: each box is only reused after that many more were given back. 0, the
+    /// default, disables the quarantine.
+    pub quarantine: usize,
+    /// A seed for picking which of the free boxes to reuse at random, rather than in the
+    /// reuse order, or `None`, the default. It should come from a source of randomness, such
+    /// as `std::collections::hash_map::RandomState`, for the choices not to be predictable.
+    pub random_seed: Option<u64>,
+}
+
+/// Free box allocations, in the order they are going to be handed out again.
+///
+/// Optionally, the most recently returned boxes are kept apart in a small hot stack that is
+/// served first, while older ones overflow into the cold list, which is where boxes are
+/// released from when shrinking. In FIFO order, all of them are kept in a single queue
+/// instead. When hardened, the most recently returned boxes are kept in quarantine before
+/// they go to either.
+pub(crate) struct FreeList<T> {
+    /// Most recently returned boxes, newest at the back. At most `hot_limit` long.
+    hot: Vec<*mut T>,
+    hot_limit: usize,
+    /// Reused from the end. When address-ordered, sorted by descending address.
+    cold: Vec<*mut T>,
+    address_ordered: bool,
+    order: ReuseOrder,
+    /// All of the free boxes in FIFO order, oldest at the front, instead of `hot` and `cold`.
+    queue: Queue<T>,
+    /// Boxes that cannot be reused yet, oldest at the front. At most `hardening.quarantine`
+    /// long.
+    quarantine: Queue<T>,
+    hardening: Hardening,
+    /// The state of the xorshift generator picking the boxes to reuse, if random.
+    random: u64,
+    /// The most boxes the list held at once.
+    peak: usize,
+    /// The fewest boxes the list held since the last `take_low`.
+    low: usize,
+    /// The fewest boxes the list held since the last `take_low` of each `LowMark`.
+    marks: [usize; 2],
+}
+
+/// One of the observers of `FreeList::take_low`, each with its own period.
+#[derive(Clone, Copy)]
+pub(crate) enum LowMark {
+    #[cfg(feature = "std")]
+    Idle,
+    Tune,
+}
+
+impl<T> FreeList<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            hot: Vec::new(),
+            hot_limit: 0,
+            cold: Vec::new(),
+            address_ordered: false,
+            order: ReuseOrder::Lifo,
+            queue: Queue::new(),
+            quarantine: Queue::new(),
+            hardening: Hardening {
+                quarantine: 0,
+                random_seed: None,
+            },
+            random: 0,
+            peak: 0,
+            low: 0,
+            marks: [0; 2],
+        }
+    }
+
+    /// An empty list with the same settings.
+    pub(crate) fn fork_empty(&self) -> Self {
+        Self {
+            hot_limit: self.hot_limit,
+            address_ordered: self.address_ordered,
+            order: self.order,
+            hardening: self.hardening,
+            random: self.random,
+            ..Self::new()
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.hot.len() + self.cold.len() + self.queue.len() + self.quarantine.len()
+    }
+
+    /// Number of free boxes that `pop` can take, which excludes those in quarantine.
+    pub(crate) fn reusable_len(&self) -> usize {
+        self.len() - self.quarantine.len()
+    }
+
+    /// Number of entries the bookkeeping has room for.
+    pub(crate) fn capacity(&self) -> usize {
+        let free = self.hot.capacity() + self.cold.capacity() + self.queue.capacity();
+        free + self.quarantine.capacity()
+    }
+
+    /// Whether `push` can be done without growing the bookkeeping.
+    pub(crate) fn has_room(&self) -> bool {
+        if self.hardening.quarantine > 0 {
+            if !self.quarantine.has_room() {
+                return false;
+            }
+            if self.quarantine.len() < self.hardening.quarantine {
+                return true;
+            }
+        }
+        if self.order == ReuseOrder::Fifo {
+            return self.queue.has_room();
+        }
+        let cold_room = self.cold.len() < self.cold.capacity();
+        if self.hot.len() < self.hot_limit {
+            self.hot.len() < self.hot.capacity()
+        } else {
+            cold_room
+        }
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.hot.shrink_to_fit();
+        self.cold.shrink_to_fit();
+        self.queue.shrink_to_fit();
+        self.quarantine.shrink_to_fit();
+    }
+
+    pub(crate) fn hardening(&self) -> Hardening {
+        self.hardening
+    }
+
+    pub(crate) fn set_hardening(&mut self, hardening: Hardening) {
+        self.hardening = hardening;
+        // Xorshift gets stuck at zero.
+        self.random = hardening.random_seed.map_or(0, |seed| seed | 1);
+        while self.quarantine.len() > hardening.quarantine {
+            match self.quarantine.pop() {
+                Some(p) => self.push_free(p),
+                None => break,
+            }
+        }
+    }
+
+    pub(crate) fn reuse_order(&self) -> ReuseOrder {
+        self.order
+    }
+
+    pub(crate) fn set_reuse_order(&mut self, order: ReuseOrder) {
+        if order == self.order {
+            return;
+        }
+        self.order = order;
+        match order {
+            ReuseOrder::Fifo => {
+                // The cold boxes are older than the hot ones.
+                let (cold, hot) = (core::mem::take(&mut self.cold), core::mem::take(&mut self.hot));
+                self.queue.extend(cold.into_iter().chain(hot));
+            }
+            ReuseOrder::Lifo => {
+                let queue = core::mem::replace(&mut self.queue, Queue::new());
+                self.extend(queue.into_iter());
+            }
+        }
+    }
+
+    pub(crate) fn is_address_ordered(&self) -> bool {
+        self.address_ordered
+    }
+
+    pub(crate) fn set_address_ordered(&mut self, ordered: bool) {
+        self.address_ordered = ordered;
+        if ordered {
+            self.sort();
+        }
+    }
+
+    pub(crate) fn hot_limit(&self) -> usize {
+        self.hot_limit
+    }
+
+    pub(crate) fn set_hot_limit(&mut self, limit: usize) {
+        self.hot_limit = limit;
+        if self.hot.len() > limit {
+            let excess = self.hot.len() - limit;
+            for p in self.hot.drain(..excess).collect::<Vec<_>>() {
+                self.push_cold(p);
+            }
+        }
+    }
+
+    fn sort(&mut self) {
+        self.cold.sort_unstable_by(|a, b| b.cmp(a));
+    }
+
+    fn push_cold(&mut self, p: *mut T) {
+        if self.address_ordered {
+            let idx = self.cold.partition_point(|&x| x > p);
+            self.cold.insert(idx, p);
+        } else {
+            self.cold.push(p);
+        }
+    }
+
+    /// Boxes of zero-sized types have no allocation, so there is nothing to keep.
+    fn keeps() -> bool {
+        core::mem::size_of::<T>() != 0
+    }
+
+    /// Add a free box.
+    pub(crate) fn push(&mut self, p: *mut T) {
+        if !Self::keeps() {
+            return;
+        }
+        #[cfg(feature = "zeroize")]
+        unsafe {
+            crate::wipe::wipe(p)
+        };
+        #[cfg(feature = "poison")]
+        unsafe {
+            crate::poison::poison(p)
+        };
+        self.push_wiped(p)
+    }
+
+    /// Add a box that is ready for reuse, to the quarantine if there is one, moving its
+    /// oldest box out to be reusable once full.
+    fn push_wiped(&mut self, p: *mut T) {
+        if self.hardening.quarantine > 0 {
+            self.quarantine.push(p);
+            if self.quarantine.len() <= self.hardening.quarantine {
+                return;
+            }
+            if let Some(oldest) = self.quarantine.pop() {
+                self.push_free(oldest);
+            }
+            return;
+        }
+        self.push_free(p)
+    }
+
+    /// Add a box that can be reused right away.
+    fn push_free(&mut self, p: *mut T) {
+        if self.order == ReuseOrder::Fifo {
+            return self.queue.push(p);
+        }
+        if self.hot_limit == 0 {
+            return self.push_cold(p);
+        }
+        if self.hot.len() == self.hot_limit {
+            let oldest = self.hot.remove(0);
+            self.push_cold(oldest);
+        }
+        self.hot.push(p);
+    }
+
+    /// Like `push`, but gives a box back instead of failing when there is no memory left for
+    /// the bookkeeping. That box is then no longer in the list, and may be another one than
+    /// `p`. This path has no panic branches: the capacity checks always pass after reserving,
+    /// but let the optimizer drop the paths that grow.
+    #[inline]
+    pub(crate) fn try_push(&mut self, p: *mut T) -> Result<(), *mut T> {
+        if !Self::keeps() {
+            return Err(p);
+        }
+        #[cfg(feature = "zeroize")]
+        unsafe {
+            crate::wipe::wipe(p)
+        };
+        #[cfg(feature = "poison")]
+        unsafe {
+            crate::poison::poison(p)
+        };
+        let p = match self.hardening.quarantine {
+            0 => p,
+            limit => {
+                self.quarantine.try_push(p)?;
+                if self.quarantine.len() <= limit {
+                    return Ok(());
+                }
+                match self.quarantine.pop() {
+                    Some(oldest) => oldest,
+                    None => return Ok(()),
+                }
+            }
+        };
+        if self.order == ReuseOrder::Fifo {
+            return self.queue.try_push(p);
+        }
+        let to_cold = if self.hot_limit == 0 {
+            p
+        } else {
+            self.hot.try_reserve(1).map_err(|_| p)?;
+            let len = self.hot.len();
+            if len == self.hot.capacity() {
+                return Err(p);
+            }
+            // Same as `push` and `remove(0)`, without their checks.
+            unsafe {
+                let base = self.hot.as_mut_ptr();
+                base.add(len).write(p);
+                if len < self.hot_limit {
+                    self.hot.set_len(len + 1);
+                    return Ok(());
+                }
+                // The oldest hot box goes cold.
+                let oldest = base.read();
+                core::ptr::copy(base.add(1), base, len);
+                oldest
+            }
+        };
+
+        self.cold.try_reserve(1).map_err(|_| to_cold)?;
+        let idx = match self.address_ordered {
+            true => self.cold.partition_point(|&x| x > to_cold),
+            false => self.cold.len(),
+        };
+        let len = self.cold.len();
+        if idx > len || len == self.cold.capacity() {
+            return Err(to_cold);
+        }
+        // Same as `insert`, without its checks.
+        unsafe {
+            let at = self.cold.as_mut_ptr().add(idx);
+            core::ptr::copy(at, at.add(1), len - idx);
+            at.write(to_cold);
+            self.cold.set_len(len + 1);
+        }
+        Ok(())
+    }
+
+    /// Add many free boxes at once. They go straight to the cold list, or to the back of the
+    /// queue in FIFO order.
+    pub(crate) fn extend<I: IntoIterator<Item = *mut T>>(&mut self, iter: I) {
+        #[cfg(feature = "poison")]
+        let iter = iter.into_iter().inspect(|&p| unsafe { crate::poison::poison(p) });
+        if self.order == ReuseOrder::Fifo {
+            return self.queue.extend(iter);
+        }
+        self.cold.extend(iter);
+        if self.address_ordered {
+            self.sort();
+        }
+    }
+
+    /// Called before taking boxes out, which is when the length may be at a peak, rather
+    /// than on the more frequent additions.
+    #[inline(always)]
+    fn note_peak(&mut self) {
+        self.peak = self.peak.max(self.len());
+    }
+
+    /// Return the most boxes the list held at once.
+    pub(crate) fn peak(&self) -> usize {
+        self.peak.max(self.len())
+    }
+
+    /// Return the fewest boxes the list held since the previous call for the same `mark`,
+    /// which none of the boxes taken out in between came from.
+    pub(crate) fn take_low(&mut self, mark: LowMark) -> usize {
+        let len = self.len();
+        let low = core::mem::replace(&mut self.low, len);
+        for pending in self.marks.iter_mut() {
+            *pending = (*pending).min(low);
+        }
+        core::mem::replace(&mut self.marks[mark as usize], len)
+    }
+
+    /// Make room for `additional` more boxes in the cold list.
+    pub(crate) fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), alloc::collections::TryReserveError> {
+        self.cold.try_reserve(additional)
+    }
+
+    /// Take the free box that should be reused next.
+    #[inline(always)]
+    pub(crate) fn pop(&mut self) -> Option<*mut T> {
+        self.note_peak();
+        let p = match (self.random, self.order) {
+            (0, ReuseOrder::Lifo) => self.hot.pop().or_else(|| self.cold.pop()),
+            (0, ReuseOrder::Fifo) => self.queue.pop(),
+            _ => self.pop_random(),
+        };
+        self.low = self.low.min(self.len());
+        #[cfg(feature = "poison")]
+        if let Some(p) = p {
+            // Aborts instead of unwinding with `no-panic`.
+            crate::no_unwind(|| unsafe { crate::poison::check(p) });
+        }
+        p
+    }
+
+    /// Take the oldest box in quarantine, for moving all of the boxes out of the list.
+    pub(crate) fn pop_quarantined(&mut self) -> Option<*mut T> {
+        self.note_peak();
+        let p = self.quarantine.pop();
+        self.low = self.low.min(self.len());
+        #[cfg(feature = "poison")]
+        if let Some(p) = p {
+            crate::no_unwind(|| unsafe { crate::poison::check(p) });
+        }
+        p
+    }
+
+    /// Move up to `n` boxes to `other`, those that can be reused first, and then those in
+    /// quarantine, which are quarantined in `other` too if it has a quarantine.
+    pub(crate) fn move_to(&mut self, other: &mut Self, n: usize) {
+        let reusable = n.min(self.reusable_len());
+        other.extend(core::iter::from_fn(|| self.pop()).take(reusable));
+        for _ in reusable..n {
+            let p = match self.pop_quarantined() {
+                Some(p) => p,
+                None => break,
+            };
+            other.push_wiped(p);
+        }
+    }
+
+    /// Take one of the free boxes at random. This path has no panic branches, as `pop`.
+    #[inline]
+    fn pop_random(&mut self) -> Option<*mut T> {
+        let (queue, hot) = (self.queue.len(), self.hot.len());
+        let n = queue + hot + self.cold.len();
+        if n == 0 {
+            return None;
+        }
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        let i = (self.random % n as u64) as usize;
+        if i < queue {
+            return self.queue.take(i);
+        }
+        if i - queue < hot {
+            return take_at(&mut self.hot, i - queue, false);
+        }
+        take_at(&mut self.cold, i - queue - hot, self.address_ordered)
+    }
+
+    /// Take out the `count` free boxes that should be released first when shrinking, passing
+    /// each to `f`. These come from the cold list first, where address order releases the
+    /// highest addresses, then from the oldest of the hot ones, and last from quarantine.
+    pub(crate) fn release<F: FnMut(*mut T)>(&mut self, count: usize, mut f: F) {
+        self.note_peak();
+        // In FIFO order, the newest boxes are the last to be reused.
+        let count = count - self.queue.release(count, &mut f);
+
+        let from_cold = count.min(self.cold.len());
+        if self.address_ordered {
+            self.cold.drain(..from_cold).for_each(&mut f);
+        } else {
+            let len = self.cold.len();
+            self.cold.drain(len - from_cold..).for_each(&mut f);
+        }
+
+        let from_hot = (count - from_cold).min(self.hot.len());
+        self.hot.drain(..from_hot).for_each(&mut f);
+        self.quarantine.release(count - from_cold - from_hot, f);
+        self.low = self.low.min(self.len());
+    }
+
+    pub(crate) fn retain<F: FnMut(&*mut T) -> bool>(&mut self, mut f: F) {
+        self.note_peak();
+        self.hot.retain(&mut f);
+        self.cold.retain(&mut f);
+        self.queue.retain(&mut f);
+        self.quarantine.retain(f);
+        self.low = self.low.min(self.len());
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &*mut T> {
+        let free = self.hot.iter().chain(self.cold.iter()).chain(self.queue.iter());
+        free.chain(self.quarantine.iter())
+    }
+
+    /// Panic if the hot stack overflows, or the cold list is out of order.
+    #[cfg(feature = "checked")]
+    pub(crate) fn check_order(&self) {
+        assert!(
+            self.hot.len() <= self.hot_limit,
+            "free list: {} hot boxes, over the limit of {}",
+            self.hot.len(),
+            self.hot_limit
+        );
+        if self.address_ordered {
+            if let Some(w) = self.cold.windows(2).find(|w| w[0] < w[1]) {
+                panic!("free list: {:p} before {:p} in address order", w[0], w[1]);
+            }
+        }
+    }
+}
+
+/// Take the box at `i` out of `v`, keeping the order of the rest if `ordered`, or putting the
+/// last one in its place otherwise. This has no panic branches.
+#[inline]
+fn take_at<T>(v: &mut Vec<*mut T>, i: usize, ordered: bool) -> Option<*mut T> {
+    let len = v.len();
+    if i >= len {
+        return None;
+    }
+    unsafe {
+        let at = v.as_mut_ptr().add(i);
+        let p = at.read();
+        match ordered {
+            true => core::ptr::copy(at.add(1), at, len - i - 1),
+            false => at.write(v.as_ptr().add(len - 1).read()),
+        }
+        v.set_len(len - 1);
+        Some(p)
+    }
+}
+
+/// A FIFO queue of free boxes: taken from the front at `head`, added at the back. The taken
+/// entries are reclaimed by moving the rest to the front when that frees at least half of
+/// the room, so that a full queue does not move its entries on every addition.
+struct Queue<T> {
+    entries: Vec<*mut T>,
+    head: usize,
+}
+
+impl<T> Queue<T> {
+    const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            head: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len() - self.head
+    }
+
+    fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    fn has_room(&self) -> bool {
+        let len = self.entries.len();
+        len < self.entries.capacity() || (self.head > 0 && self.head * 2 >= len)
+    }
+
+    fn compact(&mut self) {
+        self.entries.drain(..self.head);
+        self.head = 0;
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.compact();
+        self.entries.shrink_to_fit();
+    }
+
+    fn push(&mut self, p: *mut T) {
+        if self.entries.len() == self.entries.capacity() && self.has_room() {
+            self.compact();
+        }
+        self.entries.push(p);
+    }
+
+    /// Like `push`, but gives the box back when there is no memory left for the bookkeeping.
+    #[inline]
+    fn try_push(&mut self, p: *mut T) -> Result<(), *mut T> {
+        let len = self.entries.len();
+        if len == self.entries.capacity() && self.head * 2 >= len {
+            // Same as `compact`, without the checks of `drain`.
+            unsafe {
+                let base = self.entries.as_mut_ptr();
+                core::ptr::copy(base.add(self.head), base, len - self.head);
+                self.entries.set_len(len - self.head);
+            }
+            self.head = 0;
+        }
+        self.entries.try_reserve(1).map_err(|_| p)?;
+        let len = self.entries.len();
+        if len == self.entries.capacity() {
+            return Err(p);
+        }
+        // Same as `push`, without its checks.
+        unsafe {
+            self.entries.as_mut_ptr().add(len).write(p);
+            self.entries.set_len(len + 1);
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn pop(&mut self) -> Option<*mut T> {
+        let p = *self.entries.get(self.head)?;
+        self.head += 1;
+        if self.head == self.entries.len() {
+            self.entries.clear();
+            self.head = 0;
+        }
+        Some(p)
+    }
+
+    /// Take the box `i` places from the front, putting the front one in its place.
+    #[inline]
+    fn take(&mut self, i: usize) -> Option<*mut T> {
+        if i >= self.len() {
+            return None;
+        }
+        unsafe {
+            let front = self.entries.as_mut_ptr().add(self.head);
+            core::ptr::swap(front, front.add(i));
+        }
+        self.pop()
+    }
+
+    fn extend<I: IntoIterator<Item = *mut T>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+    }
+
+    /// Take out up to `count` of the newest boxes, which would be reused last, passing each
+    /// to `f`, and return how many.
+    fn release<F: FnMut(*mut T)>(&mut self, count: usize, f: F) -> usize {
+        let count = count.min(self.len());
+        let len = self.entries.len();
+        self.entries.drain(len - count..).for_each(f);
+        if self.head == self.entries.len() {
+            self.entries.clear();
+            self.head = 0;
+        }
+        count
+    }
+
+    fn retain<F: FnMut(&*mut T) -> bool>(&mut self, f: F) {
+        self.compact();
+        self.entries.retain(f);
+    }
+
+    fn iter(&self) -> core::slice::Iter<'_, *mut T> {
+        self.entries[self.head..].iter()
+    }
+
+    fn into_iter(mut self) -> alloc::vec::IntoIter<*mut T> {
+        self.compact();
+        self.entries.into_iter()
+    }
+}
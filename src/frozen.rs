@@ -0,0 +1,88 @@
+//! A type-state of `BoxingArena` that can no longer reach the allocator.
+
+use alloc::boxed::Box;
+
+use crate::{ArenaStats, BoxingArena};
+
+/// An arena that was frozen by `BoxingArena::freeze`.
+///
+/// It only exposes operations that are guaranteed not to allocate or deallocate: boxes are
+/// handed out only from existing free allocations, and taken back only while the free list
+/// has room for them. Every box obtained from `try_rebox` can always be given back by
+/// `try_unbox`.
+pub struct FrozenArena<T> {
+    inner: BoxingArena<T>,
+}
+
+impl<T> FrozenArena<T> {
+    pub(crate) fn new(inner: BoxingArena<T>) -> Self {
+        Self { inner }
+    }
+
+    /// Like `BoxingArena::try_rebox`: take the value out of `*v` and box it only if there are
+    /// empty boxes, otherwise leave `*v` untouched and return `None`.
+    #[track_caller]
+    pub fn try_rebox(&mut self, v: &mut Option<T>) -> Option<Box<T>> {
+        self.inner.try_rebox(v)
+    }
+
+    /// Like `BoxingArena::try_rebox_value`: box the value only if there are empty boxes,
+    /// otherwise give it back in `Err`.
+    #[track_caller]
+    pub fn try_rebox_value(&mut self, v: T) -> Result<Box<T>, T> {
+        self.inner.try_rebox_value(v)
+    }
+
+    /// Unbox the value and keep the allocation, if the free list has room for it without
+    /// growing. Otherwise the box is given back untouched.
+    #[track_caller]
+    pub fn try_unbox(&mut self, v: Box<T>) -> Result<T, Box<T>> {
+        if self.inner.items.has_room() {
+            Ok(self.inner.unbox(v))
+        } else {
+            Err(v)
+        }
+    }
+
+    /// Return the number of free boxes in the arena.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Return a snapshot of the arena's counters.
+    pub fn stats(&self) -> ArenaStats {
+        self.inner.stats()
+    }
+
+    /// Go back to a regular `BoxingArena`, which may allocate again.
+    pub fn thaw(self) -> BoxingArena<T> {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frozen() {
+        let mut fa = BoxingArena::with_capacity(2).freeze();
+        assert_eq!(fa.capacity(), 2);
+
+        let a = fa.try_rebox(&mut Some(1u64)).unwrap();
+        let b = fa.try_rebox(&mut Some(2u64)).unwrap();
+        let mut v = Some(3u64);
+        assert!(fa.try_rebox(&mut v).is_none());
+        assert_eq!(v, Some(3));
+
+        // Boxes that came from the arena can always be given back.
+        assert_eq!(fa.try_unbox(a), Ok(1));
+        assert_eq!(fa.try_unbox(b), Ok(2));
+
+        // But there is no room for a third one.
+        assert_eq!(fa.try_unbox(Box::new(3)), Err(Box::new(3)));
+
+        let ba = fa.thaw();
+        assert_eq!(ba.capacity(), 2);
+    }
+}
@@ -0,0 +1,98 @@
+//! Recycling of the allocations of boxed futures.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use crate::DynBoxingArena;
+
+/// A pool of the allocations of boxed futures, for executors and actor frameworks that box a
+/// future per task or message. Futures are boxed by `rebox` in a `PooledFuture`, whose
+/// allocation goes back to the pool, under the layout of the concrete future, when it is
+/// dropped, on whichever thread that happens. Clones of the pool share its allocations.
+#[derive(Clone, Default)]
+pub struct FuturePool {
+    arena: Arc<Mutex<DynBoxingArena>>,
+}
+
+impl FuturePool {
+    /// Create an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn arena(&self) -> MutexGuard<'_, DynBoxingArena> {
+        self.arena.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Box the future, reusing a free allocation of the same layout if there is one.
+    pub fn rebox<F>(&self, f: F) -> PooledFuture<F::Output>
+    where
+        F: Future + Send + 'static,
+    {
+        let b: Box<dyn Future<Output = F::Output> + Send> = self.arena().rebox(f);
+        PooledFuture {
+            future: Some(Box::into_pin(b)),
+            pool: self.clone(),
+        }
+    }
+
+    /// Return the number of free allocations, of all layouts.
+    pub fn capacity(&self) -> usize {
+        self.arena().capacity()
+    }
+
+    /// Release all free allocations.
+    pub fn clear(&self) {
+        self.arena().clear()
+    }
+}
+
+/// A boxed future from a `FuturePool`, which gives its allocation back to the pool when
+/// dropped, be it after completing or not.
+pub struct PooledFuture<O> {
+    future: Option<Pin<Box<dyn Future<Output = O> + Send>>>,
+    pool: FuturePool,
+}
+
+impl<O> Future for PooledFuture<O> {
+    type Output = O;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<O> {
+        self.future.as_mut().expect("polled after drop").as_mut().poll(cx)
+    }
+}
+
+impl<O> Drop for PooledFuture<O> {
+    fn drop(&mut self) {
+        if let Some(future) = self.future.take() {
+            // The future is dropped in place before its memory is reused, as pinning
+            // requires, and outside of the lock, in case that uses the pool.
+            let b = unsafe { Pin::into_inner_unchecked(future) };
+            if let Some((p, layout)) = DynBoxingArena::drop_value(b) {
+                self.pool.arena().keep(p, layout);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn future_pool() {
+        let pool = FuturePool::new();
+        let name = String::from("abc");
+        assert_eq!(pool.rebox(async move { name.len() }).await, 3);
+        assert_eq!(pool.capacity(), 1);
+
+        // Futures of the same layout reuse the allocation, even unfinished ones.
+        let name = String::from("d");
+        let f = pool.rebox(async move { name.len() });
+        assert_eq!(pool.capacity(), 0);
+        drop(f);
+        assert_eq!(pool.capacity(), 1);
+    }
+}
@@ -0,0 +1,153 @@
+//! A clonable handle to an arena, for several owners.
+
+use alloc::boxed::Box;
+use core::ops::DerefMut;
+
+use crate::{BoxingArena, Pool, SharedPool};
+
+#[cfg(feature = "std")]
+type Shared<T> = alloc::sync::Arc<crate::SyncBoxingArena<T>>;
+#[cfg(not(feature = "std"))]
+type Shared<T> = alloc::rc::Rc<core::cell::RefCell<BoxingArena<T>>>;
+
+/// A handle to an arena that many components can hold at once, each boxing and unboxing
+/// through it, instead of funnelling all of it through the single owner of a `BoxingArena`.
+/// Clones are handles to the same arena.
+///
+/// With the `std` feature, the arena is in an `Arc` behind a mutex, as a `SyncBoxingArena`,
+/// so that handles can be sent to other threads. Without it, the arena is in an `Rc` behind
+/// a `RefCell`, and calling the handle again from within `with` panics.
+pub struct ArenaHandle<T> {
+    shared: Shared<T>,
+}
+
+impl<T> ArenaHandle<T> {
+    /// Create a handle to an empty arena.
+    pub fn new() -> Self {
+        Self::from(BoxingArena::new())
+    }
+
+    /// Create a handle to an arena with `size` free boxes.
+    pub fn with_capacity(size: usize) -> Self {
+        Self::from(BoxingArena::with_capacity(size))
+    }
+
+    fn lock(&self) -> impl DerefMut<Target = BoxingArena<T>> + '_ {
+        #[cfg(feature = "std")]
+        return self.shared.lock();
+        #[cfg(not(feature = "std"))]
+        return self.shared.borrow_mut();
+    }
+
+    /// Run `f` on the arena, for the operations that are not provided on the handle.
+    pub fn with<R>(&self, f: impl FnOnce(&mut BoxingArena<T>) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Box a value, see `BoxingArena::rebox`.
+    #[track_caller]
+    pub fn rebox(&self, v: T) -> Box<T> {
+        self.lock().rebox(v)
+    }
+
+    /// Like `rebox` but only if there are free boxes, see `BoxingArena::try_rebox_value`.
+    #[track_caller]
+    pub fn try_rebox_value(&self, v: T) -> Result<Box<T>, T> {
+        self.lock().try_rebox_value(v)
+    }
+
+    /// Take the value out of the box, keeping the allocation, see `BoxingArena::unbox`. The
+    /// value is returned after the arena is released, so that dropping it does not hold up
+    /// other handles.
+    #[track_caller]
+    pub fn unbox(&self, b: Box<T>) -> T {
+        self.lock().unbox(b)
+    }
+
+    /// Return the number of free boxes.
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity()
+    }
+
+    /// Trims capacity to the given size if it is larger.
+    pub fn trim(&self, size: usize) {
+        self.lock().trim(size)
+    }
+
+    /// Return whether both handles are to the same arena.
+    pub fn same_arena(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl<T> Clone for ArenaHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> From<BoxingArena<T>> for ArenaHandle<T> {
+    fn from(arena: BoxingArena<T>) -> Self {
+        Self {
+            shared: Shared::new(arena.into()),
+        }
+    }
+}
+
+impl<T> Default for ArenaHandle<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Pool<T> for ArenaHandle<T> {
+    #[track_caller]
+    fn rebox(&mut self, v: T) -> Box<T> {
+        ArenaHandle::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&mut self, b: Box<T>) -> T {
+        ArenaHandle::unbox(self, b)
+    }
+
+    fn trim(&mut self, size: usize) {
+        ArenaHandle::trim(self, size)
+    }
+
+    fn capacity(&self) -> usize {
+        ArenaHandle::capacity(self)
+    }
+}
+
+impl<T> SharedPool<T> for ArenaHandle<T> {
+    #[track_caller]
+    fn rebox(&self, v: T) -> Box<T> {
+        ArenaHandle::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&self, b: Box<T>) -> T {
+        ArenaHandle::unbox(self, b)
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_handle() {
+        let parser = ArenaHandle::with_capacity(1);
+        let renderer = parser.clone();
+        assert!(parser.same_arena(&renderer));
+
+        let b = parser.rebox(vec![1u8; 16]);
+        assert_eq!(renderer.unbox(b).len(), 16);
+        assert!(renderer.try_rebox_value(vec![]).is_ok());
+        assert_eq!(parser.with(|arena| arena.stats().hits), 2);
+        assert!(!parser.same_arena(&ArenaHandle::new()));
+    }
+}
@@ -0,0 +1,131 @@
+//! Calling back on the allocations of an arena, for leak trackers and heap profilers.
+
+use alloc::alloc::Layout;
+use alloc::sync::Arc;
+
+use crate::BoxingArena;
+
+type Hook = Arc<dyn Fn(*mut u8, Layout) + Send + Sync>;
+
+/// Callbacks on the box allocations of an arena, as set by `BoxingArena::set_hooks`. Each is
+/// given the address and layout of the allocation, for example to annotate it for a heap
+/// profiler or a memory checker. The callbacks are shared with the arenas forked from the
+/// arena, and with the clones of the hooks.
+#[derive(Clone, Default)]
+pub struct ArenaHooks {
+    fresh_alloc: Option<Hook>,
+    reuse: Option<Hook>,
+    dealloc: Option<Hook>,
+}
+
+impl ArenaHooks {
+    /// Create a set of hooks with no callbacks.
+    pub const fn new() -> Self {
+        Self {
+            fresh_alloc: None,
+            reuse: None,
+            dealloc: None,
+        }
+    }
+
+    /// Call `f` on each box the arena allocates, to hand it out on a miss, or to keep it free
+    /// when growing.
+    pub fn on_fresh_alloc<F>(mut self, f: F) -> Self
+    where
+        F: Fn(*mut u8, Layout) + Send + Sync + 'static,
+    {
+        self.fresh_alloc = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` on each free box the arena hands out again.
+    pub fn on_reuse<F>(mut self, f: F) -> Self
+    where
+        F: Fn(*mut u8, Layout) + Send + Sync + 'static,
+    {
+        self.reuse = Some(Arc::new(f));
+        self
+    }
+
+    /// Call `f` on each box the arena deallocates, right before it does, whether a free box
+    /// or one given back that it does not keep.
+    pub fn on_dealloc<F>(mut self, f: F) -> Self
+    where
+        F: Fn(*mut u8, Layout) + Send + Sync + 'static,
+    {
+        self.dealloc = Some(Arc::new(f));
+        self
+    }
+
+    fn call<T>(hook: &Option<Hook>, p: *mut T) {
+        if let Some(f) = hook {
+            f(p as *mut u8, Layout::new::<T>())
+        }
+    }
+
+    /// Run the hook for a box handed out, newly allocated unless `hit`.
+    pub(crate) fn handed_out<T>(&self, p: *mut T, hit: bool) {
+        Self::call(if hit { &self.reuse } else { &self.fresh_alloc }, p)
+    }
+
+    pub(crate) fn fresh<T>(&self, p: *mut T) {
+        Self::call(&self.fresh_alloc, p)
+    }
+
+    pub(crate) fn deallocating<T>(&self, p: *mut T) {
+        Self::call(&self.dealloc, p)
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Set the callbacks run on the allocations of the arena, replacing the previous ones.
+    pub fn set_hooks(&mut self, hooks: ArenaHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Stop calling the callbacks set by `set_hooks`.
+    pub fn clear_hooks(&mut self) {
+        self.hooks = ArenaHooks::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn hooks() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let log = |kind: &'static str| {
+            let events = events.clone();
+            move |p: *mut u8, layout: Layout| {
+                assert_eq!(layout, Layout::new::<u64>());
+                events.lock().unwrap().push((kind, p as usize));
+            }
+        };
+        let mut ba = BoxingArena::<u64>::new();
+        ba.set_hooks(
+            ArenaHooks::new()
+                .on_fresh_alloc(log("fresh"))
+                .on_reuse(log("reuse"))
+                .on_dealloc(log("dealloc")),
+        );
+        let a = ba.rebox(1);
+        let addr = &*a as *const u64 as usize;
+        ba.unbox(a);
+        let a = ba.rebox(2);
+        ba.unbox(a);
+        ba.resize_capacity(0);
+        let kinds = ["fresh", "reuse", "dealloc"];
+        assert_eq!(*events.lock().unwrap(), kinds.map(|kind| (kind, addr)));
+
+        // A fork shares the callbacks.
+        ba.fork_empty().rebox(3);
+        assert_eq!(events.lock().unwrap().len(), 4);
+
+        ba.clear_hooks();
+        ba.rebox(4);
+        assert_eq!(events.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,82 @@
+//! Releasing the free boxes that were not reused for a while.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::free_list::LowMark;
+use crate::{clock, BoxingArena};
+
+/// The times of the calls to `trim_idle`, each with the fewest free boxes the arena held
+/// since the previous call.
+pub(crate) struct IdleMarks {
+    marks: VecDeque<(Instant, usize)>,
+}
+
+impl IdleMarks {
+    pub(crate) const fn new() -> Self {
+        Self {
+            marks: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Release the free boxes that sat unused for longer than `max_age`. This is meant to be
+    /// called periodically, for example along with `maintain`: the arena tells how many free
+    /// boxes were not needed between calls, without a timestamp per box, so `max_age` is
+    /// only as precise as the period of the calls. The first call only starts observing.
+    /// Without a clock, as in the browser, this does nothing.
+    pub fn trim_idle(&mut self, max_age: Duration) {
+        let now = match clock::now() {
+            Some(now) => now,
+            None => return,
+        };
+        let low = self.items.take_low(LowMark::Idle);
+        let marks = &mut self.idle.marks;
+        marks.push_back((now, low));
+
+        // Keep the latest mark from before the window, which is where observation starts.
+        let start = match now.checked_sub(max_age) {
+            Some(start) => start,
+            None => return,
+        };
+        while marks.len() > 1 && marks[1].0 <= start {
+            marks.pop_front();
+        }
+        if marks[0].0 > start {
+            // Not observed for long enough yet.
+            return;
+        }
+
+        let idle = marks.iter().skip(1).map(|&(_, low)| low).min().unwrap_or(0);
+        if idle > 0 {
+            for mark in marks.iter_mut() {
+                mark.1 = mark.1.saturating_sub(idle);
+            }
+            let size = self.items.len() - idle;
+            self.trim(size);
+            self.items.take_low(LowMark::Idle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trim_idle() {
+        let mut ba = BoxingArena::<u64>::with_capacity(4);
+        let age = Duration::from_millis(20);
+        ba.trim_idle(age);
+        std::thread::sleep(age);
+
+        // Only one box was needed in the meantime.
+        let b = ba.rebox(1);
+        ba.unbox(b);
+        ba.trim_idle(age);
+        assert_eq!(ba.capacity(), 1);
+        ba.trim_idle(age);
+        assert_eq!(ba.capacity(), 1);
+    }
+}
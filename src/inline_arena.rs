@@ -0,0 +1,98 @@
+//! An arena of a few boxes, without heap bookkeeping.
+
+use alloc::alloc::Layout;
+use alloc::boxed::Box;
+
+/// An arena keeping at most `N` free boxes, whose pointers are stored inline rather than in
+/// a vector, so that the arena itself never allocates. This suits pools of a handful of
+/// boxes, as in embedded firmware, where the bookkeeping allocation of a `BoxingArena` would
+/// cost as much as the reuse saves. Boxes given back while the arena is full are freed.
+pub struct InlineArena<T, const N: usize> {
+    free: [*mut T; N],
+    len: usize,
+}
+
+// The arena only holds allocations without values.
+unsafe impl<T: Send, const N: usize> Send for InlineArena<T, N> {}
+
+impl<T, const N: usize> InlineArena<T, N> {
+    /// Create an empty arena. No allocation is made by this function.
+    pub const fn new() -> Self {
+        Self {
+            free: [core::ptr::null_mut(); N],
+            len: 0,
+        }
+    }
+
+    /// Box the value, reusing a free box if there is one.
+    pub fn rebox(&mut self, v: T) -> Box<T> {
+        if self.len == 0 {
+            return Box::new(v);
+        }
+        self.len -= 1;
+        let p = self.free[self.len];
+        unsafe {
+            p.write(v);
+            Box::from_raw(p)
+        }
+    }
+
+    /// Unbox the value, and keep the allocation if there is room for it.
+    pub fn unbox(&mut self, b: Box<T>) -> T {
+        if self.len == N || core::mem::size_of::<T>() == 0 {
+            return *b;
+        }
+        let p = Box::into_raw(b);
+        self.free[self.len] = p;
+        self.len += 1;
+        unsafe { p.read() }
+    }
+
+    /// Return the number of free boxes.
+    pub fn capacity(&self) -> usize {
+        self.len
+    }
+
+    /// Trims capacity to the given size if it is larger.
+    pub fn trim(&mut self, size: usize) {
+        while self.len > size {
+            self.len -= 1;
+            unsafe { alloc::alloc::dealloc(self.free[self.len] as *mut u8, Layout::new::<T>()) }
+        }
+    }
+}
+
+impl<T, const N: usize> Default for InlineArena<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for InlineArena<T, N> {
+    fn drop(&mut self) {
+        self.trim(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_arena() {
+        let mut arena = InlineArena::<String, 2>::new();
+        let boxes: Vec<_> = (0..3).map(|i| arena.rebox(i.to_string())).collect();
+        let addr = &*boxes[0] as *const String;
+        let values: Vec<_> = boxes.into_iter().map(|b| arena.unbox(b)).collect();
+        assert_eq!(values, ["0", "1", "2"]);
+        assert_eq!(arena.capacity(), 2);
+
+        let a = arena.rebox("a".to_string());
+        let b = arena.rebox("b".to_string());
+        assert_eq!(&*b as *const String, addr);
+        arena.unbox(a);
+        arena.trim(0);
+        assert_eq!(arena.capacity(), 0);
+        drop(b);
+    }
+}
@@ -0,0 +1,96 @@
+//! Inspection of the free boxes and of address reuse, enabled by the `diagnostics` feature.
+
+use std::collections::BTreeMap;
+use std::ptr::NonNull;
+
+use crate::BoxingArena;
+
+/// How often the arena handed out the same addresses, as returned by
+/// `BoxingArena::address_reuse`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddressReuse {
+    /// Number of boxes handed out since tracking started.
+    pub handouts: u64,
+    /// Number of distinct addresses among them.
+    pub distinct_addresses: usize,
+    /// The most times a single address was handed out.
+    pub max_per_address: u64,
+}
+
+impl AddressReuse {
+    /// Return the average number of times each address was handed out, which stays near 1
+    /// when boxes are not recycled.
+    pub fn reuse_factor(&self) -> f64 {
+        if self.distinct_addresses == 0 {
+            return 0.0;
+        }
+        self.handouts as f64 / self.distinct_addresses as f64
+    }
+}
+
+/// Number of times each address was handed out, while tracking is on.
+pub(crate) struct AddressCounts(Option<BTreeMap<usize, u64>>);
+
+impl AddressCounts {
+    pub(crate) const fn new() -> Self {
+        Self(None)
+    }
+
+    pub(crate) fn handed_out(&mut self, addr: usize) {
+        if let Some(map) = &mut self.0 {
+            *map.entry(addr).or_insert(0) += 1;
+        }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Iterate over the free boxes, in no particular order, for diagnostics tools verifying
+    /// that allocations are recycled. The boxes hold no values, and must not be written to.
+    pub fn inspect_free(&self) -> impl Iterator<Item = NonNull<T>> + '_ {
+        self.items.iter().map(|&p| unsafe { NonNull::new_unchecked(p) })
+    }
+
+    /// Set whether the arena counts how many times it hands out each address, see
+    /// `address_reuse`. This costs a map lookup per box handed out. Turning it off forgets
+    /// the counts.
+    pub fn set_address_tracking(&mut self, track: bool) {
+        match (track, &self.addresses.0) {
+            (true, None) => self.addresses.0 = Some(BTreeMap::new()),
+            (false, _) => self.addresses.0 = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    /// Return how often the arena handed out the same addresses since tracking was turned on
+    /// by `set_address_tracking`, or `None` if it is off.
+    pub fn address_reuse(&self) -> Option<AddressReuse> {
+        let map = self.addresses.0.as_ref()?;
+        Some(AddressReuse {
+            handouts: map.values().sum(),
+            distinct_addresses: map.len(),
+            max_per_address: map.values().copied().max().unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inspect() {
+        let mut ba = BoxingArena::with_capacity(2);
+        let free: Vec<_> = ba.inspect_free().collect();
+        assert_eq!(free.len(), 2);
+
+        ba.set_address_tracking(true);
+        for i in 0..3u64 {
+            let b = ba.rebox(i);
+            assert!(free.iter().any(|p| std::ptr::eq(p.as_ptr(), &*b)));
+            ba.unbox(b);
+        }
+        let reuse = ba.address_reuse().unwrap();
+        assert_eq!((reuse.handouts, reuse.distinct_addresses, reuse.max_per_address), (3, 1, 3));
+        assert_eq!(reuse.reuse_factor(), 3.0);
+    }
+}
@@ -0,0 +1,110 @@
+//! Events of the `tracing` crate, enabled by the `tracing` feature.
+
+use crate::BoxingArena;
+
+impl<T> BoxingArena<T> {
+    /// Set the name that the `tracing` events of the arena carry, to tell arenas of the
+    /// same type apart.
+    pub fn set_name(&mut self, name: &'static str) {
+        self.name = Some(name);
+    }
+
+    /// Return the name that the `tracing` events of the arena carry, if one was set.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Emit the event of a box handed out, from a free box or from a new allocation.
+    pub(crate) fn trace_hand_out(&self, hit: bool) {
+        let (name, capacity) = (self.name.unwrap_or_default(), self.items.len());
+        let type_name = core::any::type_name::<T>();
+        if hit {
+            tracing::trace!(arena = name, type_name, capacity, "reused a free box");
+        } else {
+            tracing::trace!(arena = name, type_name, capacity, "allocated a new box");
+        }
+    }
+
+    /// Emit the event of the free boxes going from `from` to the current number.
+    pub(crate) fn trace_resize(&self, from: usize) {
+        let (name, capacity) = (self.name.unwrap_or_default(), self.items.len());
+        let type_name = core::any::type_name::<T>();
+        if capacity < from {
+            tracing::debug!(arena = name, type_name, capacity, from, "released free boxes");
+        } else {
+            tracing::debug!(arena = name, type_name, capacity, from, "allocated free boxes");
+        }
+    }
+
+    /// Emit the event of the arena being dropped, with the free boxes it releases.
+    pub(crate) fn trace_drop(&self) {
+        let (name, capacity) = (self.name.unwrap_or_default(), self.items.len());
+        let type_name = core::any::type_name::<T>();
+        tracing::debug!(arena = name, type_name, capacity, "dropped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Keeps the message and the `arena` field of each event.
+    #[derive(Default)]
+    struct Events(Arc<Mutex<Vec<String>>>);
+
+    struct Fields(String);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" || field.name() == "arena" {
+                self.0 += &format!("{:?};", value).replace('"', "");
+            }
+        }
+    }
+
+    impl Subscriber for Events {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields(String::new());
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn events() {
+        let events = Events::default();
+        let list = events.0.clone();
+        tracing::subscriber::with_default(events, || {
+            let mut ba = BoxingArena::new();
+            ba.set_name("requests");
+            let a = ba.rebox(1u32);
+            ba.unbox(a);
+            let b = ba.rebox(2u32);
+            ba.unbox(b);
+            ba.trim(0);
+        });
+        assert_eq!(
+            *list.lock().unwrap(),
+            [
+                "allocated a new box;requests;",
+                "reused a free box;requests;",
+                "released free boxes;requests;",
+                "dropped;requests;",
+            ]
+        );
+    }
+}
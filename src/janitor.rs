@@ -0,0 +1,140 @@
+//! A background thread running the housekeeping of registered arenas.
+
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{BoxingArena, SyncBoxingArena};
+
+/// A shared arena whose housekeeping can be run from any thread.
+pub trait Maintain: Send + Sync {
+    /// Run `BoxingArena::maintain`, then `BoxingArena::trim_idle` with `max_idle` if given.
+    /// Does nothing, without blocking, if the arena is busy.
+    fn maintain_shared(&self, max_idle: Option<Duration>);
+}
+
+fn maintain<T>(arena: &mut BoxingArena<T>, max_idle: Option<Duration>) {
+    arena.maintain();
+    if let Some(max_idle) = max_idle {
+        arena.trim_idle(max_idle);
+    }
+}
+
+impl<T: Send> Maintain for Mutex<BoxingArena<T>> {
+    fn maintain_shared(&self, max_idle: Option<Duration>) {
+        if let Ok(mut arena) = self.try_lock() {
+            maintain(&mut arena, max_idle);
+        }
+    }
+}
+
+impl<T: Send> Maintain for SyncBoxingArena<T> {
+    fn maintain_shared(&self, max_idle: Option<Duration>) {
+        if let Some(mut arena) = self.try_lock() {
+            maintain(&mut arena, max_idle);
+        }
+    }
+}
+
+/// A registered arena, with its `max_idle`.
+type Entry = (Weak<dyn Maintain>, Option<Duration>);
+
+struct Shared {
+    arenas: Mutex<Vec<Entry>>,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+/// A background thread that periodically runs the housekeeping of the arenas registered with
+/// it, for long-lived programs whose request path has no natural point to trim: each period,
+/// it calls `maintain`, applying their capacity policies, and optionally `trim_idle`. The
+/// thread stops when the janitor is dropped.
+pub struct Janitor {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Janitor {
+    /// Spawn the thread, running the housekeeping every `period`.
+    pub fn spawn(period: Duration) -> io::Result<Self> {
+        let shared = Arc::new(Shared {
+            arenas: Mutex::new(Vec::new()),
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("boxing-arena-janitor".into())
+            .spawn(move || thread_shared.run(period))?;
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Register an arena, also releasing its free boxes that sat unused for longer than
+    /// `max_idle` if given. The janitor does not keep the arena alive, and dropped arenas are
+    /// forgotten.
+    pub fn register<S: Maintain + 'static>(&self, arena: &Arc<S>, max_idle: Option<Duration>) {
+        let arena: Arc<dyn Maintain> = arena.clone();
+        let mut arenas = self.shared.arenas.lock().unwrap_or_else(|e| e.into_inner());
+        arenas.retain(|(e, _)| e.strong_count() > 0);
+        arenas.push((Arc::downgrade(&arena), max_idle));
+    }
+}
+
+impl Shared {
+    fn run(&self, period: Duration) {
+        let mut stopped = self.stopped.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            stopped = match self.wake.wait_timeout(stopped, period) {
+                Ok((stopped, _)) => stopped,
+                Err(e) => e.into_inner().0,
+            };
+            if *stopped {
+                return;
+            }
+            let arenas: Vec<_> = {
+                let arenas = self.arenas.lock().unwrap_or_else(|e| e.into_inner());
+                arenas.iter().filter_map(|(e, idle)| Some((e.upgrade()?, *idle))).collect()
+            };
+            for (arena, max_idle) in arenas {
+                arena.maintain_shared(max_idle);
+            }
+        }
+    }
+}
+
+impl Drop for Janitor {
+    fn drop(&mut self) {
+        *self.shared.stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Bounded;
+
+    #[test]
+    fn janitor() {
+        let janitor = Janitor::spawn(Duration::from_millis(1)).unwrap();
+        let arena = Arc::new(Mutex::new(BoxingArena::<u64>::with_capacity(4)));
+        arena.lock().unwrap().set_capacity_policy(Bounded(1));
+        janitor.register(&arena, None);
+        let shared = Arc::new(SyncBoxingArena::<u64>::with_capacity(4));
+        janitor.register(&shared, Some(Duration::from_millis(5)));
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while arena.lock().unwrap().capacity() != 1 || shared.capacity() != 0 {
+            assert!(std::time::Instant::now() < deadline, "the arenas were not maintained");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        drop(janitor);
+    }
+}
@@ -0,0 +1,112 @@
+//! Shared, read-only leases of a box from an arena.
+
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use crate::BoxingArena;
+
+struct Shared<T> {
+    value: ManuallyDrop<Box<T>>,
+    arena: Arc<Mutex<BoxingArena<T>>>,
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        let b = unsafe { ManuallyDrop::take(&mut self.value) };
+        let v = self
+            .arena
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .unbox(b);
+        // Outside of the lock.
+        drop(v);
+    }
+}
+
+/// A reference-counted, read-only lease of a box from a shared arena. Clones give more
+/// readers access to the same value, and the box goes back to the arena when the last of
+/// them is dropped.
+pub struct LeaseArc<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> LeaseArc<T> {
+    /// Store `v` in a box from `arena`, leased until all the clones are dropped.
+    #[track_caller]
+    pub fn new_in(v: T, arena: &Arc<Mutex<BoxingArena<T>>>) -> Self {
+        let b = arena.lock().unwrap_or_else(|e| e.into_inner()).rebox(v);
+        Self {
+            shared: Arc::new(Shared {
+                value: ManuallyDrop::new(b),
+                arena: arena.clone(),
+            }),
+        }
+    }
+
+    /// Return the number of clones sharing the lease.
+    pub fn lease_count(this: &Self) -> usize {
+        Arc::strong_count(&this.shared)
+    }
+}
+
+impl<T> Clone for LeaseArc<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Deref for LeaseArc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.shared.value
+    }
+}
+
+impl_borrow!([T] LeaseArc<T>);
+impl_cmp!([T] LeaseArc<T>);
+
+// Like `Arc`, all the clones point to the same box.
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::StableDeref for LeaseArc<T> {}
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::CloneStableDeref for LeaseArc<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lease_arc() {
+        let arena = Arc::new(Mutex::new(BoxingArena::new()));
+        let lease = LeaseArc::new_in(vec![1u8; 32], &arena);
+        let leases: Vec<_> = (0..4).map(|_| lease.clone()).collect();
+        assert_eq!(LeaseArc::lease_count(&lease), 5);
+        drop(lease);
+
+        let readers: Vec<_> = leases
+            .into_iter()
+            .map(|lease| std::thread::spawn(move || lease.iter().map(|&b| b as u32).sum::<u32>()))
+            .collect();
+
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 32);
+        }
+        assert_eq!(arena.lock().unwrap().capacity(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "stable-deref")]
+    fn stable_deref() {
+        fn target<P: stable_deref_trait::CloneStableDeref<Target = [u8; 4]>>(p: &P) -> *const u8 {
+            p.deref().as_ptr()
+        }
+
+        let arena = Arc::new(Mutex::new(BoxingArena::new()));
+        let lease = LeaseArc::new_in([1u8; 4], &arena);
+        assert_eq!(target(&lease), target(&lease.clone()));
+    }
+}
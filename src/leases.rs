@@ -0,0 +1,153 @@
+//! Boxes handed out with a deadline for giving them back.
+
+use std::collections::BTreeMap;
+use std::panic::Location;
+use std::time::{Duration, Instant};
+
+use crate::{clock, provenance, BoxingArena};
+
+/// A box handed out by `BoxingArena::rebox_leased` that was not given back by its deadline,
+/// as passed to the callback set by `BoxingArena::set_overdue_callback`.
+#[derive(Clone, Copy, Debug)]
+pub struct OverdueLease {
+    /// The address of the box.
+    pub address: usize,
+    /// When the box was due back.
+    pub deadline: Instant,
+    /// How late it is, as of the `maintain` call that reported it.
+    pub overdue_by: Duration,
+    /// The location of the call that handed the box out.
+    pub caller: &'static Location<'static>,
+}
+
+struct Lease {
+    deadline: Instant,
+    caller: &'static Location<'static>,
+    reported: bool,
+}
+
+type OverdueCallback = Box<dyn FnMut(&OverdueLease) + Send>;
+
+/// The leases of an arena, by address of the box.
+#[derive(Default)]
+pub(crate) struct Leases {
+    map: BTreeMap<usize, Lease>,
+    on_overdue: Option<OverdueCallback>,
+}
+
+impl Leases {
+    pub(crate) const fn new() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            on_overdue: None,
+        }
+    }
+
+    /// Forget the lease of the box at `address`, if any.
+    pub(crate) fn end(&mut self, address: usize) {
+        if !self.map.is_empty() {
+            self.map.remove(&address);
+        }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Like `rebox`, but the box is expected back through `unbox` within `ttl`. Otherwise,
+    /// `maintain` reports it as overdue, once, to the callback set by `set_overdue_callback`.
+    /// On targets without a clock, such as wasm32 in the browser, this is the same as `rebox`,
+    /// and so it is for a `ttl` too long for a deadline to be represented, which is never
+    /// overdue.
+    #[track_caller]
+    pub fn rebox_leased(&mut self, v: T, ttl: Duration) -> Box<T> {
+        let b = self.rebox(v);
+        if let Some(deadline) = clock::now().and_then(|now| now.checked_add(ttl)) {
+            let lease = Lease {
+                deadline,
+                caller: Location::caller(),
+                reported: false,
+            };
+            self.leases.map.insert(provenance::addr::<T>(&*b), lease);
+        }
+        b
+    }
+
+    /// Call `f` from `maintain` for each leased box that became overdue since the last call.
+    pub fn set_overdue_callback<F>(&mut self, f: F)
+    where
+        F: FnMut(&OverdueLease) + Send + 'static,
+    {
+        self.leases.on_overdue = Some(Box::new(f));
+    }
+
+    /// Iterate over the leased boxes that are overdue now, whether reported or not. Leased
+    /// boxes that were dropped instead of given back stay overdue until their address is
+    /// handed out again.
+    pub fn overdue_leases(&self) -> impl Iterator<Item = OverdueLease> + '_ {
+        // There are no leases without a clock.
+        let now = if self.leases.map.is_empty() { None } else { clock::now() };
+        self.leases.map.iter().filter_map(move |(&address, lease)| {
+            let now = now?;
+            Some(OverdueLease {
+                address,
+                deadline: lease.deadline,
+                overdue_by: now.checked_duration_since(lease.deadline)?,
+                caller: lease.caller,
+            })
+        })
+    }
+
+    /// Report the leases that became overdue to the callback, if one is set.
+    pub(crate) fn report_overdue_leases(&mut self) {
+        let leases = &mut self.leases;
+        let (f, now) = match (&mut leases.on_overdue, leases.map.is_empty()) {
+            (Some(f), false) => (f, clock::now().unwrap()),
+            _ => return,
+        };
+        for (&address, lease) in leases.map.iter_mut() {
+            if lease.reported || lease.deadline > now {
+                continue;
+            }
+            lease.reported = true;
+            f(&OverdueLease {
+                address,
+                deadline: lease.deadline,
+                overdue_by: now - lease.deadline,
+                caller: lease.caller,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn overdue() {
+        let reported = Arc::new(Mutex::new(vec![]));
+        let mut ba = BoxingArena::new();
+        let r = reported.clone();
+        ba.set_overdue_callback(move |lease| r.lock().unwrap().push(*lease));
+
+        let line = line!() + 1;
+        let late = ba.rebox_leased(1u32, Duration::ZERO);
+        let returned = ba.rebox_leased(2u32, Duration::ZERO);
+        let on_time = ba.rebox_leased(3u32, Duration::from_secs(3600));
+        let never = ba.rebox_leased(4u32, Duration::MAX);
+        ba.unbox(returned);
+
+        ba.maintain();
+        ba.maintain();
+        let reported = reported.lock().unwrap().clone();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].address, &*late as *const u32 as usize);
+        assert_eq!(reported[0].caller.line(), line);
+
+        assert_eq!(ba.overdue_leases().count(), 1);
+        ba.unbox(late);
+        ba.unbox(on_time);
+        ba.unbox(never);
+        assert_eq!(ba.overdue_leases().count(), 0);
+    }
+}
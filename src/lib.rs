@@ -1,4 +1,8 @@
 #![deny(missing_docs)]
+// The tests use the standard library even without the `std` feature, which leaves out the
+// code that needs it all the same.
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 //! The `boxing-arena` crate provides a very simply reuse of `Box` allocation by
 //! keeping a vector of reusable `Box` allocations that can be used when wanting to
 //! wrap a value in `Box`.
@@ -26,18 +30,427 @@
 //! // Instead of letting Rust drop and deallocate the Box, we do:
 //! ba.unbox(boxed_big_value);
 //! ```
+//!
+//! Optional Cargo features:
+//!
+//! * `std`, enabled by default: the types relying on threads, clocks or hash maps, such as
+//!   `SyncBoxingArena` and leases, and all the other features. Without it, the crate is
+//!   `no_std` and only needs `alloc`, for targets such as embedded firmware.
+//! * `diagnostics`: the `diagnostics` module, dumping the stats of registered arenas on panic,
+//!   and `BoxingArena::inspect_free` and `address_reuse`, for verifying that boxes are
+//!   recycled.
+//! * `signal-dump`: dumping the stats of registered arenas upon a signal, on unix.
+//! * `checked`: tracking of the boxes handed out by each arena and where, with panics
+//!   pointing at the caller when a box is given back twice, or optionally when it was not
+//!   handed out by the arena, and `check_invariants` for validating an arena's internal
+//!   state.
+//! * `call-stacks`: counting of the hits and misses of each arena by call stack, written
+//!   out in the folded format of flamegraph tools.
+//! * `poison`: filling of free boxes with the byte `0xde`, verified when they are reused,
+//!   so that writes through dangling pointers fail deterministically, pointing at the box.
+//! * `zeroize`: wiping of the boxes given back to arenas, and of those they deallocate,
+//!   through the `zeroize` crate, so that values such as secrets do not linger in free
+//!   memory.
+//! * `tracing`: events of the `tracing` crate when arenas allocate, reuse or release boxes,
+//!   and when they are dropped, with the name given by `BoxingArena::set_name`.
+//! * `chaos`: a testing mode in which arenas randomly pretend to have no free boxes, and
+//!   drop the boxes given back, see `BoxingArena::set_chaos`.
+//! * `op-log`: recording of the last operations of arenas in an `OpRing`, for post-mortem
+//!   analysis.
+//! * `parking-lot`: the mutex of `parking_lot` for `SyncBoxingArena`.
+//! * `semaphore`: `SemaphorePool`, whose boxes hold permits of a `tokio` semaphore.
+//! * `pressure`: the `pressure` module, trimming the arenas of `registry` when the system runs low
+//!   on memory.
+//! * `registry`: the `registry` module, where arenas are registered by name, in the
+//!   registry of `diagnostics`, for trimming all of them at once and collecting their stats.
+//! * `mlock`: locking of the boxes of arenas in RAM, so that they are never swapped out, see
+//!   `BoxingArena::set_locked`.
+//! * `rayon`: `BoxingArena::resize_capacity_parallel`, pre-warming arenas across the
+//!   threads of a `rayon` pool.
+//! * `no-panic`: verification that `rebox`, `try_rebox`, `try_rebox_value` and `unbox`
+//!   cannot unwind, for calling them from FFI callbacks. Optimized builds using them fail to
+//!   link otherwise, and panics in callbacks, such as samplers, abort the process.
+//! * `ffi`: `FfiPool`, a handle to a shared arena with a stable layout, for recycling boxes
+//!   across dynamic libraries built by different compilers, and `boxing_arena_*` C
+//!   functions over an `ErasedArena` of allocations of one layout, for C and C++ code.
+//! * `serde`: serialization of the box types as their value, and `ReboxSeed` and
+//!   `PooledBoxSeed`, deserializing into a box from an arena.
+//! * `stable-deref`: `StableDeref` implementations for the box types that keep their value
+//!   in place, such as `BoundedBox` and `LeaseArc`, for use with self-referential wrappers.
+//! * `loom`: synchronization through `loom` in `ConcurrentBoxingArena`, `SyncBoxingArena`
+//!   and `ShardedBoxingArena`, for model checking code using them within `loom::model`,
+//!   outside of which they cannot be used.
+//! * `bytes`: `BytesArena`, pooling the `BytesMut` buffers of the `bytes` crate.
+//! * `derive`: `#[derive(Pooled)]`, giving a type an arena of its own in each thread, see
+//!   `Pooled`.
+//! * `strict-provenance`: pointer handling through the strict-provenance APIs, for targets
+//!   where pointers are more than addresses, such as CHERI.
+//! * `allocator-api`: `ArenaAllocator`, an arena usable as the `Allocator` of standard
+//!   containers. It needs a nightly compiler.
+//! * `allocator-api2`: `ArenaAllocator`, for the containers of the `allocator-api2` crate,
+//!   on stable.
+//!
+//! The crate does not depend on OS facilities beyond the standard library's, and also works
+//! on wasm32, where shared arenas can be used from workers when built with atomics. Without
+//! a clock, as in the browser, timestamps are left at zero and leases never become overdue.
+//!
+//! All allocations and deallocations of arenas, including those of `resize_capacity` and
+//! `trim`, go through the global allocator, as those of `Box` do, so a `#[global_allocator]`
+//! such as jemalloc, mimalloc or an accounting wrapper sees all of them. Arenas are not
+//! generic over another allocator, since the boxes they hand out are plain `Box<T>`s, which
+//! anyone may drop into the global allocator. For boxes made by code that cannot be changed to
+//! use an arena, `PoolingAlloc` is a global allocator pooling the allocations of one layout.
+
+extern crate alloc;
+// For the code generated by `#[derive(Pooled)]` in tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as boxing_arena;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+/// Implement `Borrow<T>` and `AsRef<T>`, and with `mut` also `BorrowMut<T>` and `AsMut<T>`,
+/// for a box type with the given generic parameters that derefs to `T`, as `Box<T>` does.
+macro_rules! impl_borrow {
+    ([$($gen:tt)*] $ty:ty) => {
+        impl<$($gen)*> core::borrow::Borrow<T> for $ty {
+            fn borrow(&self) -> &T {
+                self
+            }
+        }
+
+        impl<$($gen)*> AsRef<T> for $ty {
+            fn as_ref(&self) -> &T {
+                self
+            }
+        }
+    };
+    ([$($gen:tt)*] $ty:ty, mut) => {
+        impl_borrow!([$($gen)*] $ty);
+
+        impl<$($gen)*> core::borrow::BorrowMut<T> for $ty {
+            fn borrow_mut(&mut self) -> &mut T {
+                self
+            }
+        }
+
+        impl<$($gen)*> AsMut<T> for $ty {
+            fn as_mut(&mut self) -> &mut T {
+                self
+            }
+        }
+    };
+}
+
+/// Implement `PartialEq`, `Eq`, `PartialOrd`, `Ord` and `Hash` for a box type with the given
+/// generic parameters, comparing and hashing the values, as `Box<T>` does.
+macro_rules! impl_cmp {
+    ([$($gen:tt)*] $ty:ty) => {
+        impl<$($gen)*> PartialEq for $ty
+        where
+            T: PartialEq,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                **self == **other
+            }
+        }
+
+        impl<$($gen)*> Eq for $ty where T: Eq {}
+
+        impl<$($gen)*> PartialOrd for $ty
+        where
+            T: PartialOrd,
+        {
+            fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                (**self).partial_cmp(&**other)
+            }
+        }
+
+        impl<$($gen)*> Ord for $ty
+        where
+            T: Ord,
+        {
+            fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+                (**self).cmp(&**other)
+            }
+        }
+
+        impl<$($gen)*> core::hash::Hash for $ty
+        where
+            T: core::hash::Hash,
+        {
+            fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                (**self).hash(state)
+            }
+        }
+    };
+}
+
+mod aligned;
+#[cfg(any(feature = "allocator-api", feature = "allocator-api2"))]
+mod allocator;
+mod arena_box;
+mod arena_map;
+#[cfg(feature = "std")]
+mod async_arena;
+#[cfg(feature = "std")]
+mod audit;
+mod auto_tune;
+mod borrowed;
+#[cfg(feature = "std")]
+mod bounded;
+mod buffer;
+#[cfg(feature = "bytes")]
+mod bytes_arena;
+#[cfg(feature = "call-stacks")]
+mod call_stacks;
+#[cfg(feature = "chaos")]
+mod chaos;
+#[cfg(feature = "checked")]
+mod checked;
+mod checkpoint;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(target_has_atomic = "64")]
+mod concurrent;
+mod counted;
+#[cfg(feature = "std")]
+mod cow;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+#[cfg(feature = "std")]
+mod dyn_arena;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fragmentation;
+mod free_list;
+mod frozen;
+#[cfg(feature = "std")]
+mod future_pool;
+mod handle;
+mod hooks;
+#[cfg(feature = "std")]
+mod idle;
+mod inline_arena;
+#[cfg(feature = "diagnostics")]
+mod inspect;
+#[cfg(feature = "tracing")]
+mod instrument;
+#[cfg(feature = "std")]
+mod janitor;
+#[cfg(feature = "std")]
+mod lease_arc;
+#[cfg(feature = "std")]
+mod leases;
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "mlock")]
+mod locked;
+mod mock;
+mod object_pool;
+#[cfg(feature = "op-log")]
+mod op_log;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod policy;
+mod pool;
+#[cfg(feature = "std")]
+mod pooled;
+mod pooled_box;
+#[cfg(target_has_atomic = "8")]
+mod pooling_alloc;
+mod provenance;
+#[cfg(feature = "poison")]
+mod poison;
+#[cfg(feature = "pressure")]
+pub mod pressure;
+mod rebox_iter;
+mod region_arena;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(all(unix, feature = "std"))]
+mod residency;
+#[cfg(feature = "std")]
+mod returns;
+mod sampling;
+#[cfg(feature = "semaphore")]
+mod semaphore;
+#[cfg(feature = "serde")]
+mod serialize;
+mod set;
+#[cfg(feature = "std")]
+mod sharded;
+mod slice_arena;
+mod small_box;
+mod stats;
+mod string_arena;
+#[cfg(any(target_has_atomic = "64", feature = "std"))]
+mod sync;
+#[cfg(feature = "std")]
+mod sync_arena;
+#[cfg(feature = "std")]
+mod trace;
+mod vec_arena;
+#[cfg(feature = "zeroize")]
+mod wipe;
+
+pub use aligned::{CacheAligned, PageAligned};
+#[cfg(any(feature = "allocator-api", feature = "allocator-api2"))]
+pub use allocator::ArenaAllocator;
+pub use arena_box::ArenaBox;
+pub use arena_map::{ArenaKey, ArenaMap};
+#[cfg(feature = "std")]
+pub use async_arena::{AsyncArenaGuard, AsyncBoxingArena, RESIZE_CHUNK};
+#[cfg(feature = "std")]
+pub use audit::Audit;
+pub use borrowed::BorrowedArena;
+#[cfg(feature = "std")]
+pub use bounded::{BoundedBox, BoundedPool};
+pub use buffer::BufferArena;
+#[cfg(feature = "bytes")]
+pub use bytes_arena::BytesArena;
+pub use checkpoint::ArenaCheckpoint;
+#[cfg(target_has_atomic = "64")]
+pub use concurrent::{ConcurrentBoxingArena, ReturnLane};
+#[cfg(target_has_atomic = "ptr")]
+pub use counted::ArcArena;
+pub use counted::RcArena;
+#[cfg(feature = "std")]
+pub use cow::{CowArena, CowSlot, PooledCow};
+#[cfg(feature = "std")]
+pub use dyn_arena::DynBoxingArena;
+#[cfg(feature = "ffi")]
+pub use ffi::{
+    boxing_arena_capacity, boxing_arena_free, boxing_arena_new, boxing_arena_rebox,
+    boxing_arena_unbox, ErasedArena, FfiBox, FfiPool, FfiPoolVtable,
+};
+pub use fragmentation::{FragmentationReport, PAGE_SIZE};
+pub use free_list::{Hardening, ReuseOrder};
+pub use frozen::FrozenArena;
+#[cfg(feature = "std")]
+pub use future_pool::{FuturePool, PooledFuture};
+pub use handle::ArenaHandle;
+pub use hooks::ArenaHooks;
+pub use inline_arena::InlineArena;
+#[cfg(feature = "diagnostics")]
+pub use inspect::AddressReuse;
+#[cfg(feature = "std")]
+pub use janitor::{Janitor, Maintain};
+#[cfg(feature = "std")]
+pub use lease_arc::LeaseArc;
+#[cfg(feature = "std")]
+pub use leases::OverdueLease;
+#[cfg(feature = "std")]
+pub use local::{rebox, unbox, with_local_arena};
+pub use mock::{MockArena, MockCalls};
+pub use object_pool::{ObjectPool, PooledObject, Reset, ReusePool};
+#[cfg(feature = "op-log")]
+pub use op_log::{OpKind, OpRecord, OpRing};
+pub use policy::{Bounded, CapacityPolicy, HighWatermark, Unbounded};
+pub use pool::{Pool, SharedPool};
+#[cfg(feature = "derive")]
+pub use boxing_arena_derive::Pooled;
+#[cfg(feature = "std")]
+pub use pooled::Pooled;
+pub use pooled_box::PooledBox;
+#[cfg(target_has_atomic = "8")]
+pub use pooling_alloc::PoolingAlloc;
+pub use rebox_iter::{MapReboxed, ReboxIterExt};
+pub use region_arena::{RegionArena, RegionBox};
+#[cfg(feature = "std")]
+pub use returns::ArenaSender;
+#[cfg(all(unix, feature = "std"))]
+pub use residency::Residency;
+pub use sampling::ReboxSample;
+#[cfg(feature = "semaphore")]
+pub use semaphore::{PermitBox, SemaphorePool};
+#[cfg(feature = "serde")]
+pub use serialize::{PooledBoxSeed, ReboxSeed};
+pub use set::ArenaSet;
+#[cfg(feature = "std")]
+pub use sharded::ShardedBoxingArena;
+pub use slice_arena::SliceArena;
+pub use small_box::SmallPooledBox;
+pub use stats::{AllocationSavings, ArenaStats};
+pub use string_arena::StringArena;
+#[cfg(feature = "std")]
+pub use sync_arena::SyncBoxingArena;
+#[cfg(feature = "std")]
+pub use trace::{ReplayReport, Trace, TraceEvent, TraceOp, TraceRecorder};
+pub use vec_arena::VecArena;
 
 /// The BoxingArena struct.
 pub struct BoxingArena<T> {
-    items: Vec<*mut T>,
+    items: free_list::FreeList<T>,
+    max_capacity: Option<usize>,
+    memory_budget: Option<usize>,
+    prefault: bool,
+    sampler: Option<sampling::Sampler>,
+    hooks: hooks::ArenaHooks,
+    policy: Option<Box<dyn policy::CapacityPolicy>>,
+    tune: Option<auto_tune::AutoTune>,
+    #[cfg(feature = "std")]
+    leases: leases::Leases,
+    counters: stats::Counters,
+    #[cfg(feature = "std")]
+    returns: Option<returns::Returns<T>>,
+    #[cfg(feature = "std")]
+    idle: idle::IdleMarks,
+    #[cfg(feature = "std")]
+    outstanding: audit::Outstanding,
+    #[cfg(feature = "checked")]
+    checkouts: checked::Checkouts,
+    #[cfg(feature = "diagnostics")]
+    addresses: inspect::AddressCounts,
+    #[cfg(feature = "op-log")]
+    op_log: Option<alloc::sync::Arc<op_log::OpRing>>,
+    #[cfg(feature = "chaos")]
+    chaos: chaos::Chaos,
+    #[cfg(feature = "mlock")]
+    locking: locked::Locking,
+    #[cfg(feature = "call-stacks")]
+    call_stacks: call_stacks::CallStacks,
+    #[cfg(feature = "tracing")]
+    name: Option<&'static str>,
 }
 
 impl<T> BoxingArena<T> {
     /// Create a new BoxingArena. All memory used by empty boxes will be de-allocated when
-    /// the BoxingArena is dropped. No allocation is made by this function.
-    pub fn new() -> Self {
+    /// the BoxingArena is dropped. No allocation is made by this function, which is `const`,
+    /// so that an arena can be kept in a `static`, for example behind a `Mutex`.
+    pub const fn new() -> Self {
         Self {
-            items: vec![],
+            items: free_list::FreeList::new(),
+            max_capacity: None,
+            memory_budget: None,
+            prefault: false,
+            sampler: None,
+            hooks: hooks::ArenaHooks::new(),
+            policy: None,
+            tune: None,
+            #[cfg(feature = "std")]
+            leases: leases::Leases::new(),
+            counters: stats::Counters::new(),
+            #[cfg(feature = "std")]
+            returns: None,
+            #[cfg(feature = "std")]
+            idle: idle::IdleMarks::new(),
+            #[cfg(feature = "std")]
+            outstanding: audit::Outstanding::new(),
+            #[cfg(feature = "checked")]
+            checkouts: checked::Checkouts::new(),
+            #[cfg(feature = "diagnostics")]
+            addresses: inspect::AddressCounts::new(),
+            #[cfg(feature = "op-log")]
+            op_log: None,
+            #[cfg(feature = "chaos")]
+            chaos: chaos::Chaos::new(),
+            #[cfg(feature = "mlock")]
+            locking: locked::Locking::new(),
+            #[cfg(feature = "call-stacks")]
+            call_stacks: call_stacks::CallStacks::new(),
+            #[cfg(feature = "tracing")]
+            name: None,
         }
     }
 
@@ -48,97 +461,1153 @@ impl<T> BoxingArena<T> {
         ba
     }
 
+    /// Create a new BoxingArena that keeps at most `max` free boxes, freeing the boxes given
+    /// back beyond that, see `set_max_capacity`. No allocation is made by this function.
+    pub fn with_max_capacity(max: usize) -> Self {
+        let mut ba = BoxingArena::new();
+        ba.set_max_capacity(Some(max));
+        ba
+    }
+
+    /// Create a new BoxingArena that reuses its free boxes in the given order, see
+    /// `set_reuse_order`. No allocation is made by this function.
+    pub fn with_reuse_order(order: ReuseOrder) -> Self {
+        let mut ba = BoxingArena::new();
+        ba.set_reuse_order(order);
+        ba
+    }
+
+    /// Create a new, empty BoxingArena with the same settings as this one, for example for a
+    /// per-task pool. No allocation is made by this function. The fork shares the hooks, and
+    /// gets the capacity policy returned by `CapacityPolicy::fork`, and the auto-tune window.
+    /// It does not keep the sampler, the overdue lease callback or the senders of `sender`,
+    /// and its stats start from zero.
+    pub fn fork_empty(&self) -> Self {
+        let mut fork = Self {
+            items: self.items.fork_empty(),
+            max_capacity: self.max_capacity,
+            memory_budget: self.memory_budget,
+            prefault: self.prefault,
+            sampler: None,
+            hooks: self.hooks.clone(),
+            policy: self.policy.as_ref().and_then(|policy| policy.fork()),
+            tune: None,
+            #[cfg(feature = "std")]
+            leases: leases::Leases::new(),
+            counters: stats::Counters::new(),
+            #[cfg(feature = "std")]
+            returns: None,
+            #[cfg(feature = "std")]
+            idle: idle::IdleMarks::new(),
+            #[cfg(feature = "std")]
+            outstanding: self.outstanding.fork_empty(),
+            #[cfg(feature = "checked")]
+            checkouts: self.checkouts.fork_empty(),
+            #[cfg(feature = "diagnostics")]
+            addresses: inspect::AddressCounts::new(),
+            #[cfg(feature = "op-log")]
+            op_log: self.op_log.clone(),
+            #[cfg(feature = "chaos")]
+            chaos: self.chaos.clone(),
+            #[cfg(feature = "mlock")]
+            locking: self.locking.fork_empty(),
+            #[cfg(feature = "call-stacks")]
+            call_stacks: call_stacks::CallStacks::new(),
+            #[cfg(feature = "tracing")]
+            name: self.name,
+        };
+        fork.set_auto_tune(self.auto_tune());
+        fork
+    }
+
     /// This function unboxes the value but keeps the allocation for later reuse by the `rebox`
-    /// function.
+    /// function. If there is no memory left to keep track of it, the allocation is released.
+    #[track_caller]
     pub fn unbox(&mut self, v: Box<T>) -> T {
+        self.take_back(v, Self::keeps_returned)
+    }
+
+    /// Unbox the value, keeping the allocation if `keep` says so.
+    #[inline(always)]
+    #[track_caller]
+    fn take_back(&mut self, v: Box<T>, keep: fn(&mut Self) -> bool) -> T {
+        let guard = NoUnwind::arm();
         unsafe {
             let raw = Box::into_raw(v);
-            let v = std::ptr::read(raw);
-            self.items.push(raw);
+            let caller = core::panic::Location::caller();
+            no_unwind(|| {
+                #[cfg(feature = "checked")]
+                self.check_in(raw, caller);
+                #[cfg(feature = "op-log")]
+                self.record_op(op_log::OpKind::Unbox, raw, caller);
+                #[cfg(feature = "std")]
+                {
+                    self.leases.end(provenance::addr(raw));
+                    self.outstanding.given_back(provenance::addr(raw));
+                }
+                // Unused without the `checked` and `op-log` features.
+                let _ = caller;
+            });
+            let v = core::ptr::read(raw);
+            self.counters.returns += 1;
+            let pushed = if keep(self) { self.items.try_push(raw) } else { Err(raw) };
+            match pushed {
+                Ok(()) => self.counters.unboxes += 1,
+                Err(raw) => self.free_slot(raw),
+            }
+            guard.disarm();
             v
         }
     }
 
-    /// When boxing a value, the arena either allocates a new Box or uses an existing empty
-    /// allocation from a previous 'unbox` operation. In the latter case, allocation would be very
-    /// fast, and the overhead would be mostly the move into the box.
-    pub fn rebox(&mut self, v: T) -> Box<T> {
-        match self.items.pop() {
-            None => Box::new(v),
-            Some(raw_ptr) => {
-                unsafe {
-                    std::ptr::write(raw_ptr, v);
-                    Box::from_raw(raw_ptr)
-                }
+    /// Like `unbox`, but move the value straight into `dst`, whose previous contents are not
+    /// dropped, and return a reference to it. As for `try_rebox`, the stack overhead of this
+    /// function is guaranteed in the order of pointer-sized.
+    #[track_caller]
+    pub fn unbox_into<'a>(
+        &mut self,
+        b: Box<T>,
+        dst: &'a mut core::mem::MaybeUninit<T>,
+    ) -> &'a mut T {
+        let raw = Box::into_raw(b);
+        unsafe {
+            core::ptr::copy_nonoverlapping(raw, dst.as_mut_ptr(), 1);
+            self.keep_slot(raw, core::panic::Location::caller());
+            &mut *dst.as_mut_ptr()
+        }
+    }
+
+    /// Like `unbox`, but only keeps the allocation if the arena holds fewer free boxes than
+    /// allowed by its maximum capacity and memory budget, and its capacity policy agrees.
+    /// Otherwise the box is given back, and can simply be dropped.
+    #[track_caller]
+    pub fn try_unbox(&mut self, v: Box<T>) -> Result<T, Box<T>> {
+        if !self.keeps_returned() {
+            return Err(v);
+        }
+        Ok(self.take_back(v, |_| true))
+    }
+
+    /// Drop the value in place and keep the allocation, like `unbox` without moving the value
+    /// out first, which matters for large values that are only discarded.
+    #[track_caller]
+    pub fn recycle(&mut self, b: Box<T>) {
+        let raw = Box::into_raw(b);
+        // Freed instead of kept if dropping the value panics.
+        let slot = EmptySlot::new(raw);
+        unsafe {
+            core::ptr::drop_in_place(raw);
+            self.keep_slot(slot.into_raw(), core::panic::Location::caller());
+        }
+    }
+
+    /// Drop the values of the boxes and keep their allocations, as `recycle` does, making
+    /// room in the free list for the whole batch at once.
+    #[track_caller]
+    pub fn unbox_all<I: IntoIterator<Item = Box<T>>>(&mut self, boxes: I) {
+        let boxes = boxes.into_iter();
+        let _ = self.items.try_reserve(boxes.size_hint().0);
+        for b in boxes {
+            self.recycle(b);
+        }
+    }
+
+    /// When boxing a value, the arena either allocates a new Box or uses an existing empty
+    /// allocation from a previous 'unbox` operation. In the latter case, allocation would be very
+    /// fast, and the overhead would be mostly the move into the box.
+    ///
+    /// The value is built on the stack of the caller before it is moved in, and the compiler
+    /// does not guarantee to elide either copy. For values of kilobytes, `rebox_copy` copies
+    /// an existing value exactly once, and `rebox_with` or `rebox_uninit` let the value be
+    /// built in the box itself, field by field through raw pointers where no copy may be made.
+    #[track_caller]
+    pub fn rebox(&mut self, v: T) -> Box<T> {
+        let guard = NoUnwind::arm();
+        let boxed = match self.pop_free() {
+            None => unsafe { self.hand_out(Box::into_raw(no_unwind(|| Box::new(v))), false) },
+            Some(raw_ptr) => unsafe {
+                core::ptr::write(raw_ptr, v);
+                self.hand_out(raw_ptr, true)
+            },
+        };
+        guard.disarm();
+        boxed
+    }
+
+    /// Box each value of `iter` as the returned iterator is advanced, as `rebox` does, for
+    /// handing out a whole batch of boxes.
+    pub fn rebox_iter<'a, I>(&'a mut self, iter: I) -> impl Iterator<Item = Box<T>> + 'a
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'a,
+    {
+        iter.into_iter().map(move |v| self.rebox(v))
+    }
+
+    /// Construct a value directly inside a free (or new) box, instead of moving it in from
+    /// the stack as `rebox` does, which matters for large values. If `f` panics, the slot
+    /// goes back to the arena.
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the slot it is given, unless it panics.
+    #[track_caller]
+    pub unsafe fn rebox_with<F>(&mut self, f: F) -> Box<T>
+    where
+        F: FnOnce(&mut core::mem::MaybeUninit<T>),
+    {
+        let mut slot = self.take_slot();
+        f(slot.as_uninit());
+        slot.into_box()
+    }
+
+    /// Box a clone of `v`, constructed directly inside a free (or new) box as `rebox_with` does,
+    /// rather than on the stack. If cloning panics, the slot goes back to the arena.
+    #[track_caller]
+    pub fn rebox_clone(&mut self, v: &T) -> Box<T>
+    where
+        T: Clone,
+    {
+        unsafe {
+            self.rebox_with(|slot| {
+                slot.write(v.clone());
+            })
+        }
+    }
+
+    /// Box a copy of `v`, made with a single copy of its bytes straight into a free (or new)
+    /// box, rather than through the stack as `rebox(*v)` may do.
+    #[track_caller]
+    pub fn rebox_copy(&mut self, v: &T) -> Box<T>
+    where
+        T: Copy,
+    {
+        unsafe {
+            self.rebox_with(|slot| {
+                core::ptr::copy_nonoverlapping(v as *const T, slot.as_mut_ptr(), 1);
+            })
+        }
+    }
+
+    /// Box the default value of `T`, constructed directly inside a free (or new) box as
+    /// `rebox_with` does, for example for a blank buffer to fill in gradually.
+    #[track_caller]
+    pub fn rebox_default(&mut self) -> Box<T>
+    where
+        T: Default,
+    {
+        unsafe {
+            self.rebox_with(|slot| {
+                slot.write(T::default());
+            })
+        }
+    }
+
+    /// Return a free (or new) box without initializing it, for example to read data into it
+    /// directly. Once filled, `assume_init` turns it into a `Box<T>` to use with the arena as
+    /// any other, and `unbox_uninit` gives it back unfilled.
+    #[track_caller]
+    pub fn rebox_uninit(&mut self) -> Box<core::mem::MaybeUninit<T>> {
+        self.take_slot().into_uninit_box()
+    }
+
+    /// Turn a box from `rebox_uninit` into a box of its value.
+    ///
+    /// # Safety
+    ///
+    /// The box must be fully initialized.
+    pub unsafe fn assume_init(b: Box<core::mem::MaybeUninit<T>>) -> Box<T> {
+        Box::from_raw(Box::into_raw(b) as *mut T)
+    }
+
+    /// Give back a box without a value, such as one from `rebox_uninit`, keeping the
+    /// allocation as `unbox` does.
+    #[track_caller]
+    pub fn unbox_uninit(&mut self, b: Box<core::mem::MaybeUninit<T>>) {
+        unsafe { self.keep_slot(Box::into_raw(b) as *mut T, core::panic::Location::caller()) }
+    }
+
+    /// Take up to `n` of the free boxes, without allocating, as boxes of uninitialized memory
+    /// for code that fills storage itself, such as FFI. They can be given back later with
+    /// `return_free_boxes`, or used as those of `rebox_uninit`.
+    #[track_caller]
+    pub fn take_free_boxes(&mut self, n: usize) -> Vec<Box<core::mem::MaybeUninit<T>>> {
+        let n = n.min(self.items.reusable_len());
+        let mut boxes = Vec::with_capacity(n);
+        for _ in 0..n {
+            boxes.push(self.rebox_uninit());
+        }
+        boxes
+    }
+
+    /// Give back boxes without values, such as those of `take_free_boxes`, keeping their
+    /// allocations as `unbox_uninit` does.
+    #[track_caller]
+    pub fn return_free_boxes<I>(&mut self, boxes: I)
+    where
+        I: IntoIterator<Item = Box<core::mem::MaybeUninit<T>>>,
+    {
+        for b in boxes {
+            self.unbox_uninit(b);
+        }
+    }
+
+    /// Like `rebox`, but pin the box, for values that must not move once in use.
+    #[track_caller]
+    pub fn rebox_pinned(&mut self, v: T) -> core::pin::Pin<Box<T>> {
+        Box::into_pin(self.rebox(v))
+    }
+
+    /// Take the value out of a pinned box and keep the allocation, for values that may move.
+    #[track_caller]
+    pub fn unbox_pinned(&mut self, b: core::pin::Pin<Box<T>>) -> T
+    where
+        T: Unpin,
+    {
+        self.unbox(core::pin::Pin::into_inner(b))
+    }
+
+    /// Drop the value of a pinned box in place and keep the allocation, see `recycle`. The
+    /// value is not moved before being dropped, so this is fine for any `T`.
+    #[track_caller]
+    pub fn recycle_pinned(&mut self, b: core::pin::Pin<Box<T>>) {
+        self.recycle(unsafe { core::pin::Pin::into_inner_unchecked(b) })
+    }
+
+    /// Construct a pinned value directly inside a free (or new) box, at its final address.
+    /// This allows self-referential or address-sensitive values to be built in place.
+    ///
+    /// # Safety
+    ///
+    /// `f` must fully initialize the slot it is given, unless it panics.
+    #[track_caller]
+    pub unsafe fn rebox_pinned_with<F>(&mut self, f: F) -> core::pin::Pin<Box<T>>
+    where
+        F: FnOnce(core::pin::Pin<&mut core::mem::MaybeUninit<T>>),
+    {
+        let mut slot = self.take_slot();
+        f(core::pin::Pin::new_unchecked(slot.as_uninit()));
+        Box::into_pin(slot.into_box())
+    }
+
+    /// Like `rebox` but only if there are empty boxes. Return `None` if `*v` is `None`.
+    /// The stack overhead of this function is guaranteed in the order of pointer-sized.
+    /// New code should prefer `try_rebox_value`, which does not need the `Option`.
+    #[track_caller]
+    pub fn try_rebox(&mut self, v: &mut Option<T>) -> Option<Box<T>> {
+        let guard = NoUnwind::arm();
+        // Test the pre-conditions
+        let raw_ptr = match v {
+            Some(_) => self.pop_free(),
+            None => None,
+        };
+
+        let boxed = match (v.as_mut(), raw_ptr) {
+            (Some(v_ref), Some(raw_ptr)) => unsafe {
+                core::ptr::copy(v_ref, raw_ptr, 1);
+                core::ptr::write(v, None);
+                Some(self.hand_out(raw_ptr, true))
+            },
+            _ => None,
+        };
+
+        guard.disarm();
+        boxed
+    }
+
+    /// Like `rebox` but only if there are empty boxes, otherwise give the value back in `Err`.
+    /// The value is moved in place into the box, so that the stack overhead of this function
+    /// stays in the order of pointer-sized, as with `try_rebox`.
+    #[track_caller]
+    pub fn try_rebox_value(&mut self, v: T) -> Result<Box<T>, T> {
+        let guard = NoUnwind::arm();
+        let boxed = match self.pop_free() {
+            Some(raw_ptr) => unsafe {
+                core::ptr::write(raw_ptr, v);
+                Ok(self.hand_out(raw_ptr, true))
+            },
+            None => Err(v),
+        };
+        guard.disarm();
+        boxed
+    }
+
+    /// Consume a box of another type and reuse its allocation for `f`'s result, if both types
+    /// have the same layout. Otherwise this is the same as `rebox(f(*b))`.
+    #[track_caller]
+    pub fn map_reboxed<U, F>(&mut self, b: Box<U>, f: F) -> Box<T>
+    where
+        F: FnOnce(U) -> T,
+    {
+        if alloc::alloc::Layout::new::<U>() != alloc::alloc::Layout::new::<T>() {
+            return self.rebox(f(*b));
+        }
+
+        let raw = Box::into_raw(b);
+        let slot = EmptySlot::new(raw as *mut T);
+        let v = f(unsafe { core::ptr::read(raw) });
+        unsafe {
+            let raw = slot.into_raw();
+            core::ptr::write(raw, v);
+            self.hand_out(raw, true)
+        }
+    }
+
+    /// Apply `f` to the value of the box, writing the result back into the same allocation,
+    /// without going through the free list as `unbox` and `rebox` would. If `f` panics, the
+    /// allocation is freed.
+    pub fn map_box<F>(&mut self, b: Box<T>, f: F) -> Box<T>
+    where
+        F: FnOnce(T) -> T,
+    {
+        let raw = Box::into_raw(b);
+        let slot = EmptySlot::new(raw);
+        let v = f(unsafe { core::ptr::read(raw) });
+        unsafe {
+            core::ptr::write(slot.into_raw(), v);
+            Box::from_raw(raw)
+        }
+    }
+
+    /// Like `map_box`, for a fallible `f`. If it fails, the allocation is kept as `unbox`
+    /// does, and the error is returned.
+    #[track_caller]
+    pub fn try_map_box<E, F>(&mut self, b: Box<T>, f: F) -> Result<Box<T>, E>
+    where
+        F: FnOnce(T) -> Result<T, E>,
+    {
+        let raw = Box::into_raw(b);
+        let slot = EmptySlot::new(raw);
+        match f(unsafe { core::ptr::read(raw) }) {
+            Ok(v) => unsafe {
+                core::ptr::write(slot.into_raw(), v);
+                Ok(Box::from_raw(raw))
+            },
+            Err(e) => {
+                unsafe { self.keep_slot(slot.into_raw(), core::panic::Location::caller()) };
+                Err(e)
+            }
+        }
+    }
+
+    /// Store `value` in an optional boxed field, replacing the value in place if the field
+    /// already holds a box, and taking a box from the arena otherwise.
+    #[track_caller]
+    pub fn set_boxed(&mut self, field: &mut Option<Box<T>>, value: T) {
+        match field {
+            Some(b) => **b = value,
+            None => *field = Some(self.rebox(value)),
+        }
+    }
+
+    /// Take the value out of an optional boxed field, keeping the allocation for reuse.
+    #[track_caller]
+    pub fn take_unboxed(&mut self, field: &mut Option<Box<T>>) -> Option<T> {
+        field.take().map(|b| self.unbox(b))
+    }
+
+    /// Move each of the boxed values into an allocation from the arena, and keep their old
+    /// allocations as free boxes. Missing free boxes are allocated together up front, so a
+    /// collection built before the arena existed ends up on fewer pages.
+    #[track_caller]
+    pub fn rehome_all(&mut self, boxes: &mut [Box<T>]) {
+        if core::mem::size_of::<T>() == 0 {
+            // No allocations to move.
+            return;
+        }
+        let reusable = self.items.reusable_len();
+        if reusable < boxes.len() {
+            self.resize_capacity(self.items.len() + boxes.len() - reusable);
+        }
+
+        // Take all the target slots first, so the old allocations are not reused right away.
+        let targets: Vec<_> = (0..boxes.len()).map(|_| self.items.pop().unwrap()).collect();
+
+        for (b, target) in boxes.iter_mut().zip(targets) {
+            let old = unsafe {
+                core::ptr::copy_nonoverlapping(&**b as *const T, target, 1);
+                core::mem::replace(b, self.hand_out(target, true))
+            };
+            self.items.push(Box::into_raw(old));
+        }
+
+        self.counters.unboxes += boxes.len() as u64;
+        self.counters.returns += boxes.len() as u64;
+    }
+
+    /// Return the number of free boxes in the BoxingArena. Boxes of zero-sized types have no
+    /// allocation, so none are kept.
+    pub fn capacity(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Resize boxes pool to a given capacity, freeing boxes if it is smaller than the current
+    /// one. To only grow the pool, use `reserve`.
+    ///
+    /// # Panics
+    ///
+    /// Calls `handle_alloc_error`, which aborts by default, if memory runs out. See
+    /// `try_resize_capacity`.
+    pub fn resize_capacity(&mut self, size: usize) {
+        if self.try_resize_capacity(size).is_err() {
+            alloc::alloc::handle_alloc_error(alloc::alloc::Layout::new::<T>());
+        }
+    }
+
+    /// Like `resize_capacity`, but returns an error if memory runs out, for example to
+    /// pre-warm a pool under a memory limit. The boxes allocated before the failure are kept.
+    pub fn try_resize_capacity(&mut self, size: usize) -> Result<(), AllocError> {
+        let n = self.items.len();
+
+        if size < n {
+            #[cfg(feature = "op-log")]
+            let op_log = &self.op_log;
+            let hooks = &self.hooks;
+            self.items.release(n - size, |p| unsafe {
+                #[cfg(feature = "op-log")]
+                op_log::record_release(op_log, p);
+                hooks.deallocating(p);
+                Self::dealloc_slot(p)
+            });
+            self.shrink_sparse_metadata();
+            #[cfg(feature = "tracing")]
+            self.trace_resize(n);
+        }
+
+        // Zero-sized values are boxed without allocating.
+        if size > n && core::mem::size_of::<T>() != 0 {
+            self.items.try_reserve(size - n).map_err(|_| AllocError)?;
+            let prefault = self.prefault;
+            #[cfg(feature = "mlock")]
+            let locking = &mut self.locking;
+            let hooks = &self.hooks;
+            let mut failed = false;
+            self.items.extend((n..size).map_while(|_| unsafe {
+                let p = alloc::alloc::alloc(alloc::alloc::Layout::new::<T>()) as *mut T;
+                if p.is_null() {
+                    failed = true;
+                    return None;
+                }
+                if prefault {
+                    Self::prefault_slot(p);
+                }
+                #[cfg(feature = "mlock")]
+                locking.lock_new(p);
+                hooks.fresh(p);
+                Some(p)
+            }));
+            #[cfg(feature = "tracing")]
+            self.trace_resize(n);
+            if failed {
+                return Err(AllocError);
+            }
+        }
+        Ok(())
+    }
+
+    /// Allocate `additional` more free boxes. Unlike `resize_capacity`, this never frees
+    /// any.
+    ///
+    /// # Panics
+    ///
+    /// If the capacity overflows `usize`. Calls `handle_alloc_error`, which aborts by default,
+    /// if memory runs out. See `try_reserve`.
+    pub fn reserve(&mut self, additional: usize) {
+        let size = self.items.len().checked_add(additional).expect("capacity overflow");
+        self.resize_capacity(size)
+    }
+
+    /// Like `reserve`, but returns an error if memory runs out or the capacity overflows. The
+    /// boxes allocated before the failure are kept.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let size = self.items.len().checked_add(additional).ok_or(AllocError)?;
+        self.try_resize_capacity(size)
+    }
+
+    /// Trims capacity to the given size if it is larger.
+    pub fn trim(&mut self, size: usize) {
+        if self.items.len() > size {
+            self.resize_capacity(size)
+        }
+    }
+
+    /// Set the number of free boxes above which the arena stops keeping allocations, or `None`
+    /// for no limit, the default. Past it, `unbox` still returns the value but frees the box,
+    /// so that a burst of traffic does not inflate the arena for good, and `try_unbox` gives
+    /// the box back. Boxes allocated by `resize_capacity` do not count against it.
+    pub fn set_max_capacity(&mut self, max: Option<usize>) {
+        self.max_capacity = max;
+    }
+
+    /// Return the limit set by `set_max_capacity`.
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.max_capacity
+    }
+
+    /// Set the number of bytes that free boxes may hold before the arena stops keeping
+    /// allocations, or `None` for no limit, the default. Each box counts for the size of `T`,
+    /// see `capacity_bytes`. This applies together with `set_max_capacity`.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+    }
+
+    /// Return the limit set by `set_memory_budget`.
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// Return the number of free boxes allowed by both the maximum capacity and the memory
+    /// budget, or `None` if there is no limit.
+    pub fn effective_max_capacity(&self) -> Option<usize> {
+        let slot_size = Self::slot_size();
+        let by_budget = self.memory_budget.filter(|_| slot_size > 0).map(|b| b / slot_size);
+        match (self.max_capacity, by_budget) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Keep the free boxes sorted by address, and reuse the lowest addresses first. Live
+    /// values then concentrate in fewer pages while the highest ones go cold, and those are
+    /// also the first to be released when shrinking. Off by default.
+    pub fn set_address_ordered(&mut self, ordered: bool) {
+        self.items.set_address_ordered(ordered)
+    }
+
+    /// Return whether free boxes are reused lowest address first.
+    pub fn is_address_ordered(&self) -> bool {
+        self.items.is_address_ordered()
+    }
+
+    /// Set the order in which free boxes are reused, LIFO by default. In FIFO order, the hot
+    /// stack and address order do not apply, and shrinking releases the boxes that would be
+    /// reused last.
+    pub fn set_reuse_order(&mut self, order: ReuseOrder) {
+        self.items.set_reuse_order(order)
+    }
+
+    /// Return the order set by `set_reuse_order`.
+    pub fn reuse_order(&self) -> ReuseOrder {
+        self.items.reuse_order()
+    }
+
+    /// Harden the reuse of free boxes, for arenas of values that an attacker may influence,
+    /// such as those of a network-facing parser: boxes given back are kept in quarantine for
+    /// a while before they are reused, and the box to reuse is picked at random, so that a
+    /// dangling pointer to a box is unlikely to point to the next value boxed. Boxes in
+    /// quarantine are counted in the capacity, but `rebox` allocates rather than reuse them.
+    pub fn set_hardening(&mut self, hardening: Hardening) {
+        self.items.set_hardening(hardening)
+    }
+
+    /// Return the hardening set by `set_hardening`.
+    pub fn hardening(&self) -> Hardening {
+        self.items.hardening()
+    }
+
+    /// Keep up to `size` of the most recently returned free boxes in a separate hot stack that
+    /// is reused first, while older ones overflow into a cold list that is the first to be
+    /// released when shrinking. A size of 0, the default, keeps a single list.
+    pub fn set_hot_capacity(&mut self, size: usize) {
+        self.items.set_hot_limit(size)
+    }
+
+    /// Return the size of the hot stack set by `set_hot_capacity`.
+    pub fn hot_capacity(&self) -> usize {
+        self.items.hot_limit()
+    }
+
+    /// Make `resize_capacity` write to each page of the free boxes it allocates, so that the
+    /// page faults are taken there rather than on first use of the boxes. Off by default.
+    pub fn set_prefault(&mut self, prefault: bool) {
+        self.prefault = prefault;
+    }
+
+    /// Return whether newly allocated free boxes are pre-faulted.
+    pub fn prefaults(&self) -> bool {
+        self.prefault
+    }
+
+    /// Release the free boxes for which `keep` returns `false`, given the address of each free
+    /// box. This allows giving back exactly the memory that falls in a given address range.
+    pub fn retain_addresses<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(*const T) -> bool,
+    {
+        #[cfg(feature = "op-log")]
+        let op_log = &self.op_log;
+        let hooks = &self.hooks;
+        self.items.retain(|&p| {
+            if keep(p) {
+                return true;
+            }
+            #[cfg(feature = "op-log")]
+            op_log::record_release(op_log, p);
+            hooks.deallocating(p);
+            unsafe {
+                Self::dealloc_slot(p);
+            }
+            false
+        });
+    }
+
+    /// Move the free boxes of `other` into this arena, for example to keep the warmed-up pool
+    /// of a worker that shuts down. The boxes are not reallocated, and like those of
+    /// `resize_capacity` they do not count against the maximum capacity.
+    pub fn merge(&mut self, mut other: BoxingArena<T>) {
+        other.items.move_to(&mut self.items, other.items.len());
+    }
+
+    /// Move up to `n` of the free boxes into a new arena with the same settings, see
+    /// `fork_empty`, for example to hand a share of a pool warmed up at startup to each worker
+    /// thread.
+    pub fn split_off(&mut self, n: usize) -> Self {
+        let mut other = self.fork_empty();
+        self.items.move_to(&mut other.items, n);
+        other
+    }
+
+    /// Consume the arena, turning its free boxes into ordinary boxes of uninitialized memory,
+    /// in the order they would have been reused, and those in quarantine last, see
+    /// `set_hardening`. They can be handed to code that knows
+    /// nothing about arenas, and written with `Box::write`.
+    pub fn into_free_boxes(mut self) -> Vec<Box<core::mem::MaybeUninit<T>>> {
+        let mut boxes = Vec::with_capacity(self.items.len());
+        while let Some(p) = self.items.pop().or_else(|| self.items.pop_quarantined()) {
+            boxes.push(unsafe { Box::from_raw(p as *mut core::mem::MaybeUninit<T>) });
+        }
+        boxes
+    }
+
+    /// Trims capacity so that the free boxes hold at most `bytes`.
+    pub fn trim_bytes(&mut self, bytes: usize) {
+        match Self::slot_size() {
+            0 => {}
+            slot_size => self.trim(bytes / slot_size),
+        }
+    }
+
+    /// Return the number of bytes held by the free boxes.
+    pub fn capacity_bytes(&self) -> usize {
+        self.items.len() * Self::slot_size()
+    }
+
+    /// Return the size in bytes of each box allocation.
+    pub fn slot_size() -> usize {
+        core::mem::size_of::<T>()
+    }
+
+    /// Return the layout of each box allocation, which is that of `T`. For a larger alignment,
+    /// wrap `T`, as in `CacheAligned<T>`.
+    pub fn slot_layout() -> alloc::alloc::Layout {
+        alloc::alloc::Layout::new::<T>()
+    }
+
+    /// Return the number of bytes the arena holds on to: those of the free boxes, and those
+    /// of the bookkeeping of the free list.
+    pub fn memory_held(&self) -> usize {
+        self.capacity_bytes() + self.metadata_bytes()
+    }
+
+    fn metadata_bytes(&self) -> usize {
+        self.items.capacity() * core::mem::size_of::<*mut T>()
+    }
+
+    /// Periodic housekeeping, meant to be called from time to time by long-lived owners. It
+    /// trims the free boxes as the capacity policy decides, see `set_capacity_policy`, tunes
+    /// them toward the working set, see `set_auto_tune`, releases free list bookkeeping left
+    /// over by earlier trims, keeps the boxes given back by other threads, see `sender`, and
+    /// reports the leases that became overdue, see `rebox_leased`.
+    pub fn maintain(&mut self) {
+        let capacity = self.items.len();
+        if let Some(size) = self.policy.as_mut().and_then(|policy| policy.trim_to(capacity)) {
+            self.trim(size);
+        }
+        self.run_auto_tune();
+        self.shrink_sparse_metadata();
+        #[cfg(feature = "std")]
+        {
+            self.collect_returned();
+            self.report_overdue_leases();
+        }
+    }
+
+    /// Release the free list bookkeeping beyond what the free boxes need. Shrinking the
+    /// capacity already does so when less than half of it is used, as does `maintain`.
+    pub fn shrink_metadata(&mut self) {
+        self.items.shrink_to_fit();
+    }
+
+    fn shrink_sparse_metadata(&mut self) {
+        if self.items.capacity() > self.items.len() * 2 {
+            self.items.shrink_to_fit();
+        }
+    }
+
+    /// Return a snapshot of the arena's counters. See `ArenaStats::savings` for an estimate of
+    /// the allocator work saved by the arena.
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            hits: self.counters.hits,
+            misses: self.counters.misses,
+            unboxes: self.counters.unboxes,
+            returns: self.counters.returns,
+            capacity: self.items.len(),
+            peak_capacity: self.items.peak(),
+            slot_size: Self::slot_size(),
+            metadata_bytes: self.metadata_bytes(),
+        }
+    }
+
+    /// Lend at most `n` of the free boxes to a callee through a `BorrowedArena`. Any of them
+    /// that are not used, or that are given back, stay in this arena.
+    pub fn lend(&mut self, n: usize) -> BorrowedArena<'_, T> {
+        BorrowedArena::new(self, n)
+    }
+
+    /// Turn the arena into a `FrozenArena`, which never calls the allocator. The frozen arena
+    /// can take back at most as many boxes as there are free boxes at this point.
+    pub fn freeze(mut self) -> FrozenArena<T> {
+        self.items.set_hot_limit(0);
+        self.items.shrink_to_fit();
+        FrozenArena::new(self)
+    }
+}
+
+/// A free box taken out of an arena to be initialized. If dropped before `into_box`, for
+/// example while unwinding, it goes back to the free list.
+struct Slot<'a, T> {
+    arena: &'a mut BoxingArena<T>,
+    ptr: *mut T,
+    hit: bool,
+}
+
+impl<'a, T> Slot<'a, T> {
+    fn as_uninit(&mut self) -> &mut core::mem::MaybeUninit<T> {
+        unsafe { &mut *(self.ptr as *mut core::mem::MaybeUninit<T>) }
+    }
+
+    /// The slot must have been initialized.
+    #[track_caller]
+    unsafe fn into_box(self) -> Box<T> {
+        let (ptr, hit) = (self.ptr, self.hit);
+        let this = core::mem::ManuallyDrop::new(self);
+        core::ptr::read(&this.arena).hand_out(ptr, hit)
+    }
+
+    /// Hand out the slot as it is.
+    #[track_caller]
+    fn into_uninit_box(self) -> Box<core::mem::MaybeUninit<T>> {
+        let (ptr, hit) = (self.ptr, self.hit);
+        let this = core::mem::ManuallyDrop::new(self);
+        unsafe {
+            core::ptr::read(&this.arena).note_hand_out(ptr, hit);
+            Box::from_raw(ptr as *mut core::mem::MaybeUninit<T>)
+        }
+    }
+}
+
+impl<'a, T> Drop for Slot<'a, T> {
+    fn drop(&mut self) {
+        self.arena.items.push(self.ptr)
+    }
+}
+
+/// An allocation without a value, such as one on its way back to its arena. If dropped, for
+/// example while unwinding, it is freed.
+pub(crate) struct EmptySlot<T> {
+    ptr: *mut T,
+    _marker: core::marker::PhantomData<T>,
+}
+
+// Nothing of `T` is left in it.
+unsafe impl<T> Send for EmptySlot<T> {}
+
+impl<T> EmptySlot<T> {
+    pub(crate) fn new(ptr: *mut T) -> Self {
+        Self {
+            ptr,
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn into_raw(self) -> *mut T {
+        core::mem::ManuallyDrop::new(self).ptr
+    }
+}
+
+impl<T> Drop for EmptySlot<T> {
+    fn drop(&mut self) {
+        unsafe { BoxingArena::dealloc_slot(self.ptr) }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Take a free box, if there is one and chaos mode does not pretend otherwise.
+    #[inline]
+    fn pop_free(&mut self) -> Option<*mut T> {
+        #[cfg(feature = "chaos")]
+        if self.chaos.roll() {
+            return None;
+        }
+        self.items.pop()
+    }
+
+    /// Take a free box, or allocate a new one, to be initialized in place.
+    #[track_caller]
+    fn take_slot(&mut self) -> Slot<'_, T> {
+        let (ptr, hit) = match self.pop_free() {
+            Some(ptr) => (ptr, true),
+            None => {
+                let ptr = Box::into_raw(Box::new(core::mem::MaybeUninit::<T>::uninit()));
+                (ptr as *mut T, false)
+            }
+        };
+        Slot { arena: self, ptr, hit }
+    }
+
+    /// Turn an initialized slot into the box handed out to the caller. `hit` tells whether
+    /// it was a free box, or a new allocation.
+    #[inline]
+    #[track_caller]
+    unsafe fn hand_out(&mut self, p: *mut T, hit: bool) -> Box<T> {
+        self.note_hand_out(p, hit);
+        Box::from_raw(p)
+    }
+
+    /// Update the counters and run the hooks for a slot being handed out.
+    #[inline]
+    #[track_caller]
+    fn note_hand_out(&mut self, p: *mut T, hit: bool) {
+        if hit {
+            self.counters.hits += 1;
+        } else {
+            self.counters.misses += 1;
+        }
+        let caller = core::panic::Location::caller();
+        no_unwind(|| {
+            self.sample(hit, caller);
+            #[cfg(feature = "std")]
+            {
+                self.leases.end(provenance::addr(p));
+                self.outstanding.handed_out(provenance::addr(p), caller);
+            }
+            #[cfg(feature = "call-stacks")]
+            self.call_stacks.record(hit);
+            #[cfg(feature = "checked")]
+            self.check_out(p, caller);
+            #[cfg(feature = "diagnostics")]
+            self.addresses.handed_out(provenance::addr(p));
+            #[cfg(feature = "op-log")]
+            self.record_op(if hit { op_log::OpKind::Hit } else { op_log::OpKind::Miss }, p, caller);
+            #[cfg(feature = "tracing")]
+            self.trace_hand_out(hit);
+            #[cfg(feature = "mlock")]
+            if !hit {
+                self.locking.lock_new(p);
+            }
+            self.hooks.handed_out(p, hit);
+        });
+    }
+
+    /// Write one byte in each page spanned by the free box at `p`.
+    unsafe fn prefault_slot(p: *mut T) {
+        let size = Self::slot_size();
+        let base = p as *mut u8;
+        for offset in (0..size).step_by(PAGE_SIZE).chain(size.checked_sub(1)) {
+            // Volatile, so that the write of an uninitialized slot is not optimized away.
+            core::ptr::write_volatile(base.add(offset), 0);
+        }
+    }
+
+    /// Keep the allocation at `p`, whose value is gone, as `unbox` does.
+    #[inline]
+    unsafe fn keep_slot(&mut self, p: *mut T, caller: &'static core::panic::Location<'static>) {
+        let guard = NoUnwind::arm();
+        no_unwind(|| {
+            #[cfg(feature = "checked")]
+            self.check_in(p, caller);
+            #[cfg(feature = "op-log")]
+            self.record_op(op_log::OpKind::Unbox, p, caller);
+            #[cfg(feature = "std")]
+            {
+                self.leases.end(provenance::addr(p));
+                self.outstanding.given_back(provenance::addr(p));
             }
+            // Unused without the `checked` and `op-log` features.
+            let _ = caller;
+        });
+        self.counters.returns += 1;
+        let pushed = if self.keeps_returned() { self.items.try_push(p) } else { Err(p) };
+        match pushed {
+            Ok(()) => self.counters.unboxes += 1,
+            Err(p) => self.free_slot(p),
         }
+        guard.disarm();
     }
 
-    /// Like `rebox` but only if there are empty boxes. Return `None` if `*v` is `None`.
-    /// The stack overhead of this function is guaranteed in the order of pointer-sized.
-    pub fn try_rebox(&mut self, v: &mut Option<T>) -> Option<Box<T>> {
-        // Test the pre-conditions
-        if v.is_none() {
-            return None;
+    /// Return whether to keep a box given back, as chaos, the maximum capacity and the
+    /// capacity policy decide.
+    #[inline]
+    fn keeps_returned(&mut self) -> bool {
+        #[cfg(feature = "chaos")]
+        if self.chaos.roll() {
+            return false;
         }
-        if self.items.len() == 0 {
-            return None;
+        let capacity = self.items.len();
+        if let Some(max) = self.effective_max_capacity() {
+            if capacity >= max {
+                return false;
+            }
         }
+        match &mut self.policy {
+            Some(policy) => no_unwind(|| policy.keep(capacity)),
+            None => true,
+        }
+    }
 
-        let raw_ptr = self.items.pop().unwrap();
-        let v_ref = v.as_mut().unwrap();
+    /// Deallocate a box given back, running the hook first.
+    #[inline]
+    unsafe fn free_slot(&mut self, p: *mut T) {
+        no_unwind(|| self.hooks.deallocating(p));
+        Self::dealloc_slot(p)
+    }
 
-        let boxed = unsafe {
-            std::ptr::copy(v_ref, raw_ptr, 1);
-            std::ptr::write(v, None);
-            Box::from_raw(raw_ptr)
-        };
+    /// Deallocate a free box. It must not be in the free list anymore.
+    ///
+    /// Free boxes come either from `Box` or from `alloc` with the layout of `T`, which the
+    /// documentation of `Box` guarantees to be interchangeable, and keep the provenance of
+    /// their allocation: they are never made from bare addresses.
+    #[inline]
+    unsafe fn dealloc_slot(p: *mut T) {
+        let layout = alloc::alloc::Layout::new::<T>();
+        if layout.size() != 0 {
+            #[cfg(feature = "zeroize")]
+            wipe::wipe(p);
+            #[cfg(feature = "mlock")]
+            locked::unlock(p);
+            alloc::alloc::dealloc(p as *mut u8, layout);
+        }
+    }
+}
+
+/// The error returned by the fallible functions of arenas when memory runs out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError;
 
-        Some(boxed)
+impl core::fmt::Display for AllocError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("memory allocation failed")
     }
+}
 
-    /// Return the number of free boxes in the BoxingArena.
-    pub fn capacity(&self) -> usize {
-        self.items.len()
+#[cfg(feature = "std")]
+impl std::error::Error for AllocError {}
+
+/// Created at the start of a hot path and disarmed at its end. With the `no-panic` feature,
+/// optimized builds fail to link if the code in between may unwind, in the same way as the
+/// `no-panic` crate, but without a closure that would lose the `#[track_caller]` location.
+struct NoUnwind;
+
+impl NoUnwind {
+    #[inline(always)]
+    fn arm() -> Self {
+        NoUnwind
     }
 
-    /// Resize boxes pool to a given capacity.
-    pub fn resize_capacity(&mut self, size: usize) {
-        let mut n = self.items.len();
+    #[inline(always)]
+    fn disarm(self) {
+        let _ = core::mem::ManuallyDrop::new(self);
+    }
+}
 
-        while size < n {
-            let p = self.items.pop().unwrap();
-            unsafe {
-                std::alloc::dealloc(p as *mut u8, std::alloc::Layout::new::<T>());
-            }
-            n -= 1;
+#[cfg(all(feature = "no-panic", not(debug_assertions)))]
+impl Drop for NoUnwind {
+    #[inline(always)]
+    fn drop(&mut self) {
+        extern "C" {
+            #[link_name = "\n\nERROR[boxing-arena]: detected a path that may unwind out of `rebox`, `try_rebox`, `try_rebox_value` or `unbox`\n\n"]
+            fn detected_unwind() -> !;
         }
+        unsafe { detected_unwind() }
+    }
+}
 
-        while size > n {
-            let p = unsafe {
-                std::alloc::alloc(std::alloc::Layout::new::<T>())
-            };
-            self.items.push(p as *mut T);
-            n += 1;
+/// Run `f`, which may panic in code that is not ours, such as callbacks or allocation error
+/// handlers. With the `no-panic` feature, a panic aborts the process instead of unwinding
+/// out of the hot paths.
+#[inline(always)]
+fn no_unwind<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "no-panic")]
+    {
+        // Cannot unwind, unlike `std::process::abort` as far as the optimizer knows.
+        extern "C" fn abort() -> ! {
+            std::process::abort()
+        }
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+            Ok(r) => r,
+            Err(e) => {
+                core::mem::forget(e);
+                abort()
+            }
         }
     }
+    #[cfg(not(feature = "no-panic"))]
+    f()
+}
 
-    /// Trims capacity to the given size if it is larger.
-    pub fn trim(&mut self, size: usize) {
-        if self.items.len() > size {
-            self.resize_capacity(size)
+// The arena owns the free allocations it points to.
+unsafe impl<T: Send> Send for BoxingArena<T> {}
+
+impl<T> Default for BoxingArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<Box<T>> for BoxingArena<T> {
+    /// Drop the values of the boxes and keep their allocations, as `recycle` does.
+    #[track_caller]
+    fn extend<I: IntoIterator<Item = Box<T>>>(&mut self, iter: I) {
+        for b in iter {
+            self.recycle(b);
         }
     }
 }
 
+impl<T> core::iter::FromIterator<Box<T>> for BoxingArena<T> {
+    /// Create an arena keeping the allocations of the boxes, whose values are dropped.
+    #[track_caller]
+    fn from_iter<I: IntoIterator<Item = Box<T>>>(iter: I) -> Self {
+        let mut ba = Self::new();
+        ba.extend(iter);
+        ba
+    }
+}
+
+impl<T> IntoIterator for BoxingArena<T> {
+    type Item = Box<core::mem::MaybeUninit<T>>;
+    type IntoIter = alloc::vec::IntoIter<Self::Item>;
+
+    /// Iterate over the free boxes, as returned by `into_free_boxes`.
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_free_boxes().into_iter()
+    }
+}
+
 impl<T> Drop for BoxingArena<T> {
     fn drop(&mut self) {
+        #[cfg(feature = "tracing")]
+        self.trace_drop();
         // Deallocate all the free boxes that we kept.
         unsafe {
-            for p in &self.items {
-                std::alloc::dealloc(*p as *mut u8, std::alloc::Layout::new::<T>());
+            for p in self.items.iter() {
+                self.hooks.deallocating(*p);
+                Self::dealloc_slot(*p);
             }
         }
+        #[cfg(feature = "std")]
+        self.outstanding.report(core::any::type_name::<T>());
     }
 }
 
@@ -173,7 +1642,7 @@ mod tests {
 
         // Check c_addr exists in addresses
         let v = addresses.iter().position(|x| *x == c_addr);
-        assert_eq!(v.is_some(), true);
+        assert!(v.is_some());
 
         ba.resize_capacity(4);
         assert_eq!(ba.capacity(), 4);
@@ -188,10 +1657,673 @@ mod tests {
         assert_eq!(*boxed.unwrap(), 42);
         let none = ba.try_rebox(&mut None);
         assert_eq!(ba.capacity(), 1);
-        assert_eq!(none.is_none(), true);
+        assert!(none.is_none());
         ba.resize_capacity(0);
         let none = ba.try_rebox(&mut Some(42));
         assert_eq!(ba.capacity(), 0);
-        assert_eq!(none.is_none(), true);
+        assert!(none.is_none());
+
+        // Test `try_rebox_value`
+        assert_eq!(ba.try_rebox_value(42).unwrap_err(), 42);
+        ba.resize_capacity(1);
+        assert_eq!(*ba.try_rebox_value(42).unwrap(), 42);
+        assert_eq!(ba.capacity(), 0);
+    }
+
+    #[test]
+    fn retain_addresses() {
+        let mut ba = BoxingArena::<u64>::with_capacity(4);
+        let mut addresses = vec![];
+        ba.retain_addresses(|p| {
+            addresses.push(p as usize);
+            true
+        });
+        addresses.sort();
+
+        // Release the two lowest ones.
+        let limit = addresses[1];
+        ba.retain_addresses(|p| p as usize > limit);
+        assert_eq!(ba.capacity(), 2);
+        let b = ba.rebox(1);
+        assert!(&*b as *const u64 as usize > limit);
+    }
+
+    #[test]
+    fn address_ordered() {
+        let mut ba = BoxingArena::<u64>::with_capacity(8);
+        ba.set_address_ordered(true);
+
+        let boxes: Vec<_> = (0..8).map(|i| ba.rebox(i)).collect();
+        let addresses: Vec<_> = boxes.iter().map(|b| &**b as *const u64 as usize).collect();
+        let mut sorted = addresses.clone();
+        sorted.sort();
+        assert_eq!(addresses, sorted);
+
+        // Return them in reverse, the lowest is still reused first.
+        for b in boxes.into_iter().rev() {
+            ba.unbox(b);
+        }
+        let b = ba.rebox(0);
+        assert_eq!(&*b as *const u64 as usize, sorted[0]);
+
+        // Shrinking releases the highest addresses.
+        ba.resize_capacity(2);
+        ba.retain_addresses(|p| {
+            assert!((p as usize) < sorted[3]);
+            true
+        });
+    }
+
+    #[test]
+    fn hot_cold() {
+        let mut ba = BoxingArena::<u64>::new();
+        ba.set_hot_capacity(2);
+
+        let boxes: Vec<_> = (0..4).map(|i| ba.rebox(i)).collect();
+        let addresses: Vec<_> = boxes.iter().map(|b| &**b as *const u64 as usize).collect();
+        for b in boxes {
+            ba.unbox(b);
+        }
+
+        // The two oldest went cold and are released first.
+        ba.trim(2);
+        let mut kept = vec![];
+        ba.retain_addresses(|p| {
+            kept.push(p as usize);
+            true
+        });
+        assert_eq!(kept, &addresses[2..]);
+
+        // The most recent is reused first.
+        let b = ba.rebox(0);
+        assert_eq!(&*b as *const u64 as usize, addresses[3]);
+    }
+
+    #[test]
+    fn fifo() {
+        let mut ba = BoxingArena::<u64>::with_reuse_order(ReuseOrder::Fifo);
+        assert_eq!(ba.reuse_order(), ReuseOrder::Fifo);
+
+        let boxes: Vec<_> = (0..4).map(|i| ba.rebox(i)).collect();
+        let addresses: Vec<_> = boxes.iter().map(|b| &**b as *const u64 as usize).collect();
+        for b in boxes {
+            ba.unbox(b);
+        }
+
+        // The oldest is reused first, and the newest released first.
+        let b = ba.rebox(0);
+        assert_eq!(&*b as *const u64 as usize, addresses[0]);
+        ba.trim(2);
+        ba.unbox(b);
+        let reused: Vec<_> = (0..3).map(|i| ba.rebox(i)).collect();
+        let reused: Vec<_> = reused.iter().map(|b| &**b as *const u64 as usize).collect();
+        assert_eq!(reused, [addresses[1], addresses[2], addresses[0]]);
+    }
+
+    #[test]
+    fn hardening() {
+        let mut ba = BoxingArena::<u64>::new();
+        let hardening = Hardening {
+            quarantine: 2,
+            random_seed: Some(7),
+        };
+        ba.set_hardening(hardening);
+        assert_eq!(ba.hardening(), hardening);
+
+        let boxes: Vec<_> = (0..4).map(|i| ba.rebox(i)).collect();
+        let addresses: Vec<_> = boxes.iter().map(|b| &**b as *const u64 as usize).collect();
+        for b in boxes {
+            ba.unbox(b);
+        }
+        assert_eq!(ba.capacity(), 4);
+
+        // The last two given back are in quarantine, and the others are picked at random.
+        let reused: Vec<_> = (0..4).map(|i| ba.rebox(i)).collect();
+        let mut reused: Vec<_> = reused.iter().map(|b| &**b as *const u64 as usize).collect();
+        assert!(!addresses[2..].contains(&reused[0]) && !addresses[2..].contains(&reused[1]));
+        reused[..2].sort_unstable();
+        let mut first = addresses[..2].to_vec();
+        first.sort_unstable();
+        assert_eq!(reused[..2], first[..]);
+        assert_eq!(ba.stats().misses, 6);
+
+        // Lifting the quarantine makes its boxes reusable.
+        ba.set_hardening(Hardening::default());
+        let b = ba.rebox(0);
+        assert!(addresses[2..].contains(&(&*b as *const u64 as usize)));
+        assert_eq!(ba.stats().misses, 6);
+    }
+
+    #[test]
+    fn quarantined_boxes() {
+        let quarantined = || {
+            let mut ba = BoxingArena::<u64>::with_capacity(2);
+            ba.set_hardening(Hardening {
+                quarantine: 2,
+                random_seed: None,
+            });
+            ba.unbox(Box::new(0));
+            ba.unbox(Box::new(1));
+            ba
+        };
+
+        // Only the reusable boxes are taken, without allocating.
+        let mut ba = quarantined();
+        assert_eq!(ba.capacity(), 4);
+        let taken = ba.take_free_boxes(4);
+        assert_eq!((taken.len(), ba.stats().misses), (2, 0));
+        ba.return_free_boxes(taken);
+        assert_eq!(ba.capacity(), 4);
+
+        let mut boxes = vec![Box::new(1), Box::new(2)];
+        ba.rehome_all(&mut boxes);
+        assert_eq!((ba.capacity(), ba.stats().misses), (4, 0));
+        ba.rehome_all(&mut boxes);
+        assert_eq!(ba.capacity(), 4);
+
+        let mut other = ba.split_off(3);
+        assert_eq!((ba.capacity(), other.capacity()), (1, 3));
+        other.merge(ba);
+        assert_eq!(other.capacity(), 4);
+        assert_eq!(other.into_free_boxes().len(), 4);
+    }
+
+    #[test]
+    fn map_reboxed() {
+        let mut ba = BoxingArena::<u64>::new();
+        let a = Box::new(5i64);
+        let a_addr = &*a as *const i64 as usize;
+        let b = ba.map_reboxed(a, |v| v as u64 * 2);
+        assert_eq!(*b, 10);
+        assert_eq!(&*b as *const u64 as usize, a_addr);
+
+        // Different layout, falls back to the pool.
+        let c = ba.map_reboxed(Box::new(3u8), u64::from);
+        assert_eq!(*c, 3);
+        assert_eq!(ba.stats().misses, 1);
+    }
+
+    #[test]
+    fn map_box() {
+        let mut ba = BoxingArena::new();
+        let a = ba.rebox(vec![1u32]);
+        let addr = &*a as *const Vec<u32>;
+        let a = ba.map_box(a, |mut v| {
+            v.push(2);
+            v
+        });
+        assert_eq!((&*a as *const Vec<u32>, &a[..]), (addr, &[1, 2][..]));
+        assert_eq!((ba.stats().hits + ba.stats().misses, ba.capacity()), (1, 0));
+
+        let a = ba.try_map_box(a, Ok::<_, ()>).unwrap();
+        assert_eq!(ba.try_map_box(a, |_| Err("too short")), Err("too short"));
+        assert_eq!(ba.capacity(), 1);
+    }
+
+    #[test]
+    fn optional_fields() {
+        let mut ba = BoxingArena::new();
+        let mut field = None;
+        ba.set_boxed(&mut field, 1u32);
+        let addr = &**field.as_ref().unwrap() as *const u32;
+        ba.set_boxed(&mut field, 2);
+        assert_eq!(&**field.as_ref().unwrap() as *const u32, addr);
+
+        assert_eq!(ba.take_unboxed(&mut field), Some(2));
+        assert_eq!(ba.take_unboxed(&mut field), None);
+        assert_eq!(ba.capacity(), 1);
+    }
+
+    #[test]
+    fn rehome_all() {
+        let mut ba = BoxingArena::with_capacity(1);
+        let mut boxes: Vec<_> = (0..3u64).map(Box::new).collect();
+        let old: Vec<_> = boxes.iter().map(|b| &**b as *const u64).collect();
+
+        ba.rehome_all(&mut boxes);
+        assert_eq!(boxes, [Box::new(0), Box::new(1), Box::new(2)]);
+        assert_eq!(ba.capacity(), 3);
+        for b in &boxes {
+            assert!(!old.contains(&(&**b as *const u64)));
+        }
+        ba.retain_addresses(|p| {
+            assert!(old.contains(&p));
+            true
+        });
+    }
+
+    #[test]
+    fn fork_empty() {
+        let mut ba = BoxingArena::<u32>::with_capacity(4);
+        ba.set_hot_capacity(3);
+        ba.set_address_ordered(true);
+        let _ = ba.rebox(1);
+
+        let fork = ba.fork_empty();
+        assert_eq!(fork.capacity(), 0);
+        assert_eq!(fork.hot_capacity(), 3);
+        assert!(fork.is_address_ordered());
+        assert_eq!(fork.stats().hits, 0);
+    }
+
+    #[test]
+    fn try_unbox() {
+        let mut ba = BoxingArena::new();
+        ba.set_max_capacity(Some(1));
+        assert_eq!(ba.try_unbox(Box::new(1u32)), Ok(1));
+        assert_eq!(ba.try_unbox(Box::new(2u32)), Err(Box::new(2)));
+        assert_eq!(ba.capacity(), 1);
+
+        ba.set_max_capacity(None);
+        assert_eq!(ba.try_unbox(Box::new(3u32)), Ok(3));
+        assert_eq!(ba.fork_empty().max_capacity(), None);
+
+        ba.set_capacity_policy(Bounded(2));
+        assert_eq!(ba.try_unbox(Box::new(4u32)), Err(Box::new(4)));
+        assert_eq!(ba.capacity(), 2);
+    }
+
+    #[test]
+    fn max_capacity() {
+        let mut ba = BoxingArena::with_max_capacity(2);
+        let boxes: Vec<_> = (0..4u32).map(|i| ba.rebox(i)).collect();
+        let values: Vec<_> = boxes.into_iter().map(|b| ba.unbox(b)).collect();
+        assert_eq!(values, [0, 1, 2, 3]);
+        assert_eq!(ba.capacity(), 2);
+        assert_eq!(ba.stats().unboxes, 2);
+        // The boxes freed count as given back too.
+        assert_eq!((ba.stats().returns, ba.stats().outstanding()), (4, 0));
+    }
+
+    #[test]
+    fn recycle() {
+        use std::rc::Rc;
+
+        let mut ba = BoxingArena::new();
+        let shared = Rc::new(());
+        let b = ba.rebox([shared.clone(), shared.clone()]);
+        ba.recycle(b);
+        assert_eq!(Rc::strong_count(&shared), 1);
+        assert_eq!(ba.capacity(), 1);
+        assert_eq!(ba.stats().unboxes, 1);
+    }
+
+    #[test]
+    fn prefault() {
+        let mut ba = BoxingArena::<[u8; 3 * PAGE_SIZE]>::new();
+        assert!(!ba.prefaults());
+        ba.set_prefault(true);
+        ba.resize_capacity(2);
+        assert!(ba.fork_empty().prefaults());
+
+        let b = ba.rebox([1; 3 * PAGE_SIZE]);
+        assert_eq!(b[3 * PAGE_SIZE - 1], 1);
+        ba.unbox(b);
+        assert_eq!(ba.capacity(), 2);
+    }
+
+    #[test]
+    fn memory_budget() {
+        let mut ba = BoxingArena::<[u8; 100]>::with_capacity(10);
+        assert_eq!(ba.capacity_bytes(), 1000);
+        ba.trim_bytes(550);
+        assert_eq!(ba.capacity(), 5);
+
+        ba.set_memory_budget(Some(600));
+        assert_eq!(ba.effective_max_capacity(), Some(6));
+        ba.set_max_capacity(Some(3));
+        assert_eq!(ba.effective_max_capacity(), Some(3));
+        assert!(ba.try_unbox(Box::new([0; 100])).is_err());
+
+        // Past the budget, `unbox` frees the box.
+        ba.set_max_capacity(None);
+        ba.unbox(Box::new([0; 100]));
+        assert_eq!(ba.capacity_bytes(), 600);
+        ba.unbox(Box::new([0; 100]));
+        assert_eq!(ba.capacity_bytes(), 600);
+    }
+
+    #[test]
+    fn rebox_pinned() {
+        let mut ba = BoxingArena::new();
+        let b = ba.rebox_pinned(5u32);
+        assert_eq!(ba.unbox_pinned(b), 5);
+
+        let mut ba = BoxingArena::new();
+        let shared = std::rc::Rc::new(());
+        let b = ba.rebox_pinned((shared.clone(), std::marker::PhantomPinned));
+        ba.recycle_pinned(b);
+        assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+        assert_eq!(ba.capacity(), 1);
+    }
+
+    #[test]
+    fn rebox_pinned_with() {
+        struct SelfRef {
+            value: u32,
+            ptr: *const u32,
+            _pin: std::marker::PhantomPinned,
+        }
+
+        let mut ba = BoxingArena::<SelfRef>::with_capacity(1);
+        let pinned = unsafe {
+            ba.rebox_pinned_with(|slot| {
+                let slot = slot.get_unchecked_mut().as_mut_ptr();
+                std::ptr::addr_of_mut!((*slot).value).write(7);
+                std::ptr::addr_of_mut!((*slot).ptr).write(std::ptr::addr_of!((*slot).value));
+            })
+        };
+        assert_eq!(unsafe { *pinned.ptr }, pinned.value);
+        assert_eq!(ba.capacity(), 0);
+
+        // A panic gives the slot back.
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe {
+            ba.rebox_pinned_with(|_| panic!("construction failed"))
+        }));
+        assert!(r.is_err());
+        assert_eq!(ba.capacity(), 1);
+    }
+
+    #[test]
+    fn rebox_with() {
+        let mut ba = BoxingArena::<[u64; 512]>::with_capacity(1);
+        let b = unsafe {
+            ba.rebox_with(|slot| {
+                let slot = slot.as_mut_ptr() as *mut u64;
+                for i in 0..512 {
+                    slot.add(i).write(i as u64);
+                }
+            })
+        };
+        assert_eq!(b[511], 511);
+        assert_eq!((ba.capacity(), ba.stats().hits), (0, 1));
+    }
+
+    #[test]
+    fn rebox_uninit() {
+        let mut ba = BoxingArena::<[u8; 64]>::with_capacity(1);
+        let mut b = ba.rebox_uninit();
+        unsafe { std::ptr::write_bytes(b.as_mut_ptr(), 7, 1) };
+        let b = unsafe { BoxingArena::assume_init(b) };
+        assert_eq!(ba.unbox(b), [7; 64]);
+
+        let b = ba.rebox_uninit();
+        ba.unbox_uninit(b);
+        assert_eq!(ba.capacity(), 1);
+        assert_eq!((ba.stats().hits, ba.stats().unboxes), (2, 2));
+    }
+
+    #[test]
+    fn stats() {
+        let mut ba = BoxingArena::new();
+        let a = ba.rebox([0u8; 64]);
+        let _ = ba.unbox(a);
+        let b = ba.rebox([1u8; 64]);
+        let _ = ba.try_rebox(&mut Some([2u8; 64]));
+
+        let stats = ba.stats();
+        assert_eq!((stats.hits, stats.misses, stats.unboxes), (1, 1, 1));
+        assert_eq!((stats.capacity, stats.peak_capacity), (0, 1));
+        assert_eq!(stats.hit_ratio(), 0.5);
+        assert_eq!(stats.slot_size, 64);
+
+        let savings = stats.savings();
+        assert_eq!(savings.allocator_calls_avoided, 2);
+        assert_eq!(savings.bytes_avoided, 64);
+        assert_eq!(savings.net_bytes, 64 - stats.metadata_bytes as i64);
+        assert_eq!(ba.memory_held(), stats.metadata_bytes);
+        assert_eq!(BoxingArena::<[u8; 64]>::slot_layout().size(), 64);
+        drop(b);
+    }
+
+    #[test]
+    fn shrink_metadata() {
+        let mut ba = BoxingArena::<u64>::with_capacity(1000);
+        ba.trim(10);
+        assert!(ba.stats().metadata_bytes < 20 * core::mem::size_of::<*mut u64>());
+        let a = ba.rebox(1);
+        ba.trim(0);
+        ba.unbox(a);
+        ba.shrink_metadata();
+        assert_eq!(ba.stats().metadata_bytes, core::mem::size_of::<*mut u64>());
+    }
+
+    #[test]
+    fn merge() {
+        let mut ba = BoxingArena::<u64>::with_capacity(1);
+        let mut worker = BoxingArena::new();
+        let a = worker.rebox(1);
+        let addr = &*a as *const u64;
+        worker.unbox(a);
+        worker.reserve(1);
+
+        ba.merge(worker);
+        assert_eq!(ba.capacity(), 3);
+        let boxes: Vec<_> = (0..3).map(|i| ba.rebox(i)).collect();
+        assert!(boxes.iter().any(|b| std::ptr::eq(&**b, addr)));
+    }
+
+    #[test]
+    fn split_off() {
+        let mut ba = BoxingArena::<u64>::with_capacity(5);
+        let workers: Vec<_> = (0..3).map(|_| ba.split_off(2)).collect();
+        assert_eq!(ba.capacity(), 0);
+        let capacities: Vec<_> = workers
+            .into_iter()
+            .map(|mut worker| {
+                std::thread::spawn(move || {
+                    let b = worker.rebox(1);
+                    worker.unbox(b);
+                    worker.capacity()
+                })
+            })
+            .map(|t| t.join().unwrap())
+            .collect();
+        assert_eq!(capacities, [2, 2, 1]);
+    }
+
+    #[test]
+    fn extend() {
+        let boxes: Vec<_> = (0..3).map(|i| Box::new(vec![i])).collect();
+        let mut ba: BoxingArena<_> = boxes.into_iter().collect();
+        assert_eq!(ba.capacity(), 3);
+        ba.extend(vec![Box::new(vec![3])]);
+        assert_eq!(ba.capacity(), 4);
+    }
+
+    #[test]
+    fn batches() {
+        let mut ba = BoxingArena::new();
+        let boxes: Vec<_> = ba.rebox_iter(0..1024u32).collect();
+        assert_eq!(*boxes[1023], 1023);
+        ba.unbox_all(boxes);
+        assert_eq!(ba.capacity(), 1024);
+        assert_eq!(ba.rebox_iter(0..4).map(|b| *b).sum::<u32>(), 6);
+        assert_eq!((ba.capacity(), ba.stats().hits), (1020, 4));
+    }
+
+    #[test]
+    fn take_free_boxes() {
+        let mut ba = BoxingArena::<u64>::with_capacity(3);
+        let boxes = ba.take_free_boxes(5);
+        assert_eq!((boxes.len(), ba.capacity()), (3, 0));
+        ba.return_free_boxes(boxes);
+        assert_eq!(ba.capacity(), 3);
+    }
+
+    #[test]
+    fn rebox_clone() {
+        let mut ba = BoxingArena::with_capacity(1);
+        let message = vec![7u8; 1024];
+        let a = ba.rebox_clone(&message);
+        let b = ba.rebox_clone(&message);
+        assert_eq!((&*a, &*b), (&message, &message));
+        assert_eq!(ba.stats().hits, 1);
+
+        let mut ba = BoxingArena::<[u64; 32]>::new();
+        let a = ba.rebox([1; 32]);
+        ba.unbox(a);
+        assert_eq!(*ba.rebox_default(), [0; 32]);
+
+        let page = [5u8; 4096];
+        let mut ba = BoxingArena::with_capacity(1);
+        let a = ba.rebox_copy(&page);
+        assert_eq!((*a, ba.stats().hits), (page, 1));
+    }
+
+    #[test]
+    fn unbox_into() {
+        let mut ba = BoxingArena::new();
+        let b = ba.rebox([3u8; 4096]);
+        let mut dst = core::mem::MaybeUninit::uninit();
+        let v = ba.unbox_into(b, &mut dst);
+        assert_eq!(v[4095], 3);
+        assert_eq!(ba.capacity(), 1);
+    }
+
+    #[test]
+    fn into_free_boxes() {
+        let mut ba = BoxingArena::<u64>::new();
+        let a = ba.rebox(1);
+        let addr = &*a as *const u64;
+        ba.unbox(a);
+        ba.resize_capacity(3);
+
+        let mut boxes = ba.into_free_boxes();
+        assert_eq!(boxes.len(), 3);
+        assert!(boxes.iter().any(|b| b.as_ptr() == addr));
+        let b = Box::write(boxes.remove(0), 2);
+        assert_eq!(*b, 2);
+
+        let mut ba = BoxingArena::<u64>::with_capacity(2);
+        ba.set_hot_capacity(1);
+        assert_eq!(ba.into_iter().count(), 2);
+    }
+
+    #[test]
+    fn static_arena() {
+        static ARENA: std::sync::Mutex<BoxingArena<u64>> =
+            std::sync::Mutex::new(BoxingArena::new());
+
+        let a = ARENA.lock().unwrap().rebox(1);
+        let addr = &*a as *const u64;
+        ARENA.lock().unwrap().unbox(a);
+        let b = ARENA.lock().unwrap().rebox(2);
+        assert_eq!(&*b as *const u64, addr);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut ba = BoxingArena::<()>::with_capacity(4);
+        assert_eq!(ba.capacity(), 0);
+        let mut boxes = vec![ba.rebox(()), ba.rebox(())];
+        ba.rehome_all(&mut boxes);
+        for b in boxes {
+            ba.unbox(b);
+        }
+        ba.recycle(Box::new(()));
+        assert_eq!(ba.capacity(), 0);
+        assert_eq!(ba.stats().misses, 2);
+    }
+
+    #[test]
+    fn try_reserve() {
+        let mut ba = BoxingArena::<u64>::new();
+        ba.try_reserve(2).unwrap();
+        ba.reserve(1);
+        ba.reserve(0);
+        assert_eq!(ba.capacity(), 3);
+        assert_eq!(ba.try_reserve(usize::MAX), Err(AllocError));
+        ba.try_resize_capacity(1).unwrap();
+        assert_eq!(ba.capacity(), 1);
+    }
+
+    /// Payloads counting how many of them are alive, to catch both leaks and double drops.
+    #[derive(Debug)]
+    struct Counted {
+        id: usize,
+        alive: std::rc::Rc<core::cell::Cell<usize>>,
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.alive.set(self.alive.get() - 1);
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Rebox,
+        TryRebox,
+        Unbox(usize),
+        Recycle(usize),
+        Resize(usize),
+        Trim(usize),
+    }
+
+    fn op() -> impl proptest::strategy::Strategy<Value = Op> {
+        use proptest::prelude::*;
+        prop_oneof![
+            Just(Op::Rebox),
+            Just(Op::TryRebox),
+            any::<usize>().prop_map(Op::Unbox),
+            any::<usize>().prop_map(Op::Recycle),
+            (0..8usize).prop_map(Op::Resize),
+            (0..8usize).prop_map(Op::Trim),
+        ]
+    }
+
+    proptest::proptest! {
+        #[cfg_attr(miri, ignore = "too slow under Miri")]
+        #[test]
+        fn interleavings(ops in proptest::collection::vec(op(), 0..64)) {
+            let alive = std::rc::Rc::new(core::cell::Cell::new(0));
+            let mut ba = BoxingArena::new();
+            let mut live: Vec<Box<Counted>> = vec![];
+            let mut capacity = 0usize;
+            for (id, op) in ops.into_iter().enumerate() {
+                let mut v = Some(Counted { id, alive: alive.clone() });
+                alive.set(alive.get() + 1);
+                match op {
+                    Op::Rebox | Op::TryRebox => {
+                        let b = match op {
+                            Op::Rebox => Some(ba.rebox(v.take().unwrap())),
+                            _ => ba.try_rebox(&mut v),
+                        };
+                        proptest::prop_assert_eq!(b.is_some(), v.is_none());
+                        if let Some(b) = b {
+                            capacity = capacity.saturating_sub(1);
+                            proptest::prop_assert_eq!(b.id, id);
+                            proptest::prop_assert!(live.iter().all(|l| !core::ptr::eq(&**l, &*b)));
+                            live.push(b);
+                        }
+                    }
+                    Op::Unbox(i) | Op::Recycle(i) if !live.is_empty() => {
+                        let b = live.swap_remove(i % live.len());
+                        let expected = b.id;
+                        match op {
+                            Op::Unbox(_) => proptest::prop_assert_eq!(ba.unbox(b).id, expected),
+                            _ => ba.recycle(b),
+                        }
+                        capacity += 1;
+                    }
+                    Op::Resize(size) => {
+                        ba.resize_capacity(size);
+                        capacity = size;
+                    }
+                    Op::Trim(size) => {
+                        ba.trim(size);
+                        capacity = capacity.min(size);
+                    }
+                    _ => {}
+                }
+                drop(v);
+                proptest::prop_assert_eq!(ba.capacity(), capacity);
+                proptest::prop_assert_eq!(alive.get(), live.len());
+            }
+            drop(live);
+            drop(ba);
+            proptest::prop_assert_eq!(alive.get(), 0);
+        }
     }
 }
@@ -0,0 +1,66 @@
+//! Arenas local to each thread, one per type, used through free functions.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::BoxingArena;
+
+thread_local! {
+    static ARENAS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// Run `f` with the arena of the current thread for `T`, creating it if needed. Returns
+/// `None` if it is already in use further up the stack, for example from a sampler, or if
+/// the thread is exiting.
+pub fn with_local_arena<T: 'static, R>(f: impl FnOnce(&mut BoxingArena<T>) -> R) -> Option<R> {
+    ARENAS
+        .try_with(|arenas| {
+            let mut arenas = arenas.try_borrow_mut().ok()?;
+            let arena = arenas
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Box::new(BoxingArena::<T>::new()));
+            Some(f(arena.downcast_mut().unwrap()))
+        })
+        .ok()
+        .flatten()
+}
+
+/// Box a value with the arena of the current thread for `T`, so that code does not need to
+/// pass an arena around. Falls back to `Box::new` where `with_local_arena` cannot run.
+pub fn rebox<T: 'static>(v: T) -> Box<T> {
+    let mut v = Some(v);
+    with_local_arena(|arena| arena.rebox(v.take().unwrap())).unwrap_or_else(|| Box::new(v.unwrap()))
+}
+
+/// Unbox the value, keeping the allocation in the arena of the current thread for `T`. The
+/// box may come from another thread. Falls back to deallocating it where
+/// `with_local_arena` cannot run.
+pub fn unbox<T: 'static>(b: Box<T>) -> T {
+    let mut b = Some(b);
+    with_local_arena(|arena| arena.unbox(b.take().unwrap())).unwrap_or_else(|| *b.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local() {
+        let a = rebox(1u64);
+        let addr = &*a as *const u64;
+        assert_eq!(unbox(a), 1);
+        assert_eq!(with_local_arena(|arena: &mut BoxingArena<u64>| arena.capacity()), Some(1));
+
+        // Each type has its own arena.
+        let s = rebox(String::from("a"));
+        assert_eq!(unbox(s), "a");
+        let b = rebox(2u64);
+        assert_eq!(&*b as *const u64, addr);
+
+        // Reentrant use falls back to the global allocator.
+        let nested = with_local_arena(|_: &mut BoxingArena<u64>| unbox(rebox(3u64)));
+        assert_eq!(nested, Some(3));
+        drop(b);
+    }
+}
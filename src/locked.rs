@@ -0,0 +1,180 @@
+//! Keeping the boxes of an arena locked in RAM, enabled by the `mlock` feature.
+
+use std::alloc::Layout;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{BoxingArena, PAGE_SIZE};
+
+/// Whether the boxes of an arena are locked, and how many of them could not be.
+pub(crate) struct Locking {
+    enabled: bool,
+    failures: u64,
+}
+
+impl Locking {
+    pub(crate) const fn new() -> Self {
+        Self {
+            enabled: false,
+            failures: 0,
+        }
+    }
+
+    /// A disabled state with the same setting.
+    pub(crate) fn fork_empty(&self) -> Self {
+        Self {
+            enabled: self.enabled,
+            failures: 0,
+        }
+    }
+
+    /// Lock a box newly allocated by the arena, if enabled.
+    pub(crate) fn lock_new<T>(&mut self, p: *mut T) {
+        if self.enabled && lock::<T>(p).is_err() {
+            self.failures += 1;
+        }
+    }
+}
+
+fn page_size() -> usize {
+    static PAGE: AtomicUsize = AtomicUsize::new(0);
+    match PAGE.load(Ordering::Relaxed) {
+        0 => {
+            let page = system_page_size();
+            PAGE.store(page, Ordering::Relaxed);
+            page
+        }
+        page => page,
+    }
+}
+
+#[cfg(unix)]
+fn system_page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(windows)]
+fn system_page_size() -> usize {
+    use windows_sys::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
+
+    let mut info: SYSTEM_INFO = unsafe { std::mem::zeroed() };
+    unsafe { GetSystemInfo(&mut info) };
+    info.dwPageSize as usize
+}
+
+#[cfg(not(any(unix, windows)))]
+fn system_page_size() -> usize {
+    PAGE_SIZE
+}
+
+/// Whether each box of `T` covers whole pages of its own, so that locking it, and unlocking
+/// it, does not affect any other allocation.
+fn page_granular<T>() -> bool {
+    let layout = Layout::new::<T>();
+    layout.size() != 0 && layout.align() >= PAGE_SIZE && layout.align() >= page_size()
+}
+
+fn lock<T>(p: *mut T) -> io::Result<()> {
+    let len = std::mem::size_of::<T>();
+    #[cfg(unix)]
+    let locked = unsafe { libc::mlock(p as *const libc::c_void, len) == 0 };
+    #[cfg(windows)]
+    let locked = unsafe { windows_sys::Win32::System::Memory::VirtualLock(p as _, len) != 0 };
+    #[cfg(not(any(unix, windows)))]
+    let locked = {
+        let _ = (p, len);
+        return Err(io::ErrorKind::Unsupported.into());
+    };
+    match locked {
+        true => Ok(()),
+        false => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Unlock a box before it is deallocated, or when locking is turned off. Pages that were not
+/// locked are left as they are.
+pub(crate) fn unlock<T>(p: *mut T) {
+    if !page_granular::<T>() {
+        return;
+    }
+    let len = std::mem::size_of::<T>();
+    #[cfg(unix)]
+    unsafe {
+        libc::munlock(p as *const libc::c_void, len);
+    }
+    #[cfg(windows)]
+    unsafe {
+        windows_sys::Win32::System::Memory::VirtualUnlock(p as _, len);
+    }
+    #[cfg(not(any(unix, windows)))]
+    let _ = (p, len);
+}
+
+impl<T> BoxingArena<T> {
+    /// Lock the boxes of the arena in RAM, with `mlock` or `VirtualLock`, so that the values
+    /// they hold, such as key material, are never written to swap. The free boxes are locked
+    /// right away, and the boxes the arena allocates later, when growing or missing, as they
+    /// are allocated. The boxes are unlocked when the arena deallocates them, or when turned
+    /// off; a box that is dropped instead of given back stays locked.
+    ///
+    /// Each box must cover whole pages of its own, as in `BoxingArena<PageAligned<T>>`, or
+    /// this fails with `InvalidInput`. Otherwise, this returns the error of the first free box
+    /// that could not be locked, often due to the limit on locked memory, and stays on: see
+    /// `lock_failures`.
+    pub fn set_locked(&mut self, locked: bool) -> io::Result<()> {
+        if !locked {
+            self.locking.enabled = false;
+            self.items.iter().for_each(|&p| unlock(p));
+            return Ok(());
+        }
+        if !page_granular::<T>() {
+            let msg = "boxes do not cover whole pages, see `PageAligned`";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        self.locking.enabled = true;
+        let mut result = Ok(());
+        for &p in self.items.iter() {
+            if let Err(e) = lock(p) {
+                self.locking.failures += 1;
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+
+    /// Return whether the boxes of the arena are locked in RAM, see `set_locked`.
+    pub fn is_locked(&self) -> bool {
+        self.locking.enabled
+    }
+
+    /// Return the number of boxes that could not be locked since locking was turned on.
+    pub fn lock_failures(&self) -> u64 {
+        self.locking.failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PageAligned;
+
+    #[test]
+    fn locked() {
+        let mut ba = BoxingArena::<u64>::new();
+        let e = ba.set_locked(true).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        if page_size() != PAGE_SIZE {
+            return;
+        }
+        let mut ba = BoxingArena::<PageAligned<u64>>::with_capacity(1);
+        ba.set_locked(true).unwrap();
+        let a = ba.rebox(PageAligned(1));
+        let b = ba.rebox(PageAligned(2));
+        assert_eq!((ba.is_locked(), ba.lock_failures()), (true, 0));
+        ba.unbox(a);
+        ba.unbox(b);
+        ba.set_locked(false).unwrap();
+        ba.resize_capacity(0);
+    }
+}
@@ -0,0 +1,95 @@
+//! A pool that does not pool, for tests and comparisons.
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+use crate::Pool;
+
+/// The number of calls made to a `MockArena`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MockCalls {
+    /// Calls to `rebox`.
+    pub reboxes: u64,
+    /// Calls to `unbox`.
+    pub unboxes: u64,
+    /// Calls to `trim`.
+    pub trims: u64,
+}
+
+/// A `Pool` that always allocates a new box and always deallocates the boxes given back,
+/// so that pooling can be switched off without changing the code that uses the pool.
+pub struct MockArena<T> {
+    calls: MockCalls,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T> MockArena<T> {
+    /// Create a new MockArena.
+    pub fn new() -> Self {
+        Self {
+            calls: MockCalls::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Return the number of calls made so far.
+    pub fn calls(&self) -> MockCalls {
+        self.calls
+    }
+}
+
+impl<T> Default for MockArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Pool<T> for MockArena<T> {
+    fn rebox(&mut self, v: T) -> Box<T> {
+        self.calls.reboxes += 1;
+        Box::new(v)
+    }
+
+    fn unbox(&mut self, b: Box<T>) -> T {
+        self.calls.unboxes += 1;
+        *b
+    }
+
+    fn trim(&mut self, _size: usize) {
+        self.calls.trims += 1;
+    }
+
+    fn capacity(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn churn<P: Pool<String>>(pool: &mut P) -> usize {
+        let b = pool.rebox("a".into());
+        pool.unbox(b);
+        pool.trim(0);
+        pool.capacity()
+    }
+
+    #[test]
+    fn mock() {
+        let mut mock = MockArena::new();
+        assert_eq!(churn(&mut mock), 0);
+        assert_eq!(
+            mock.calls(),
+            MockCalls {
+                reboxes: 1,
+                unboxes: 1,
+                trims: 1
+            }
+        );
+
+        let b = mock.rebox(String::from("b"));
+        assert_eq!(mock.capacity(), 0);
+        assert_eq!(mock.unbox(b), "b");
+    }
+}
@@ -0,0 +1,309 @@
+//! Pools of initialized objects, reset between uses.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::hash::BuildHasher;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+/// Values that can be brought back to a blank state for reuse, keeping the resources they
+/// own, such as their heap allocations.
+pub trait Reset {
+    /// Bring the value back to the state of a newly made one.
+    fn reset(&mut self);
+}
+
+/// A pool of boxed values that are reset, rather than dropped, when given back, so that
+/// objects that are expensive to build are reused whole.
+///
+/// Objects are handed out most recently used first, since those have the warmest caches,
+/// and shrinking the pool evicts the least recently used ones first.
+pub struct ObjectPool<T: Reset> {
+    /// Least recently used at the front.
+    objects: VecDeque<Box<T>>,
+    factory: Option<Box<dyn FnMut() -> T + Send>>,
+}
+
+impl<T: Reset> ObjectPool<T> {
+    /// Create a new, empty ObjectPool.
+    pub fn new() -> Self {
+        Self {
+            objects: VecDeque::new(),
+            factory: None,
+        }
+    }
+
+    /// Create a new, empty ObjectPool, building objects with `factory` when `checkout` finds
+    /// the pool empty.
+    pub fn with_factory<F: FnMut() -> T + Send + 'static>(factory: F) -> Self {
+        Self {
+            objects: VecDeque::new(),
+            factory: Some(Box::new(factory)),
+        }
+    }
+
+    /// Take the most recently used object, or build one with the factory if the pool is
+    /// empty, in a guard that gives it back to the pool, reset, when dropped. Returns `None`
+    /// if the pool is empty and was not created by `with_factory`.
+    pub fn checkout(&mut self) -> Option<PooledObject<'_, T>> {
+        let b = match self.pop() {
+            Some(b) => b,
+            None => Box::new((self.factory.as_mut()?)()),
+        };
+        Some(self.guard(b))
+    }
+
+    /// Like `checkout`, but builds the object with `f` if the pool is empty, instead of the
+    /// factory.
+    pub fn checkout_or_else<F: FnOnce() -> T>(&mut self, f: F) -> PooledObject<'_, T> {
+        let b = self.get_or_else(f);
+        self.guard(b)
+    }
+
+    fn guard(&mut self, b: Box<T>) -> PooledObject<'_, T> {
+        PooledObject {
+            object: ManuallyDrop::new(b),
+            pool: self,
+        }
+    }
+
+    /// Take the most recently used object, if there is one.
+    pub fn pop(&mut self) -> Option<Box<T>> {
+        self.objects.pop_back()
+    }
+
+    /// Take the most recently used object, or build one with `f` if the pool is empty.
+    pub fn get_or_else<F: FnOnce() -> T>(&mut self, f: F) -> Box<T> {
+        self.pop().unwrap_or_else(|| Box::new(f()))
+    }
+
+    /// Reset an object and keep it for reuse.
+    pub fn put(&mut self, mut b: Box<T>) {
+        b.reset();
+        self.objects.push_back(b);
+    }
+
+    /// Return the number of objects held.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Return whether the pool holds no objects.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Drop the least recently used objects until at most `size` remain.
+    pub fn shrink(&mut self, size: usize) {
+        let excess = self.objects.len().saturating_sub(size);
+        self.objects.drain(..excess);
+    }
+}
+
+impl<T: Reset> Default for ObjectPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An object checked out of an `ObjectPool` by `checkout` or `checkout_or_else`, which is
+/// reset and put back into the pool when dropped.
+pub struct PooledObject<'a, T: Reset> {
+    object: ManuallyDrop<Box<T>>,
+    pool: &'a mut ObjectPool<T>,
+}
+
+impl<'a, T: Reset> PooledObject<'a, T> {
+    /// Detach the object from the pool, which will not get it back.
+    pub fn into_box(this: Self) -> Box<T> {
+        let mut this = ManuallyDrop::new(this);
+        unsafe { ManuallyDrop::take(&mut this.object) }
+    }
+}
+
+impl<'a, T: Reset> Deref for PooledObject<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<'a, T: Reset> DerefMut for PooledObject<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.object
+    }
+}
+
+impl<'a, T: Reset> Drop for PooledObject<'a, T> {
+    fn drop(&mut self) {
+        let b = unsafe { ManuallyDrop::take(&mut self.object) };
+        self.pool.put(b);
+    }
+}
+
+/// A pool of values that are reset, rather than dropped, when given back, such as scratch
+/// maps or queues that keep their allocations between uses. Unlike `ObjectPool`, the values
+/// are not boxed, so it suits containers that own their storage.
+pub struct ReusePool<T: Reset> {
+    values: Vec<T>,
+}
+
+impl<T: Reset> ReusePool<T> {
+    /// Create a new, empty ReusePool.
+    pub fn new() -> Self {
+        Self { values: Vec::new() }
+    }
+
+    /// Take the most recently used value, or build one with `f` if the pool is empty.
+    pub fn checkout_or_else<F: FnOnce() -> T>(&mut self, f: F) -> T {
+        self.values.pop().unwrap_or_else(f)
+    }
+
+    /// Take the most recently used value, or a default one if the pool is empty.
+    pub fn checkout(&mut self) -> T
+    where
+        T: Default,
+    {
+        self.checkout_or_else(T::default)
+    }
+
+    /// Reset a value and keep it for reuse.
+    pub fn checkin(&mut self, mut v: T) {
+        v.reset();
+        self.values.push(v);
+    }
+
+    /// Return the number of values held.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Return whether the pool holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Drop the least recently used values until at most `size` remain.
+    pub fn shrink(&mut self, size: usize) {
+        let excess = self.values.len().saturating_sub(size);
+        self.values.drain(..excess);
+    }
+}
+
+impl<T: Reset> Default for ReusePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+macro_rules! impl_reset_by_clear {
+    ($([$($gens:tt)*] $ty:ty),* $(,)?) => {
+        $(
+            impl<$($gens)*> Reset for $ty {
+                fn reset(&mut self) {
+                    self.clear();
+                }
+            }
+        )*
+    };
+}
+
+impl_reset_by_clear!(
+    [T] Vec<T>,
+    [T] VecDeque<T>,
+    [T: Ord] BinaryHeap<T>,
+    [K, V] BTreeMap<K, V>,
+    [T] BTreeSet<T>,
+    [] String,
+);
+
+#[cfg(feature = "std")]
+impl_reset_by_clear!(
+    [K, V, S: BuildHasher] HashMap<K, V, S>,
+    [T, S: BuildHasher] HashSet<T, S>,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Scratch(Vec<u8>);
+
+    impl Reset for Scratch {
+        fn reset(&mut self) {
+            self.0.clear();
+        }
+    }
+
+    #[test]
+    fn lru() {
+        let mut pool = ObjectPool::new();
+        let mut objects: Vec<_> = (0..3)
+            .map(|_| pool.get_or_else(|| Scratch(Vec::with_capacity(16))))
+            .collect();
+        let addrs: Vec<_> = objects.iter().map(|b| &**b as *const Scratch).collect();
+        for (i, o) in objects.iter_mut().enumerate() {
+            o.0.push(i as u8);
+        }
+        for o in objects {
+            pool.put(o);
+        }
+        assert_eq!(pool.len(), 3);
+
+        // The most recently used object comes out first, reset with its buffer intact.
+        let o = pool.get_or_else(|| unreachable!());
+        assert_eq!(&*o as *const Scratch, addrs[2]);
+        assert!(o.0.is_empty() && o.0.capacity() >= 16);
+        pool.put(o);
+
+        // Shrinking evicts the least recently used ones.
+        pool.shrink(1);
+        assert_eq!(&*pool.pop().unwrap() as *const Scratch, addrs[2]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn checkout() {
+        let mut pool = ObjectPool::with_factory(|| Scratch(Vec::with_capacity(16)));
+        let mut o = pool.checkout().unwrap();
+        o.0.push(1);
+        let addr = &*o as *const Scratch;
+        drop(o);
+        assert_eq!(pool.len(), 1);
+
+        let o = pool.checkout().unwrap();
+        assert_eq!(&*o as *const Scratch, addr);
+        assert!(o.0.is_empty());
+        let b = PooledObject::into_box(o);
+        assert!(pool.is_empty());
+        drop(b);
+
+        let mut pool = ObjectPool::new();
+        assert!(pool.checkout().is_none());
+        drop(pool.checkout_or_else(|| Scratch(vec![])));
+        assert!(pool.checkout().is_some());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reuse_pool() {
+        let mut pool = ReusePool::<HashMap<u32, String>>::new();
+        let mut scratch = pool.checkout();
+        scratch.extend((0..100).map(|i| (i, i.to_string())));
+        let capacity = scratch.capacity();
+        pool.checkin(scratch);
+
+        let scratch = pool.checkout_or_else(|| unreachable!());
+        assert!(scratch.is_empty());
+        assert_eq!(scratch.capacity(), capacity);
+        pool.checkin(scratch);
+        pool.shrink(0);
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Recording of the last operations of arenas, enabled by the `op-log` feature.
+
+use std::io::{self, Write};
+use std::panic::Location;
+use std::sync::atomic::{fence, AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::{clock, provenance, BoxingArena};
+
+/// The kind of an operation recorded in an `OpRing`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpKind {
+    /// A free box was handed out.
+    Hit,
+    /// A new box was allocated and handed out.
+    Miss,
+    /// A box was given back to the arena.
+    Unbox,
+    /// A free box was deallocated.
+    Release,
+}
+
+impl OpKind {
+    fn from_u8(v: u8) -> Option<Self> {
+        Some(match v {
+            0 => OpKind::Hit,
+            1 => OpKind::Miss,
+            2 => OpKind::Unbox,
+            3 => OpKind::Release,
+            _ => return None,
+        })
+    }
+}
+
+/// An operation recorded in an `OpRing`.
+#[derive(Clone, Copy, Debug)]
+pub struct OpRecord {
+    /// The position of the operation among all those recorded by the ring.
+    pub seq: u64,
+    /// What was done.
+    pub kind: OpKind,
+    /// The address of the box.
+    pub address: usize,
+    /// Nanoseconds since the ring was created, or 0 on targets without a clock.
+    pub timestamp_ns: u64,
+    /// The location of the call into the arena, when known.
+    pub caller: Option<&'static Location<'static>>,
+}
+
+#[derive(Default)]
+struct Entry {
+    // Zero while never written, odd while being written, and `2 * (seq + 1)` once done.
+    state: AtomicU64,
+    kind: AtomicU8,
+    address: AtomicUsize,
+    timestamp_ns: AtomicU64,
+    caller: AtomicPtr<Location<'static>>,
+}
+
+/// A fixed-size ring of the last operations done on the arenas it is installed in with
+/// `BoxingArena::set_op_log`.
+///
+/// Recording does not lock nor allocate, and the ring can be read from any thread at any
+/// time, for example from a panic hook, to reconstruct the sequence of operations that
+/// led to a corrupted box. Records overwritten while being read are skipped.
+pub struct OpRing {
+    entries: Box<[Entry]>,
+    next: AtomicU64,
+    start: Option<Instant>,
+}
+
+impl OpRing {
+    /// Create a ring keeping the last `size` operations.
+    pub fn new(size: usize) -> Arc<Self> {
+        Arc::new(Self {
+            entries: (0..size.max(1)).map(|_| Entry::default()).collect(),
+            next: AtomicU64::new(0),
+            start: clock::now(),
+        })
+    }
+
+    fn record(&self, kind: OpKind, address: usize, caller: Option<&'static Location<'static>>) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let entry = &self.entries[(seq % self.entries.len() as u64) as usize];
+        let caller = caller.map_or(std::ptr::null_mut(), |c| c as *const _ as *mut _);
+
+        entry.state.store(2 * seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        entry.kind.store(kind as u8, Ordering::Relaxed);
+        entry.address.store(address, Ordering::Relaxed);
+        let timestamp_ns = clock::elapsed_ns(self.start);
+        entry.timestamp_ns.store(timestamp_ns, Ordering::Relaxed);
+        entry.caller.store(caller, Ordering::Relaxed);
+        entry.state.store(2 * (seq + 1), Ordering::Release);
+    }
+
+    /// Return the recorded operations, oldest first.
+    pub fn records(&self) -> Vec<OpRecord> {
+        let mut records: Vec<_> = self.entries.iter().filter_map(Self::read).collect();
+        records.sort_by_key(|r| r.seq);
+        records
+    }
+
+    fn read(entry: &Entry) -> Option<OpRecord> {
+        let state = entry.state.load(Ordering::Acquire);
+        if state == 0 || state % 2 == 1 {
+            return None;
+        }
+        let kind = entry.kind.load(Ordering::Relaxed);
+        let address = entry.address.load(Ordering::Relaxed);
+        let timestamp_ns = entry.timestamp_ns.load(Ordering::Relaxed);
+        let caller = entry.caller.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if entry.state.load(Ordering::Relaxed) != state {
+            return None;
+        }
+
+        Some(OpRecord {
+            seq: state / 2 - 1,
+            kind: OpKind::from_u8(kind)?,
+            address,
+            timestamp_ns,
+            caller: unsafe { caller.as_ref() },
+        })
+    }
+
+    /// Write one line for each recorded operation, oldest first.
+    pub fn dump(&self, w: &mut dyn Write) -> io::Result<()> {
+        for r in self.records() {
+            write!(w, "boxing-arena: #{} {}ns {:?} {:#x}", r.seq, r.timestamp_ns, r.kind, r.address)?;
+            match r.caller {
+                Some(caller) => writeln!(w, " at {}", caller)?,
+                None => writeln!(w)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Record the operations of this arena in `ring`, or stop recording them if `None`.
+    /// Several arenas may share a ring.
+    pub fn set_op_log(&mut self, ring: Option<Arc<OpRing>>) {
+        self.op_log = ring;
+    }
+
+    /// Return the ring set by `set_op_log`.
+    pub fn op_log(&self) -> Option<&Arc<OpRing>> {
+        self.op_log.as_ref()
+    }
+
+    /// Record an operation on the box at `p`, done at `caller`.
+    pub(crate) fn record_op(&self, kind: OpKind, p: *const T, caller: &'static Location<'static>) {
+        if let Some(ring) = &self.op_log {
+            ring.record(kind, provenance::addr(p), Some(caller));
+        }
+    }
+}
+
+/// Record the release of the free box at `p` in `ring`, if any.
+pub(crate) fn record_release<T>(ring: &Option<Arc<OpRing>>, p: *const T) {
+    if let Some(ring) = ring {
+        ring.record(OpKind::Release, provenance::addr(p), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn op_log() {
+        let ring = OpRing::new(3);
+        let mut ba = BoxingArena::new();
+        ba.set_op_log(Some(ring.clone()));
+
+        let a = ba.rebox(1u32);
+        let addr = &*a as *const u32 as usize;
+        ba.unbox(a);
+        drop(ba.rebox(2));
+        ba.resize_capacity(2);
+        ba.resize_capacity(0);
+
+        // Only the last three operations remain.
+        let records = ring.records();
+        let kinds: Vec<_> = records.iter().map(|r| r.kind).collect();
+        assert_eq!(kinds, [OpKind::Hit, OpKind::Release, OpKind::Release][..]);
+        assert_eq!(records[0].seq, 2);
+        assert_eq!(records[0].address, addr);
+        assert!(records[1].caller.is_none());
+
+        let ring = OpRing::new(8);
+        ba.set_op_log(Some(ring.clone()));
+        let line = line!() + 1;
+        let b = ba.rebox(3u32);
+        ba.unbox(b);
+        let records = ring.records();
+        assert_eq!(records[0].kind, OpKind::Miss);
+        let caller = records[0].caller.unwrap();
+        assert_eq!((caller.file(), caller.line()), (file!(), line));
+
+        let mut out = vec![];
+        ring.dump(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.lines().nth(1).unwrap().contains(" Unbox "));
+    }
+}
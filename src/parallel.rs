@@ -0,0 +1,78 @@
+//! Pre-warming arenas across the threads of a `rayon` pool, enabled by the `rayon` feature.
+
+use std::alloc::Layout;
+
+use rayon::prelude::*;
+
+use crate::BoxingArena;
+
+/// A box allocated on a thread of the pool, on its way to the arena.
+struct SendPtr<T>(*mut T);
+
+// The allocation belongs to no other thread, and is only used by the arena once sent back.
+unsafe impl<T> Send for SendPtr<T> {}
+
+impl<T> BoxingArena<T> {
+    /// Like `resize_capacity`, but when growing, allocates the new free boxes, and prefaults
+    /// them if set to, on the threads of the current `rayon` pool, for pre-warming an arena of
+    /// millions of boxes at start-up. The hooks and locking of the arena still apply to each
+    /// box, on the calling thread.
+    ///
+    /// # Panics
+    ///
+    /// Calls `handle_alloc_error`, which aborts by default, if memory runs out. The boxes
+    /// allocated before are kept.
+    pub fn resize_capacity_parallel(&mut self, size: usize) {
+        let n = self.items.len();
+        if size <= n || core::mem::size_of::<T>() == 0 {
+            return self.resize_capacity(size);
+        }
+        if self.items.try_reserve(size - n).is_err() {
+            std::alloc::handle_alloc_error(Layout::new::<T>());
+        }
+
+        let prefault = self.prefault;
+        let mut boxes = Vec::new();
+        (n..size)
+            .into_par_iter()
+            .map(|_| unsafe {
+                let p = std::alloc::alloc(Layout::new::<T>()) as *mut T;
+                if prefault && !p.is_null() {
+                    Self::prefault_slot(p);
+                }
+                SendPtr(p)
+            })
+            .collect_into_vec(&mut boxes);
+
+        let failed = boxes.iter().any(|p| p.0.is_null());
+        for p in boxes.into_iter().map(|p| p.0).filter(|p| !p.is_null()) {
+            #[cfg(feature = "mlock")]
+            self.locking.lock_new(p);
+            self.hooks.fresh(p);
+            self.items.extend(core::iter::once(p));
+        }
+        #[cfg(feature = "tracing")]
+        self.trace_resize(n);
+        if failed {
+            std::alloc::handle_alloc_error(Layout::new::<T>());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_capacity_parallel() {
+        let mut ba = BoxingArena::<[u8; 4096]>::new();
+        ba.set_prefault(true);
+        ba.resize_capacity_parallel(1000);
+        assert_eq!(ba.capacity(), 1000);
+        let b = ba.rebox([1; 4096]);
+        assert_eq!((ba.stats().hits, ba.capacity()), (1, 999));
+        ba.unbox(b);
+        ba.resize_capacity_parallel(10);
+        assert_eq!(ba.capacity(), 10);
+    }
+}
@@ -0,0 +1,43 @@
+//! Poisoning of free boxes, enabled by the `poison` feature.
+
+/// The byte that free boxes are filled with.
+pub(crate) const POISON: u8 = 0xde;
+
+/// Fill the free box at `p` with the poison byte.
+#[inline]
+pub(crate) unsafe fn poison<T>(p: *mut T) {
+    core::ptr::write_bytes(p as *mut u8, POISON, core::mem::size_of::<T>());
+}
+
+/// Verify that the free box at `p` is still filled with the poison byte, panicking
+/// otherwise: something wrote to it after it was given back to its arena.
+#[inline]
+pub(crate) unsafe fn check<T>(p: *const T) {
+    let bytes = core::slice::from_raw_parts(p as *const u8, core::mem::size_of::<T>());
+    if let Some(offset) = bytes.iter().position(|&b| b != POISON) {
+        panic!(
+            "free box at {:p} was written to at offset {} after being given back to its arena",
+            p, offset
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BoxingArena;
+
+    #[test]
+    #[cfg_attr(feature = "no-panic", ignore = "aborts with no-panic")]
+    fn poison() {
+        let mut ba = BoxingArena::with_capacity(1);
+        let a = ba.rebox([1u32; 4]);
+        let p = &*a as *const [u32; 4] as *mut u8;
+        ba.unbox(a);
+        assert_eq!(unsafe { *p.add(3) }, super::POISON);
+
+        // A write after `unbox`, as a dangling pointer would do.
+        unsafe { *p.add(6) = 0 };
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ba.rebox([2; 4])));
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Policies deciding how many free boxes an arena retains.
+
+use alloc::boxed::Box;
+
+use crate::BoxingArena;
+
+/// Decides whether an arena keeps the allocations of the boxes given back to it, and how far
+/// `maintain` trims its free boxes, see `BoxingArena::set_capacity_policy`.
+pub trait CapacityPolicy: Send {
+    /// Return whether to keep the allocation of a box given back to an arena that holds
+    /// `capacity` free boxes, rather than freeing it right away.
+    fn keep(&mut self, capacity: usize) -> bool;
+
+    /// Return the number of free boxes to trim an arena holding `capacity` of them down to,
+    /// when `maintain` is called, or `None` to keep them all. The default keeps them all.
+    fn trim_to(&mut self, capacity: usize) -> Option<usize> {
+        let _ = capacity;
+        None
+    }
+
+    /// Return the policy of an arena forked from one with this policy, by `fork_empty` or
+    /// `split_off`, or `None` for the fork to keep every box. The default returns `None`.
+    fn fork(&self) -> Option<Box<dyn CapacityPolicy>> {
+        None
+    }
+}
+
+/// Keep every box given back, which is what arenas do without a policy.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unbounded;
+
+impl CapacityPolicy for Unbounded {
+    fn keep(&mut self, _capacity: usize) -> bool {
+        true
+    }
+
+    fn fork(&self) -> Option<Box<dyn CapacityPolicy>> {
+        Some(Box::new(*self))
+    }
+}
+
+/// Keep at most the given number of free boxes, freeing the boxes given back beyond it.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounded(pub usize);
+
+impl CapacityPolicy for Bounded {
+    fn keep(&mut self, capacity: usize) -> bool {
+        capacity < self.0
+    }
+
+    fn trim_to(&mut self, _capacity: usize) -> Option<usize> {
+        Some(self.0)
+    }
+
+    fn fork(&self) -> Option<Box<dyn CapacityPolicy>> {
+        Some(Box::new(*self))
+    }
+}
+
+/// Keep every box given back, and on each `maintain`, trim the free boxes to a fraction of
+/// the most that the arena held at once since the previous one. An arena that stays idle
+/// thus gives its memory back gradually, while one under steady load keeps what it uses.
+#[derive(Clone, Copy, Debug)]
+pub struct HighWatermark {
+    keep_fraction: f64,
+    peak: usize,
+}
+
+impl HighWatermark {
+    /// Create a policy keeping `keep_fraction`, between 0 and 1, of the high watermark.
+    pub fn new(keep_fraction: f64) -> Self {
+        Self {
+            keep_fraction,
+            peak: 0,
+        }
+    }
+
+    /// Return the fraction of the high watermark that is kept.
+    pub fn keep_fraction(&self) -> f64 {
+        self.keep_fraction
+    }
+}
+
+impl CapacityPolicy for HighWatermark {
+    fn keep(&mut self, capacity: usize) -> bool {
+        self.peak = self.peak.max(capacity + 1);
+        true
+    }
+
+    fn trim_to(&mut self, capacity: usize) -> Option<usize> {
+        let keep = self.peak.max(capacity) as f64 * self.keep_fraction;
+        self.peak = 0;
+        // Rounded up, without `f64::ceil`, which needs `std`.
+        let size = keep as usize;
+        Some(if (size as f64) < keep { size + 1 } else { size })
+    }
+
+    /// The fork starts with its own high watermark.
+    fn fork(&self) -> Option<Box<dyn CapacityPolicy>> {
+        Some(Box::new(Self::new(self.keep_fraction)))
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Let `policy` decide whether the boxes given back are kept, by `unbox` and the other
+    /// functions taking boxes back, and how far `maintain` trims the free boxes. A forked
+    /// arena gets the policy returned by `CapacityPolicy::fork`.
+    pub fn set_capacity_policy<P: CapacityPolicy + 'static>(&mut self, policy: P) {
+        self.policy = Some(Box::new(policy));
+    }
+
+    /// Go back to keeping every box given back.
+    pub fn clear_capacity_policy(&mut self) {
+        self.policy = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies() {
+        let mut ba = BoxingArena::new();
+        ba.set_capacity_policy(Bounded(1));
+        let (a, b) = (ba.rebox(1u32), ba.rebox(2u32));
+        ba.unbox(a);
+        ba.unbox(b);
+        assert_eq!(ba.capacity(), 1);
+
+        let mut ba = BoxingArena::<u32>::with_capacity(4);
+        ba.set_capacity_policy(HighWatermark::new(0.5));
+        let boxes: Vec<_> = (0..4).map(|i| ba.rebox(i)).collect();
+        for b in boxes {
+            ba.unbox(b);
+        }
+        ba.maintain();
+        assert_eq!(ba.capacity(), 2);
+        ba.maintain();
+        assert_eq!(ba.capacity(), 1);
+
+        // A split off arena keeps the policy.
+        let mut ba = BoxingArena::<u32>::with_capacity(2);
+        ba.set_capacity_policy(Bounded(1));
+        let mut other = ba.split_off(1);
+        let (a, b) = (other.rebox(1), other.rebox(2));
+        other.unbox(a);
+        other.unbox(b);
+        assert_eq!(other.capacity(), 1);
+    }
+}
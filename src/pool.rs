@@ -0,0 +1,88 @@
+//! A trait for the operations that arenas have in common.
+
+use alloc::boxed::Box;
+use core::cell::RefCell;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use crate::{ArenaBox, BoxingArena};
+
+/// Boxing and unboxing through a pool of allocations, so that code can be written against
+/// any implementation, for example to record or replay it, or to switch pooling off.
+pub trait Pool<T> {
+    /// Box a value, reusing a free allocation if the pool has one.
+    fn rebox(&mut self, v: T) -> Box<T>;
+
+    /// Take the value out of the box, and keep the allocation for reuse if the pool wants it.
+    fn unbox(&mut self, b: Box<T>) -> T;
+
+    /// Release free allocations so that at most `size` of them remain.
+    fn trim(&mut self, size: usize);
+
+    /// Return the number of free allocations held.
+    fn capacity(&self) -> usize;
+}
+
+/// A pool that can be used through a shared reference, so that several `ArenaBox` guards
+/// can borrow it at once.
+pub trait SharedPool<T> {
+    /// Box a value, reusing a free allocation if the pool has one.
+    fn rebox(&self, v: T) -> Box<T>;
+
+    /// Take the value out of the box, and keep the allocation for reuse if the pool wants it.
+    fn unbox(&self, b: Box<T>) -> T;
+
+    /// Box a value in a guard that gives the allocation back to the pool when dropped.
+    #[track_caller]
+    fn rebox_guarded(&self, v: T) -> ArenaBox<'_, T>
+    where
+        Self: Sized,
+    {
+        ArenaBox::shared(self.rebox(v), self)
+    }
+}
+
+impl<T> Pool<T> for BoxingArena<T> {
+    #[track_caller]
+    fn rebox(&mut self, v: T) -> Box<T> {
+        BoxingArena::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&mut self, b: Box<T>) -> T {
+        BoxingArena::unbox(self, b)
+    }
+
+    fn trim(&mut self, size: usize) {
+        BoxingArena::trim(self, size)
+    }
+
+    fn capacity(&self) -> usize {
+        BoxingArena::capacity(self)
+    }
+}
+
+impl<T> SharedPool<T> for RefCell<BoxingArena<T>> {
+    #[track_caller]
+    fn rebox(&self, v: T) -> Box<T> {
+        self.borrow_mut().rebox(v)
+    }
+
+    #[track_caller]
+    fn unbox(&self, b: Box<T>) -> T {
+        self.borrow_mut().unbox(b)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> SharedPool<T> for Mutex<BoxingArena<T>> {
+    #[track_caller]
+    fn rebox(&self, v: T) -> Box<T> {
+        self.lock().unwrap_or_else(|e| e.into_inner()).rebox(v)
+    }
+
+    #[track_caller]
+    fn unbox(&self, b: Box<T>) -> T {
+        self.lock().unwrap_or_else(|e| e.into_inner()).unbox(b)
+    }
+}
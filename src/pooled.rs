@@ -0,0 +1,59 @@
+//! Types boxed by arenas of their own, local to each thread.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{BoxingArena, PooledBox};
+
+/// Types with an arena of their own in each thread, for boxing them without passing an arena
+/// around. With the `derive` feature, `#[derive(Pooled)]` implements it, and
+/// `#[pooled(capacity = N)]` sets the most free boxes each of the arenas keeps:
+///
+/// ```rust,ignore
+/// use boxing_arena::Pooled;
+///
+/// #[derive(Pooled)]
+/// #[pooled(capacity = 128)]
+/// struct Message {
+///     payload: [u8; 256],
+/// }
+///
+/// let m = Message::pooled_new(Message { payload: [0; 256] });
+/// assert_eq!(m.payload.len(), 256);
+/// ```
+pub trait Pooled: Sized {
+    /// Return the arena of the current thread for the type.
+    fn local_arena() -> Rc<RefCell<BoxingArena<Self>>>;
+
+    /// Store the value in a box from the arena of the current thread, which it goes back to
+    /// when dropped.
+    #[track_caller]
+    fn pooled_new(v: Self) -> PooledBox<Self> {
+        PooledBox::new_in(v, &Self::local_arena())
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::Pooled;
+
+    #[derive(Pooled)]
+    #[pooled(capacity = 1)]
+    struct Event {
+        id: u64,
+    }
+
+    #[test]
+    fn derive() {
+        let a = Event::pooled_new(Event { id: 1 });
+        let b = Event::pooled_new(Event { id: 2 });
+        let addr = &*a as *const Event;
+        assert_eq!(a.id + b.id, 3);
+        drop(a);
+        drop(b);
+
+        let arena = Event::local_arena();
+        assert_eq!(arena.borrow().capacity(), 1);
+        assert_eq!(&*Event::pooled_new(Event { id: 3 }) as *const Event, addr);
+    }
+}
@@ -0,0 +1,124 @@
+//! A box that owns a handle to its arena, and goes back to it when dropped.
+
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+use crate::BoxingArena;
+
+/// A box from an arena shared through `Rc<RefCell<_>>`, which gives its allocation back to
+/// the arena when dropped. Unlike `ArenaBox`, it does not borrow the arena, so it can be
+/// stored in long-lived structures.
+///
+/// If the arena is already borrowed when the box is dropped, the allocation is freed
+/// instead. Like `Rc`, it is meant for use within a single thread.
+pub struct PooledBox<T> {
+    value: ManuallyDrop<Box<T>>,
+    arena: Rc<RefCell<BoxingArena<T>>>,
+}
+
+impl<T> PooledBox<T> {
+    /// Store `v` in a box from `arena`.
+    #[track_caller]
+    pub fn new_in(v: T, arena: &Rc<RefCell<BoxingArena<T>>>) -> Self {
+        let b = arena.borrow_mut().rebox(v);
+        Self {
+            value: ManuallyDrop::new(b),
+            arena: arena.clone(),
+        }
+    }
+
+    /// Return the arena that the box goes back to.
+    pub fn arena(this: &Self) -> &Rc<RefCell<BoxingArena<T>>> {
+        &this.arena
+    }
+
+    fn into_parts(this: Self) -> (Box<T>, Rc<RefCell<BoxingArena<T>>>) {
+        let mut this = ManuallyDrop::new(this);
+        unsafe { (ManuallyDrop::take(&mut this.value), core::ptr::read(&this.arena)) }
+    }
+
+    /// Take the value out, giving the allocation back to the arena.
+    #[track_caller]
+    pub fn into_inner(this: Self) -> T {
+        let (b, arena) = Self::into_parts(this);
+        let v = arena.borrow_mut().unbox(b);
+        v
+    }
+
+    /// Detach the box from the arena, which will not get the allocation back.
+    pub fn into_box(this: Self) -> Box<T> {
+        Self::into_parts(this).0
+    }
+}
+
+impl<T> Deref for PooledBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for PooledBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl_borrow!([T] PooledBox<T>, mut);
+impl_cmp!([T] PooledBox<T>);
+
+// The value is in a box, which stays in place until the `PooledBox` is dropped.
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::StableDeref for PooledBox<T> {}
+
+impl<T> Drop for PooledBox<T> {
+    fn drop(&mut self) {
+        let b = unsafe { ManuallyDrop::take(&mut self.value) };
+        let v = match self.arena.try_borrow_mut() {
+            Ok(mut arena) => arena.unbox(b),
+            Err(_) => *b,
+        };
+        // Outside of the borrow, in case dropping it uses the arena.
+        drop(v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Node {
+        children: Vec<PooledBox<Node>>,
+    }
+
+    #[test]
+    fn pooled_box() {
+        let arena = Rc::new(RefCell::new(BoxingArena::new()));
+        let leaf = |arena| PooledBox::new_in(Node { children: vec![] }, arena);
+        let root = PooledBox::new_in(
+            Node {
+                children: vec![leaf(&arena), leaf(&arena)],
+            },
+            &arena,
+        );
+        assert_eq!(root.children.len(), 2);
+
+        // Children go back as the root's value is dropped, outside of the borrow.
+        drop(root);
+        assert_eq!(arena.borrow().capacity(), 3);
+
+        let b = PooledBox::new_in(Node { children: vec![] }, &arena);
+        let held = arena.borrow_mut();
+        drop(b);
+        assert_eq!(held.capacity(), 2);
+        drop(held);
+
+        let v = PooledBox::into_inner(leaf(&arena));
+        assert!(v.children.is_empty());
+        assert_eq!(arena.borrow().capacity(), 2);
+    }
+}
@@ -0,0 +1,161 @@
+//! A global allocator pooling the allocations of one layout.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A `GlobalAlloc` keeping freed allocations of one layout for reuse, and forwarding all
+/// other allocations to `inner`, for getting the benefit of an arena for a type boxed by code
+/// that cannot be changed, such as a dependency:
+///
+/// ```rust
+/// use boxing_arena::PoolingAlloc;
+/// use std::alloc::{Layout, System};
+///
+/// #[global_allocator]
+/// static ALLOC: PoolingAlloc<System> =
+///     PoolingAlloc::new(System, Layout::new::<[u64; 8]>(), 1024);
+/// # fn main() {}
+/// ```
+///
+/// The free allocations are linked through their own memory, so layouts smaller than a
+/// pointer are not pooled, and the list is behind a spin lock, held only for a few
+/// instructions.
+pub struct PoolingAlloc<A> {
+    inner: A,
+    layout: Layout,
+    max_free: usize,
+    locked: AtomicBool,
+    free: UnsafeCell<FreeBlocks>,
+}
+
+struct FreeBlocks {
+    /// The first free allocation, holding the address of the next one.
+    head: *mut u8,
+    len: usize,
+}
+
+// The free list is only accessed with the lock held.
+unsafe impl<A: Sync> Sync for PoolingAlloc<A> {}
+
+impl<A> PoolingAlloc<A> {
+    /// Pool the allocations of `layout`, keeping at most `max_free` of them free, and forward
+    /// the others to `inner`.
+    pub const fn new(inner: A, layout: Layout, max_free: usize) -> Self {
+        Self {
+            inner,
+            layout,
+            max_free,
+            locked: AtomicBool::new(false),
+            free: UnsafeCell::new(FreeBlocks {
+                head: core::ptr::null_mut(),
+                len: 0,
+            }),
+        }
+    }
+
+    /// Return the number of free allocations kept.
+    pub fn free_count(&self) -> usize {
+        self.with_free(|free| free.len)
+    }
+
+    fn pools(&self, layout: Layout) -> bool {
+        layout == self.layout && layout.size() >= core::mem::size_of::<*mut u8>()
+    }
+
+    fn with_free<R>(&self, f: impl FnOnce(&mut FreeBlocks) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let r = f(unsafe { &mut *self.free.get() });
+        self.locked.store(false, Ordering::Release);
+        r
+    }
+}
+
+impl<A: GlobalAlloc> PoolingAlloc<A> {
+    /// Give the free allocations back to the inner allocator.
+    pub fn release_free(&self) {
+        let mut p = self.with_free(|free| {
+            free.len = 0;
+            core::mem::replace(&mut free.head, core::ptr::null_mut())
+        });
+        while !p.is_null() {
+            unsafe {
+                let next = (p as *mut *mut u8).read_unaligned();
+                self.inner.dealloc(p, self.layout);
+                p = next;
+            }
+        }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for PoolingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.pools(layout) {
+            let p = self.with_free(|free| {
+                let p = free.head;
+                if !p.is_null() {
+                    free.head = (p as *mut *mut u8).read_unaligned();
+                    free.len -= 1;
+                }
+                p
+            });
+            if !p.is_null() {
+                return p;
+            }
+        }
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if self.pools(layout) {
+            let kept = self.with_free(|free| {
+                if free.len == self.max_free {
+                    return false;
+                }
+                (ptr as *mut *mut u8).write_unaligned(free.head);
+                free.head = ptr;
+                free.len += 1;
+                true
+            });
+            if kept {
+                return;
+            }
+        }
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::System;
+
+    #[test]
+    fn pooling_alloc() {
+        let layout = Layout::new::<[u64; 4]>();
+        let alloc = PoolingAlloc::new(System, layout, 1);
+        unsafe {
+            let a = alloc.alloc(layout);
+            let b = alloc.alloc(layout);
+            alloc.dealloc(a, layout);
+            alloc.dealloc(b, layout);
+            assert_eq!(alloc.free_count(), 1);
+            assert_eq!(alloc.alloc(layout), a);
+
+            // Other layouts go to the inner allocator.
+            let other = Layout::new::<[u64; 2]>();
+            let c = alloc.alloc(other);
+            alloc.dealloc(c, other);
+            assert_eq!(alloc.free_count(), 0);
+            alloc.dealloc(a, layout);
+        }
+        alloc.release_free();
+        assert_eq!(alloc.free_count(), 0);
+    }
+}
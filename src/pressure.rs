@@ -0,0 +1,144 @@
+//! Trimming of registered arenas when the system runs low on memory, enabled by the
+//! `pressure` feature.
+//!
+//! The arenas registered with `registry::register` are trimmed with `registry::trim_all`,
+//! releasing all of their free boxes. On Windows, macOS and Linux, `watch_low_memory` makes
+//! the operating system's low memory notifications call it. On Linux, these are the pressure
+//! stall information (PSI) triggers of `/proc/pressure/memory`, so the kernel must have PSI
+//! enabled.
+
+#[cfg(any(windows, target_os = "macos", target_os = "linux"))]
+use crate::registry::trim_all;
+
+/// Spawn a thread that calls `registry::trim_all` each time the system enters a low memory
+/// condition, as signaled by `CreateMemoryResourceNotification`.
+#[cfg(windows)]
+pub fn watch_low_memory() -> std::io::Result<()> {
+    use windows_sys::Win32::System::Memory::{
+        CreateMemoryResourceNotification, LowMemoryResourceNotification,
+        QueryMemoryResourceNotification,
+    };
+    use windows_sys::Win32::System::Threading::{WaitForSingleObject, INFINITE};
+
+    let handle = unsafe { CreateMemoryResourceNotification(LowMemoryResourceNotification) };
+    if handle.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The handle is only used by the watcher thread, and never closed.
+    let handle = crate::provenance::expose(handle);
+
+    std::thread::Builder::new()
+        .name("boxing-arena-pressure".into())
+        .spawn(move || loop {
+            let handle: windows_sys::Win32::Foundation::HANDLE = crate::provenance::from_exposed(handle);
+            unsafe { WaitForSingleObject(handle, INFINITE) };
+            trim_all();
+
+            // The notification stays signaled while memory is low, wait for it to clear.
+            loop {
+                let mut low = 0;
+                if unsafe { QueryMemoryResourceNotification(handle, &mut low) } == 0 || low == 0 {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+        })?;
+    Ok(())
+}
+
+/// Call `registry::trim_all` each time the system reports memory pressure, through a dispatch
+/// memory pressure source on the global queue.
+#[cfg(target_os = "macos")]
+pub fn watch_low_memory() -> std::io::Result<()> {
+    use std::ffi::c_void;
+
+    #[allow(non_upper_case_globals)]
+    extern "C" {
+        static _dispatch_source_type_memorypressure: c_void;
+        fn dispatch_get_global_queue(identifier: isize, flags: usize) -> *mut c_void;
+        fn dispatch_source_create(
+            kind: *const c_void,
+            handle: usize,
+            mask: usize,
+            queue: *mut c_void,
+        ) -> *mut c_void;
+        fn dispatch_source_set_event_handler_f(
+            source: *mut c_void,
+            handler: extern "C" fn(*mut c_void),
+        );
+        fn dispatch_resume(object: *mut c_void);
+    }
+
+    const DISPATCH_MEMORYPRESSURE_WARN: usize = 0x2;
+    const DISPATCH_MEMORYPRESSURE_CRITICAL: usize = 0x4;
+
+    extern "C" fn handler(_context: *mut c_void) {
+        trim_all();
+    }
+
+    // The source is never cancelled, it lives as long as the process.
+    unsafe {
+        let source = dispatch_source_create(
+            &_dispatch_source_type_memorypressure,
+            0,
+            DISPATCH_MEMORYPRESSURE_WARN | DISPATCH_MEMORYPRESSURE_CRITICAL,
+            dispatch_get_global_queue(0, 0),
+        );
+        if source.is_null() {
+            return Err(std::io::Error::other("cannot create a memory pressure source"));
+        }
+        dispatch_source_set_event_handler_f(source, handler);
+        dispatch_resume(source);
+    }
+    Ok(())
+}
+
+/// Spawn a thread that calls `registry::trim_all` each time some tasks stall on memory for
+/// 150ms within 2s, as signaled by a trigger on `/proc/pressure/memory`. Fails if the
+/// kernel has no PSI support.
+#[cfg(target_os = "linux")]
+pub fn watch_low_memory() -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+
+    let mut file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open("/proc/pressure/memory")?;
+    // The trigger lives as long as the file stays open, and its window must be a multiple of
+    // 2s for unprivileged processes. It is armed by a single write.
+    file.write_all(b"some 150000 2000000\0")?;
+
+    std::thread::Builder::new()
+        .name("boxing-arena-pressure".into())
+        .spawn(move || loop {
+            let mut fd = libc::pollfd { fd: file.as_raw_fd(), events: libc::POLLPRI, revents: 0 };
+            if unsafe { libc::poll(&mut fd, 1, -1) } < 0 {
+                if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+            if fd.revents & libc::POLLERR != 0 {
+                // The trigger is gone, such as when the cgroup is removed.
+                break;
+            }
+            if fd.revents & libc::POLLPRI != 0 {
+                trim_all();
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    #[test]
+    fn watch_psi() {
+        // Containers and older kernels may have no PSI.
+        if std::path::Path::new("/proc/pressure/memory").exists() {
+            super::watch_low_memory().unwrap();
+        }
+    }
+}
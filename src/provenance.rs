@@ -0,0 +1,85 @@
+//! Conversions between pointers and addresses.
+//!
+//! By default these are plain casts. With the `strict-provenance` feature, they use the
+//! strict-provenance APIs instead, so that no pointer obtained from an integer is ever
+//! dereferenced without its provenance having been exposed first, as required on targets
+//! where pointers carry more than an address, such as CHERI.
+
+/// Return the address of `p`, for bookkeeping. It must not be turned back into a pointer
+/// that is dereferenced.
+#[inline(always)]
+pub(crate) fn addr<T>(p: *const T) -> usize {
+    #[cfg(feature = "strict-provenance")]
+    {
+        p.addr()
+    }
+    #[cfg(not(feature = "strict-provenance"))]
+    {
+        p as usize
+    }
+}
+
+/// Return the address of `p`, allowing `from_exposed` to turn it back into a usable pointer.
+#[cfg(any(feature = "checked", all(feature = "pressure", windows)))]
+#[inline(always)]
+pub(crate) fn expose<T>(p: *const T) -> usize {
+    #[cfg(feature = "strict-provenance")]
+    {
+        p.expose_provenance()
+    }
+    #[cfg(not(feature = "strict-provenance"))]
+    {
+        p as usize
+    }
+}
+
+/// Return a pointer to `addr`, which must come from `expose`.
+#[cfg(any(feature = "checked", all(feature = "pressure", windows)))]
+#[inline(always)]
+pub(crate) fn from_exposed<T>(addr: usize) -> *mut T {
+    #[cfg(feature = "strict-provenance")]
+    {
+        core::ptr::with_exposed_provenance_mut(addr)
+    }
+    #[cfg(not(feature = "strict-provenance"))]
+    {
+        addr as *mut T
+    }
+}
+
+/// Return a pointer to `addr` without provenance, for passing an address to the system. It
+/// must not be dereferenced.
+#[cfg(all(unix, feature = "std"))]
+#[inline(always)]
+pub(crate) fn without_provenance<T>(addr: usize) -> *mut T {
+    #[cfg(feature = "strict-provenance")]
+    {
+        core::ptr::without_provenance_mut(addr)
+    }
+    #[cfg(not(feature = "strict-provenance"))]
+    {
+        addr as *mut T
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses() {
+        let v = 7u32;
+        let p: *const u32 = &v;
+        assert_eq!(addr(p), p as usize);
+    }
+
+    #[test]
+    #[cfg(any(feature = "checked", all(feature = "pressure", windows)))]
+    fn round_trip() {
+        let mut v = 7u32;
+        let p: *mut u32 = &mut v;
+        let q: *mut u32 = from_exposed(expose(p));
+        unsafe { *q += 1 };
+        assert_eq!(v, 8);
+    }
+}
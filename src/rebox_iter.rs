@@ -0,0 +1,67 @@
+//! Mapping iterators of boxes through an arena.
+
+use alloc::boxed::Box;
+
+use crate::BoxingArena;
+
+/// An extension trait for iterators of boxes, mapping each value into a box that reuses the
+/// allocation of the box it came from, see `BoxingArena::map_reboxed`.
+pub trait ReboxIterExt<U>: Iterator<Item = Box<U>> + Sized {
+    /// Unbox each item, apply `f` to its value, and box the result, reusing the allocation of
+    /// the item when `U` and `T` have the same layout, and taking a box from `arena`
+    /// otherwise.
+    fn map_reboxed<T, F>(self, arena: &mut BoxingArena<T>, f: F) -> MapReboxed<'_, Self, T, F>
+    where
+        F: FnMut(U) -> T,
+    {
+        MapReboxed {
+            iter: self,
+            arena,
+            f,
+        }
+    }
+}
+
+impl<U, I: Iterator<Item = Box<U>>> ReboxIterExt<U> for I {}
+
+/// The iterator returned by `ReboxIterExt::map_reboxed`.
+pub struct MapReboxed<'a, I, T, F> {
+    iter: I,
+    arena: &'a mut BoxingArena<T>,
+    f: F,
+}
+
+impl<'a, U, I, T, F> Iterator for MapReboxed<'a, I, T, F>
+where
+    I: Iterator<Item = Box<U>>,
+    F: FnMut(U) -> T,
+{
+    type Item = Box<T>;
+
+    fn next(&mut self) -> Option<Box<T>> {
+        let b = self.iter.next()?;
+        let f = &mut self.f;
+        Some(self.arena.map_reboxed(b, f))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_reboxed() {
+        let mut ba = BoxingArena::<u64>::new();
+        let boxes = vec![Box::new(1i64), Box::new(-2)];
+        let addrs: Vec<_> = boxes.iter().map(|b| &**b as *const i64 as usize).collect();
+        let mapped: Vec<_> = boxes.into_iter().map_reboxed(&mut ba, i64::unsigned_abs).collect();
+        assert_eq!(mapped, [Box::new(1), Box::new(2)]);
+        let mapped_addrs: Vec<_> = mapped.iter().map(|b| &**b as *const u64 as usize).collect();
+        assert_eq!(mapped_addrs, addrs);
+        assert_eq!(ba.stats().hits, 2);
+    }
+}
@@ -0,0 +1,182 @@
+//! An arena whose boxes are carved out of a memory region given by the caller.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+/// A slot of the region, holding either a value or, while free, the next free slot.
+#[repr(C)]
+union Slot<T> {
+    value: ManuallyDrop<T>,
+    next: *mut Slot<T>,
+}
+
+/// An arena that never touches the allocator: its slots are carved out of a memory region
+/// supplied by the caller, such as a static buffer, for targets that forbid heap allocation
+/// after startup. Slots are taken from the region as they are first needed, and once given
+/// back, are linked in a free list kept within them.
+///
+/// As a `Box` would free its allocation with the global allocator, the arena hands out
+/// `RegionBox`es instead, which give their slot back when dropped, and borrow the arena
+/// meanwhile.
+pub struct RegionArena<T> {
+    /// The first slot never handed out, followed by `unused` more.
+    next_unused: Cell<*mut Slot<T>>,
+    unused: Cell<usize>,
+    free: Cell<*mut Slot<T>>,
+    free_len: Cell<usize>,
+    slots: usize,
+    _marker: PhantomData<T>,
+}
+
+// The arena only holds a region without values, which it has exclusive access to.
+unsafe impl<T: Send> Send for RegionArena<T> {}
+
+impl<T> RegionArena<T> {
+    /// Create an arena over the whole of `region`.
+    pub fn new(region: &'static mut [MaybeUninit<u8>]) -> Self {
+        unsafe { Self::from_raw_parts(region.as_mut_ptr() as *mut u8, region.len()) }
+    }
+
+    /// Create an arena over the `len` bytes at `ptr`, leaving out those before the first
+    /// address aligned for the slots.
+    ///
+    /// # Safety
+    ///
+    /// The bytes must be valid for reads and writes, and not be accessed other than through
+    /// the arena, for as long as it lives.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> Self {
+        let size = core::mem::size_of::<Slot<T>>();
+        let offset = ptr.align_offset(core::mem::align_of::<Slot<T>>()).min(len);
+        let slots = (len - offset) / size;
+        Self {
+            next_unused: Cell::new(ptr.add(offset) as *mut Slot<T>),
+            unused: Cell::new(slots),
+            free: Cell::new(ptr::null_mut()),
+            free_len: Cell::new(0),
+            slots,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Box the value in a free slot, or give it back in `Err` if the region is exhausted.
+    pub fn rebox(&self, v: T) -> Result<RegionBox<'_, T>, T> {
+        let slot = match self.take_slot() {
+            Some(slot) => slot,
+            None => return Err(v),
+        };
+        unsafe {
+            let p = slot as *mut T;
+            p.write(v);
+            Ok(RegionBox {
+                ptr: NonNull::new_unchecked(p),
+                arena: self,
+            })
+        }
+    }
+
+    fn take_slot(&self) -> Option<*mut Slot<T>> {
+        let free = self.free.get();
+        if !free.is_null() {
+            self.free.set(unsafe { (*free).next });
+            self.free_len.set(self.free_len.get() - 1);
+            return Some(free);
+        }
+        let unused = self.unused.get().checked_sub(1)?;
+        let slot = self.next_unused.get();
+        self.next_unused.set(unsafe { slot.add(1) });
+        self.unused.set(unused);
+        Some(slot)
+    }
+
+    /// Give back a slot of which the value was moved out or dropped.
+    unsafe fn give_back(&self, slot: *mut Slot<T>) {
+        ptr::addr_of_mut!((*slot).next).write(self.free.get());
+        self.free.set(slot);
+        self.free_len.set(self.free_len.get() + 1);
+    }
+
+    /// Return the number of slots that can still be handed out.
+    pub fn capacity(&self) -> usize {
+        self.free_len.get() + self.unused.get()
+    }
+
+    /// Return the number of slots the region holds in all.
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+}
+
+/// A box from a `RegionArena`, which gives its slot back to the arena when dropped.
+pub struct RegionBox<'a, T> {
+    ptr: NonNull<T>,
+    arena: &'a RegionArena<T>,
+}
+
+impl<'a, T> RegionBox<'a, T> {
+    /// Take the value out, giving the slot back to the arena.
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        unsafe {
+            let v = this.ptr.as_ptr().read();
+            this.arena.give_back(this.ptr.as_ptr() as *mut Slot<T>);
+            v
+        }
+    }
+}
+
+impl<'a, T> Deref for RegionBox<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<'a, T> DerefMut for RegionBox<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl_borrow!(['a, T] RegionBox<'a, T>, mut);
+impl_cmp!(['a, T] RegionBox<'a, T>);
+
+impl<'a, T: core::fmt::Debug> core::fmt::Debug for RegionBox<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<'a, T> Drop for RegionBox<'a, T> {
+    fn drop(&mut self) {
+        // The slot goes back even if dropping the value panics.
+        let v = unsafe { self.ptr.as_ptr().read() };
+        unsafe { self.arena.give_back(self.ptr.as_ptr() as *mut Slot<T>) };
+        drop(v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_arena() {
+        let mut region = [const { MaybeUninit::<String>::uninit() }; 2];
+        let len = core::mem::size_of_val(&region);
+        let arena = unsafe { RegionArena::from_raw_parts(region.as_mut_ptr() as *mut u8, len) };
+        assert_eq!((arena.slots(), arena.capacity()), (2, 2));
+
+        let a = arena.rebox(String::from("a")).unwrap();
+        let b = arena.rebox(String::from("b")).unwrap();
+        assert_eq!(arena.rebox(String::from("c")).unwrap_err(), "c");
+        let addr = &*a as *const String;
+        drop(a);
+        let c = arena.rebox(String::from("c")).unwrap();
+        assert_eq!(&*c as *const String, addr);
+        assert_eq!((RegionBox::into_inner(b), arena.capacity()), (String::from("b"), 1));
+    }
+}
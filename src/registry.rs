@@ -0,0 +1,115 @@
+//! Trimming and reporting on all the arenas of the process at once, enabled by the `registry`
+//! feature.
+//!
+//! Arenas shared between threads, through `Arc<Mutex<BoxingArena<T>>>` or
+//! `Arc<SyncBoxingArena<T>>`, are registered under a name with `register`, in the registry
+//! of the `diagnostics` module, so that their stats are dumped too. Then `trim_all` releases
+//! the free boxes of all of them, for example upon a memory pressure signal, `stats_all`
+//! collects the stats of all registered arenas in one place, and `arenas` enumerates them.
+
+use std::sync::{Arc, Mutex};
+
+use crate::diagnostics::{self, Entry, StatsSource};
+use crate::{ArenaStats, BoxingArena, SyncBoxingArena};
+
+/// A shared arena that can also be trimmed from any thread.
+pub trait Managed: StatsSource {
+    /// Release the free boxes, returning the number of bytes released. Returns 0 without
+    /// blocking if the arena is busy.
+    fn trim_free(&self) -> usize;
+}
+
+fn trim_free<T>(arena: &mut BoxingArena<T>) -> usize {
+    let bytes = arena.capacity_bytes();
+    arena.resize_capacity(0);
+    arena.maintain();
+    bytes
+}
+
+impl<T: Send> Managed for Mutex<BoxingArena<T>> {
+    fn trim_free(&self) -> usize {
+        self.try_lock().map_or(0, |mut arena| trim_free(&mut arena))
+    }
+}
+
+impl<T: Send> Managed for SyncBoxingArena<T> {
+    fn trim_free(&self) -> usize {
+        self.try_lock().map_or(0, |mut arena| trim_free(&mut arena))
+    }
+}
+
+/// Register an arena under `name`, which need not be unique, for both trimming and dumping
+/// its stats. The registry does not keep the arena alive, and dropped arenas are forgotten.
+pub fn register<S: Managed + 'static>(name: impl Into<String>, arena: &Arc<S>) {
+    let source: Arc<dyn StatsSource> = arena.clone();
+    let managed: Arc<dyn Managed> = arena.clone();
+    diagnostics::push(Entry {
+        name: name.into(),
+        source: Arc::downgrade(&source),
+        managed: Some(Arc::downgrade(&managed)),
+    });
+}
+
+/// Return the live arenas registered with `register`, with their names, in the order they
+/// were registered. The registry is not locked while the arenas are used.
+pub fn arenas() -> Vec<(String, Arc<dyn Managed>)> {
+    let registry = diagnostics::REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let managed = registry.iter().filter_map(|e| Some((e, e.managed.as_ref()?.upgrade()?)));
+    managed.map(|(e, arena)| (e.name.clone(), arena)).collect()
+}
+
+/// Release the free boxes of all arenas registered with `register`, returning the number of
+/// bytes released.
+pub fn trim_all() -> usize {
+    arenas().iter().map(|(_, arena)| arena.trim_free()).sum()
+}
+
+/// Return the stats of each registered arena with its name, including those registered with
+/// `diagnostics::register`, `None` for busy ones.
+pub fn stats_all() -> Vec<(String, Option<ArenaStats>)> {
+    let sources: Vec<_> = {
+        let registry = diagnostics::REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        let live = registry.iter().filter_map(|e| Some((e.name.clone(), e.source.upgrade()?)));
+        live.collect()
+    };
+    let sources = sources.into_iter();
+    sources.map(|(name, source)| (name, source.stats())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        let arena = Arc::new(Mutex::new(BoxingArena::<[u8; 64]>::with_capacity(4)));
+        register("registry-test", &arena);
+        let shared = Arc::new(SyncBoxingArena::<u64>::with_capacity(2));
+        register("registry-test-shared", &shared);
+        let dumped = Arc::new(Mutex::new(BoxingArena::<u8>::with_capacity(1)));
+        diagnostics::register("registry-test-dumped", &dumped);
+        let ours = |name: &String| name.starts_with("registry-test");
+
+        let stats: Vec<_> = stats_all().into_iter().filter(|(name, _)| ours(name)).collect();
+        let capacities: Vec<_> = stats.iter().map(|(_, s)| s.map(|s| s.capacity)).collect();
+        assert_eq!(capacities, [Some(4), Some(2), Some(1)]);
+        let mut out = vec![];
+        diagnostics::dump_stats(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("registry-test-shared: capacity=2"));
+
+        // Locked arenas are left alone instead of blocking.
+        let guard = arena.lock().unwrap();
+        let shared_guard = shared.try_lock().unwrap();
+        assert_eq!(arena.trim_free() + shared.trim_free(), 0);
+        drop(shared_guard);
+        assert!(trim_all() >= 2 * 8);
+        assert_eq!((guard.capacity(), shared.capacity()), (4, 0));
+        drop(guard);
+        assert!(trim_all() >= 4 * 64);
+        assert_eq!((arena.lock().unwrap().capacity(), dumped.lock().unwrap().capacity()), (0, 1));
+
+        drop(arena);
+        let names: Vec<_> = arenas().into_iter().map(|(name, _)| name).filter(ours).collect();
+        assert_eq!(names, ["registry-test-shared"]);
+    }
+}
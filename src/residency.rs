@@ -0,0 +1,92 @@
+//! Finding out how much of an arena's free memory is actually resident, on unix.
+
+use std::collections::BTreeSet;
+use std::io;
+
+use crate::{provenance, BoxingArena};
+
+/// How many of the pages holding an arena's free boxes are resident in RAM, as returned by
+/// `BoxingArena::residency`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Residency {
+    /// The size of a page, in bytes.
+    pub page_size: usize,
+    /// Number of distinct pages that free boxes occupy.
+    pub pages: usize,
+    /// How many of these pages are resident.
+    pub resident_pages: usize,
+}
+
+impl Residency {
+    /// Return the number of pages that are swapped out, or were never touched.
+    pub fn non_resident_pages(&self) -> usize {
+        self.pages - self.resident_pages
+    }
+
+    /// Return the number of bytes of RAM that the resident pages take.
+    pub fn resident_bytes(&self) -> usize {
+        self.resident_pages * self.page_size
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Report how many of the pages occupied by the free boxes are resident, using `mincore`.
+    /// A page only partly covered by free boxes counts in full, even if it also holds values
+    /// in use.
+    pub fn residency(&self) -> io::Result<Residency> {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let size = std::mem::size_of::<T>().max(1);
+        let pages: BTreeSet<usize> = self
+            .items
+            .iter()
+            .map(|&p| provenance::addr(p))
+            .flat_map(|a| a / page_size..=(a + size - 1) / page_size)
+            .collect();
+
+        let mut residency = Residency {
+            page_size,
+            pages: pages.len(),
+            resident_pages: 0,
+        };
+
+        // Query each run of consecutive pages with a single call.
+        let mut pages = pages.into_iter().peekable();
+        let mut vec = vec![];
+        while let Some(first) = pages.next() {
+            let mut count = 1;
+            while pages.peek() == Some(&(first + count)) {
+                pages.next();
+                count += 1;
+            }
+
+            vec.resize(count, 0u8);
+            let addr = provenance::without_provenance::<libc::c_void>(first * page_size);
+            if unsafe { libc::mincore(addr, count * page_size, vec.as_mut_ptr() as *mut _) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            residency.resident_pages += vec.iter().filter(|&&v| v & 1 != 0).count();
+        }
+
+        Ok(residency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "mincore is not supported by Miri")]
+    fn residency() {
+        let ba = BoxingArena::<[u8; 1 << 16]>::new();
+        assert_eq!(ba.residency().unwrap().pages, 0);
+
+        let mut ba = BoxingArena::<[u8; 1 << 16]>::with_capacity(2);
+        ba.set_prefault(true);
+        ba.resize_capacity(4);
+        let residency = ba.residency().unwrap();
+        assert!(residency.pages >= 4 * (1 << 16) / residency.page_size);
+        assert!(residency.resident_pages <= residency.pages);
+        assert!(residency.resident_bytes() > 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! Giving boxes back to an arena from other threads.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::{BoxingArena, EmptySlot};
+
+/// The channel over which the senders of an arena give boxes back.
+pub(crate) struct Returns<T> {
+    sender: Sender<EmptySlot<T>>,
+    receiver: Receiver<EmptySlot<T>>,
+}
+
+/// A handle for giving boxes back to a `BoxingArena` from any thread, as returned by
+/// `BoxingArena::sender`. The arena gets the allocations back on `collect_returned`, or
+/// `maintain`. If the arena is gone, they are freed.
+pub struct ArenaSender<T> {
+    sender: Sender<EmptySlot<T>>,
+}
+
+impl<T> ArenaSender<T> {
+    /// Take the value out of the box, and send the allocation back to the arena.
+    pub fn unbox(&self, b: Box<T>) -> T {
+        let ptr = Box::into_raw(b);
+        let v = unsafe { std::ptr::read(ptr) };
+        self.send(ptr);
+        v
+    }
+
+    /// Drop the value in place, on this thread, and send the allocation back to the arena.
+    pub fn recycle(&self, b: Box<T>) {
+        let ptr = Box::into_raw(b);
+        // Freed by the slot if dropping the value panics.
+        let slot = EmptySlot::new(ptr);
+        unsafe { std::ptr::drop_in_place(ptr) };
+        // Dropped, and freed, if the arena is gone.
+        let _ = self.sender.send(slot);
+    }
+
+    fn send(&self, ptr: *mut T) {
+        let _ = self.sender.send(EmptySlot::new(ptr));
+    }
+}
+
+impl<T> Clone for ArenaSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T> BoxingArena<T> {
+    /// Return a handle through which other threads can give boxes back to this arena, for
+    /// example in a pipeline where one thread allocates messages and another consumes them.
+    pub fn sender(&mut self) -> ArenaSender<T> {
+        let returns = self.returns.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            Returns { sender, receiver }
+        });
+        ArenaSender {
+            sender: returns.sender.clone(),
+        }
+    }
+
+    /// Keep the allocations given back through the senders of this arena since the last
+    /// call, as far as the maximum capacity and memory budget allow, and return how many
+    /// were given back.
+    #[track_caller]
+    pub fn collect_returned(&mut self) -> usize {
+        let slots: Vec<_> = match &self.returns {
+            Some(returns) => returns.receiver.try_iter().collect(),
+            None => return 0,
+        };
+        let count = slots.len();
+        let caller = std::panic::Location::caller();
+        for slot in slots {
+            unsafe { self.keep_slot(slot.into_raw(), caller) }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns() {
+        let mut ba = BoxingArena::new();
+        let sender = ba.sender();
+        let boxes: Vec<_> = (0..4).map(|i| ba.rebox(vec![i; 8])).collect();
+        assert_eq!(ba.collect_returned(), 0);
+
+        std::thread::spawn(move || {
+            let mut boxes = boxes.into_iter();
+            assert_eq!(sender.unbox(boxes.next().unwrap()), [0; 8]);
+            for b in boxes {
+                sender.recycle(b);
+            }
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(ba.collect_returned(), 4);
+        assert_eq!(ba.capacity(), 4);
+
+        // Freed when the arena is gone.
+        let b = ba.rebox(vec![]);
+        let sender = ba.sender();
+        drop(ba);
+        sender.recycle(b);
+    }
+}
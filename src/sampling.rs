@@ -0,0 +1,80 @@
+//! Calling back on a sample of the boxes handed out.
+
+use alloc::boxed::Box;
+use core::panic::Location;
+
+use crate::BoxingArena;
+
+/// A box handed out by an arena, as passed to the callback set by
+/// `BoxingArena::set_sampler`.
+#[derive(Clone, Copy, Debug)]
+pub struct ReboxSample {
+    /// Whether the box was a free one, rather than a new allocation.
+    pub hit: bool,
+    /// The location of the call that asked for the box.
+    pub caller: &'static Location<'static>,
+}
+
+pub(crate) struct Sampler {
+    every: u64,
+    countdown: u64,
+    callback: Box<dyn FnMut(ReboxSample) + Send>,
+}
+
+impl<T> BoxingArena<T> {
+    /// Call `f` for every `every`th box handed out by the arena, to attribute its traffic at a
+    /// low cost. A period of 0 is the same as 1. A forked arena does not keep the sampler.
+    pub fn set_sampler<F>(&mut self, every: u64, f: F)
+    where
+        F: FnMut(ReboxSample) + Send + 'static,
+    {
+        let every = every.max(1);
+        self.sampler = Some(Sampler {
+            every,
+            countdown: every,
+            callback: Box::new(f),
+        });
+    }
+
+    /// Stop calling the callback set by `set_sampler`.
+    pub fn clear_sampler(&mut self) {
+        self.sampler = None;
+    }
+
+    /// Count a box handed out at `caller`, calling the sampler if it is due.
+    pub(crate) fn sample(&mut self, hit: bool, caller: &'static Location<'static>) {
+        if let Some(sampler) = &mut self.sampler {
+            sampler.countdown -= 1;
+            if sampler.countdown == 0 {
+                sampler.countdown = sampler.every;
+                (sampler.callback)(ReboxSample { hit, caller });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn sampler() {
+        let samples = Arc::new(Mutex::new(vec![]));
+        let mut ba = BoxingArena::with_capacity(2);
+        let s = samples.clone();
+        ba.set_sampler(3, move |sample| s.lock().unwrap().push(sample));
+
+        let line = line!() + 1;
+        let boxes: Vec<_> = (0..6).map(|i| ba.rebox(i)).collect();
+        let samples_taken = samples.lock().unwrap().clone();
+        assert_eq!(samples_taken.len(), 2);
+        assert!(!samples_taken[0].hit);
+        assert_eq!(samples_taken[0].caller.line(), line);
+
+        ba.clear_sampler();
+        drop(boxes);
+        ba.rebox(0);
+        assert_eq!(samples.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,149 @@
+//! Limiting the boxes out with a tokio semaphore, enabled by the `semaphore` feature.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use crate::BoxingArena;
+
+/// A shared arena where each box handed out holds a permit of a `tokio::sync::Semaphore`,
+/// released when the box is given back. The semaphore can be shared with other parts of an
+/// admission control scheme.
+///
+/// Clones refer to the same arena and semaphore.
+pub struct SemaphorePool<T> {
+    arena: Arc<Mutex<BoxingArena<T>>>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl<T> SemaphorePool<T> {
+    /// Hand out boxes from `arena`, each holding a permit of `semaphore`.
+    pub fn new(arena: BoxingArena<T>, semaphore: Arc<Semaphore>) -> Self {
+        Self {
+            arena: Arc::new(Mutex::new(arena)),
+            semaphore,
+        }
+    }
+
+    /// Return the semaphore.
+    pub fn semaphore(&self) -> &Arc<Semaphore> {
+        &self.semaphore
+    }
+
+    /// Box a value once a permit is available. Fails if the semaphore is closed.
+    pub async fn rebox(&self, v: T) -> Result<PermitBox<T>, AcquireError> {
+        let permit = self.semaphore.clone().acquire_owned().await?;
+        Ok(self.hand_out(v, permit))
+    }
+
+    /// Box a value if a permit is available right away.
+    #[track_caller]
+    pub fn try_rebox(&self, v: T) -> Result<PermitBox<T>, (T, TryAcquireError)> {
+        match self.semaphore.clone().try_acquire_owned() {
+            Ok(permit) => Ok(self.hand_out(v, permit)),
+            Err(e) => Err((v, e)),
+        }
+    }
+
+    #[track_caller]
+    fn hand_out(&self, v: T, permit: OwnedSemaphorePermit) -> PermitBox<T> {
+        let b = self.arena.lock().unwrap_or_else(|e| e.into_inner()).rebox(v);
+        PermitBox {
+            value: Some(b),
+            arena: self.arena.clone(),
+            _permit: permit,
+        }
+    }
+
+    /// Run `f` on the underlying arena.
+    pub fn with_arena<R>(&self, f: impl FnOnce(&mut BoxingArena<T>) -> R) -> R {
+        f(&mut self.arena.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl<T> Clone for SemaphorePool<T> {
+    fn clone(&self) -> Self {
+        Self {
+            arena: self.arena.clone(),
+            semaphore: self.semaphore.clone(),
+        }
+    }
+}
+
+/// A box handed out by a `SemaphorePool`, holding a permit. Dropping it gives the box back
+/// to the arena and releases the permit.
+pub struct PermitBox<T> {
+    value: Option<Box<T>>,
+    arena: Arc<Mutex<BoxingArena<T>>>,
+    // Dropped after the box is given back.
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<T> PermitBox<T> {
+    /// Take the value out, giving the box back to the arena and releasing the permit.
+    pub fn into_inner(mut self) -> T {
+        let b = self.value.take().unwrap();
+        self.arena.lock().unwrap_or_else(|e| e.into_inner()).unbox(b)
+    }
+}
+
+impl<T> Deref for PermitBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for PermitBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl_borrow!([T] PermitBox<T>, mut);
+impl_cmp!([T] PermitBox<T>);
+
+// The value is in a box, which stays in place until the `PermitBox` is dropped.
+#[cfg(feature = "stable-deref")]
+unsafe impl<T> stable_deref_trait::StableDeref for PermitBox<T> {}
+
+impl<T> Drop for PermitBox<T> {
+    fn drop(&mut self) {
+        if let Some(b) = self.value.take() {
+            let v = self.arena.lock().unwrap_or_else(|e| e.into_inner()).unbox(b);
+            // Outside of the lock.
+            drop(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn permits() {
+        let semaphore = Arc::new(Semaphore::new(2));
+        let pool = SemaphorePool::new(BoxingArena::new(), semaphore.clone());
+
+        let a = pool.rebox(1u32).await.unwrap();
+        let b = pool.try_rebox(2).ok().unwrap();
+        assert_eq!(semaphore.available_permits(), 0);
+        assert!(pool.try_rebox(3).is_err());
+
+        // Permits taken elsewhere count against the pool too.
+        drop(a);
+        let other = semaphore.clone().try_acquire_owned().unwrap();
+        assert!(pool.try_rebox(3).is_err());
+        drop(other);
+
+        assert_eq!(b.into_inner(), 2);
+        assert_eq!(semaphore.available_permits(), 2);
+        assert_eq!(pool.with_arena(|arena| arena.capacity()), 2);
+
+        semaphore.close();
+        assert!(pool.rebox(4).await.is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! The box types serialize as their value, like `Box<T>` does, so that they can replace
+//! `Box` fields under `#[derive(Serialize)]`. Deserializing into an arena's boxes goes
+//! through `ReboxSeed`, or `PooledBoxSeed` for deserializing in place.
+
+use std::marker::PhantomData;
+
+use serde::de::{DeserializeSeed, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{ArenaBox, BoundedBox, BoxingArena, LeaseArc, PooledBox, PooledCow, SmallPooledBox};
+
+macro_rules! impl_serialize {
+    ([$($gen:tt)*] $ty:ty) => {
+        impl<$($gen)*> Serialize for $ty
+        where
+            T: Serialize,
+        {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                (**self).serialize(serializer)
+            }
+        }
+    };
+}
+
+impl_serialize!(['a, T] ArenaBox<'a, T>);
+impl_serialize!([T] BoundedBox<T>);
+impl_serialize!([T] LeaseArc<T>);
+impl_serialize!([T] PooledBox<T>);
+impl_serialize!([T] PooledCow<T>);
+impl_serialize!([T, const N: usize] SmallPooledBox<T, N>);
+#[cfg(feature = "ffi")]
+impl_serialize!([T: 'static] crate::FfiBox<T>);
+#[cfg(feature = "semaphore")]
+impl_serialize!([T] crate::PermitBox<T>);
+
+/// Deserializes a `T` into a box from the arena, reusing a free box if there is one. Use it
+/// with `DeserializeSeed::deserialize`, or from the `Visitor` of a containing type.
+pub struct ReboxSeed<'a, T> {
+    arena: &'a mut BoxingArena<T>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> ReboxSeed<'a, T> {
+    /// Deserialize into a box from `arena`.
+    pub fn new(arena: &'a mut BoxingArena<T>) -> Self {
+        Self {
+            arena,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'de, T: Deserialize<'de>> DeserializeSeed<'de> for ReboxSeed<'a, T> {
+    type Value = Box<T>;
+
+    #[track_caller]
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<T>, D::Error> {
+        let v = T::deserialize(deserializer)?;
+        Ok(self.arena.rebox(v))
+    }
+}
+
+/// Deserializes a `T` in place, into a box from the arena holding `T::default()`, so that
+/// large values are not built on the stack and then moved into the box. This goes through
+/// `Deserialize::deserialize_in_place`, which types deriving `Deserialize` implement field by
+/// field with the `deserialize_in_place` feature of `serde_derive`; for other types it
+/// amounts to `ReboxSeed`. On error, the box goes back to the arena.
+pub struct PooledBoxSeed<'a, T> {
+    arena: &'a mut BoxingArena<T>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> PooledBoxSeed<'a, T> {
+    /// Deserialize into a box from `arena`.
+    pub fn new(arena: &'a mut BoxingArena<T>) -> Self {
+        Self {
+            arena,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'de, T: Deserialize<'de> + Default> DeserializeSeed<'de> for PooledBoxSeed<'a, T> {
+    type Value = Box<T>;
+
+    #[track_caller]
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Box<T>, D::Error> {
+        let mut b = self.arena.rebox(T::default());
+        match T::deserialize_in_place(deserializer, &mut b) {
+            Ok(()) => Ok(b),
+            Err(e) => {
+                self.arena.recycle(b);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn serialize() {
+        let arena = Arc::new(Mutex::new(BoxingArena::new()));
+        let lease = LeaseArc::new_in(vec![1u8, 2], &arena);
+        assert_eq!(serde_json::to_string(&lease).unwrap(), "[1,2]");
+
+        let mut arena = BoxingArena::new();
+        let s = SmallPooledBox::<_, 8>::new_in(String::from("abc"), &mut arena);
+        assert_eq!(serde_json::to_string(&s).unwrap(), "\"abc\"");
+    }
+
+    #[test]
+    fn rebox_seed() {
+        let mut arena = BoxingArena::with_capacity(1);
+        let mut de = serde_json::Deserializer::from_str("7");
+        let b: Box<u64> = ReboxSeed::new(&mut arena).deserialize(&mut de).unwrap();
+        assert_eq!(*b, 7);
+        assert_eq!(arena.capacity(), 0);
+    }
+
+    #[test]
+    fn pooled_box_seed() {
+        let mut arena = BoxingArena::<Vec<u32>>::new();
+        let mut de = serde_json::Deserializer::from_str("[1, 2] [3, \"x\"]");
+        let b = PooledBoxSeed::new(&mut arena).deserialize(&mut de).unwrap();
+        assert_eq!(*b, [1, 2]);
+        arena.unbox(b);
+
+        assert!(PooledBoxSeed::new(&mut arena).deserialize(&mut de).is_err());
+        assert_eq!(arena.capacity(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+//! Managing arenas of many different types behind one handle.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::any::{Any, TypeId};
+
+use crate::{ArenaStats, BoxingArena};
+
+/// The type-erased operations `ArenaSet` performs on all of its arenas.
+trait ErasedArena {
+    fn type_name(&self) -> &'static str;
+    fn trim(&mut self, size: usize);
+    fn maintain(&mut self);
+    fn capacity_bytes(&self) -> usize;
+    fn stats(&self) -> ArenaStats;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> ErasedArena for BoxingArena<T> {
+    fn type_name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+
+    fn trim(&mut self, size: usize) {
+        BoxingArena::trim(self, size)
+    }
+
+    fn maintain(&mut self) {
+        BoxingArena::maintain(self)
+    }
+
+    fn capacity_bytes(&self) -> usize {
+        BoxingArena::capacity_bytes(self)
+    }
+
+    fn stats(&self) -> ArenaStats {
+        BoxingArena::stats(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A collection of `BoxingArena`s of different types, at most one per type.
+#[derive(Default)]
+pub struct ArenaSet {
+    arenas: BTreeMap<TypeId, Box<dyn ErasedArena>>,
+}
+
+impl ArenaSet {
+    /// Create an empty set. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self {
+            arenas: BTreeMap::new(),
+        }
+    }
+
+    /// Return the arena for `T`, if there is one.
+    pub fn get<T: 'static>(&self) -> Option<&BoxingArena<T>> {
+        self.arenas
+            .get(&TypeId::of::<T>())
+            .and_then(|a| a.as_any().downcast_ref())
+    }
+
+    /// Return the arena for `T`, creating an empty one if needed.
+    pub fn arena<T: 'static>(&mut self) -> &mut BoxingArena<T> {
+        self.arenas
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(BoxingArena::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    /// Box a value with the arena for `T`, creating it if needed.
+    #[track_caller]
+    pub fn rebox<T: 'static>(&mut self, v: T) -> Box<T> {
+        self.arena::<T>().rebox(v)
+    }
+
+    /// Unbox the value and keep the allocation in the arena for `T`, creating it if needed.
+    #[track_caller]
+    pub fn unbox<T: 'static>(&mut self, b: Box<T>) -> T {
+        self.arena::<T>().unbox(b)
+    }
+
+    /// Put an arena in the set, returning the one it replaces.
+    pub fn insert<T: 'static>(&mut self, arena: BoxingArena<T>) -> Option<BoxingArena<T>> {
+        let old = self.remove::<T>();
+        self.arenas.insert(TypeId::of::<T>(), Box::new(arena));
+        old
+    }
+
+    /// Take the arena for `T` out of the set.
+    pub fn remove<T: 'static>(&mut self) -> Option<BoxingArena<T>> {
+        let mut erased = self.arenas.remove(&TypeId::of::<T>())?;
+        let arena = erased.as_any_mut().downcast_mut::<BoxingArena<T>>().unwrap();
+        Some(core::mem::take(arena))
+    }
+
+    /// Return the number of arenas in the set.
+    pub fn len(&self) -> usize {
+        self.arenas.len()
+    }
+
+    /// Return whether the set holds no arenas.
+    pub fn is_empty(&self) -> bool {
+        self.arenas.is_empty()
+    }
+
+    /// Trim every arena in the set to at most `size` free boxes.
+    pub fn trim_all(&mut self, size: usize) {
+        for arena in self.arenas.values_mut() {
+            arena.trim(size);
+        }
+    }
+
+    /// Run `BoxingArena::maintain` on every arena in the set.
+    pub fn maintain(&mut self) {
+        for arena in self.arenas.values_mut() {
+            arena.maintain();
+        }
+    }
+
+    /// Return the total number of bytes held by free boxes across the set.
+    pub fn capacity_bytes(&self) -> usize {
+        self.arenas.values().map(|a| a.capacity_bytes()).sum()
+    }
+
+    /// Iterate over the type name and counters of each arena in the set.
+    pub fn stats(&self) -> impl Iterator<Item = (&'static str, ArenaStats)> + '_ {
+        self.arenas.values().map(|a| (a.type_name(), a.stats()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set() {
+        let mut set = ArenaSet::new();
+        set.arena::<u32>().resize_capacity(2);
+        set.arena::<[u8; 100]>().resize_capacity(1);
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.capacity_bytes(), 2 * 4 + 100);
+
+        let b = set.arena::<u32>().rebox(5);
+        assert_eq!(set.get::<u32>().unwrap().capacity(), 1);
+        assert!(set.get::<u64>().is_none());
+        drop(b);
+
+        let hits: u64 = set.stats().map(|(_, s)| s.hits).sum();
+        assert_eq!(hits, 1);
+
+        set.trim_all(0);
+        set.maintain();
+        assert_eq!(set.capacity_bytes(), 0);
+
+        let old = set.insert(BoxingArena::<u32>::with_capacity(3)).unwrap();
+        assert_eq!(old.stats().hits, 1);
+        assert_eq!(set.remove::<u32>().unwrap().capacity(), 3);
+        assert_eq!(set.len(), 1);
+
+        let s = set.rebox(String::from("message"));
+        assert_eq!(set.unbox(s), "message");
+        assert_eq!(set.get::<String>().unwrap().capacity(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+//! An arena split into shards, for sharing between many threads.
+
+use crate::{sync, Pool, SharedPool, SyncBoxingArena};
+
+/// Spread on its own cache lines, so that threads using neighbouring shards do not contend.
+#[repr(align(128))]
+struct Shard<T>(SyncBoxingArena<T>);
+
+/// An arena split into shards, each behind its own lock, for threads to share in an `Arc`
+/// when the single lock of a `SyncBoxingArena` becomes the bottleneck. Each thread uses one
+/// shard for its boxes, and when that one has no free boxes, takes half of those of another
+/// shard that has some, before allocating.
+pub struct ShardedBoxingArena<T> {
+    shards: Box<[Shard<T>]>,
+}
+
+impl<T> ShardedBoxingArena<T> {
+    /// Create an empty arena with a shard for each thread the system can run in parallel.
+    pub fn new() -> Self {
+        let shards = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(shards)
+    }
+
+    /// Create an empty arena with the given number of shards, at least one.
+    pub fn with_shards(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Shard(SyncBoxingArena::new())).collect(),
+        }
+    }
+
+    /// Return the number of shards.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn local_index(&self) -> usize {
+        // Threads are assigned shards in turn.
+        sync::thread_index() % self.shards.len()
+    }
+
+    /// Return the shard of the calling thread, for the operations that are not provided on
+    /// the sharded arena.
+    pub fn local(&self) -> &SyncBoxingArena<T> {
+        &self.shards[self.local_index()].0
+    }
+
+    /// Move half of the free boxes of the first other shard that has some into the shard of
+    /// the calling thread. Only one shard is locked at a time.
+    fn steal(&self, local: usize) {
+        let n = self.shards.len();
+        for victim in (1..n).map(|i| &self.shards[(local + i) % n].0) {
+            let stolen = {
+                let mut victim = victim.lock();
+                let half = victim.capacity().div_ceil(2);
+                victim.split_off(half)
+            };
+            if stolen.capacity() > 0 {
+                self.shards[local].0.lock().merge(stolen);
+                return;
+            }
+        }
+    }
+
+    /// Box a value, reusing a free box of the local shard, or one stolen from another shard.
+    #[track_caller]
+    pub fn rebox(&self, v: T) -> Box<T> {
+        let local = self.local_index();
+        let v = match self.shards[local].0.try_rebox_value(v) {
+            Ok(b) => return b,
+            Err(v) => v,
+        };
+        self.steal(local);
+        self.shards[local].0.rebox(v)
+    }
+
+    /// Unbox the value and keep the allocation in the local shard.
+    #[track_caller]
+    pub fn unbox(&self, b: Box<T>) -> T {
+        self.local().unbox(b)
+    }
+
+    /// Return the number of free boxes of all shards.
+    pub fn capacity(&self) -> usize {
+        self.shards.iter().map(|shard| shard.0.capacity()).sum()
+    }
+
+    /// Trims the capacity of each shard to its share of the given size.
+    pub fn trim(&self, size: usize) {
+        let share = size.div_ceil(self.shards.len());
+        for shard in self.shards.iter() {
+            shard.0.trim(share);
+        }
+    }
+}
+
+impl<T> Default for ShardedBoxingArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Pool<T> for ShardedBoxingArena<T> {
+    #[track_caller]
+    fn rebox(&mut self, v: T) -> Box<T> {
+        ShardedBoxingArena::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&mut self, b: Box<T>) -> T {
+        ShardedBoxingArena::unbox(self, b)
+    }
+
+    fn trim(&mut self, size: usize) {
+        ShardedBoxingArena::trim(self, size)
+    }
+
+    fn capacity(&self) -> usize {
+        ShardedBoxingArena::capacity(self)
+    }
+}
+
+impl<T> SharedPool<T> for ShardedBoxingArena<T> {
+    #[track_caller]
+    fn rebox(&self, v: T) -> Box<T> {
+        ShardedBoxingArena::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&self, b: Box<T>) -> T {
+        ShardedBoxingArena::unbox(self, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "loom"))]
+    #[test]
+    fn sharded() {
+        use std::sync::Arc;
+
+        let arena = Arc::new(ShardedBoxingArena::with_shards(4));
+        arena.local().lock().reserve(8);
+
+        // Other threads steal the free boxes warmed up by this one.
+        let workers: Vec<_> = (0..3u32)
+            .map(|i| {
+                let arena = arena.clone();
+                std::thread::spawn(move || {
+                    let b = arena.rebox(vec![i; 16]);
+                    arena.unbox(b).len()
+                })
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), 16);
+        }
+        // No box was allocated.
+        assert_eq!(arena.capacity(), 8);
+        arena.trim(4);
+        assert!(arena.capacity() <= 4);
+    }
+
+    #[cfg(feature = "loom")]
+    #[test]
+    fn loom() {
+        use loom::sync::Arc;
+
+        loom::model(|| {
+            let arena = Arc::new(ShardedBoxingArena::with_shards(2));
+            arena.unbox(Box::new(0u64));
+            let thread = {
+                let arena = arena.clone();
+                loom::thread::spawn(move || {
+                    let b = arena.rebox(1);
+                    assert_eq!(arena.unbox(b), 1);
+                })
+            };
+            let b = arena.rebox(2);
+            assert_eq!(arena.unbox(b), 2);
+            thread.join().unwrap();
+            assert!((1..=2).contains(&arena.capacity()));
+        });
+    }
+}
@@ -0,0 +1,182 @@
+//! An arena for boxed slices of varying lengths.
+
+use alloc::alloc::Layout;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+/// An arena that keeps the allocations of `Box<[T]>`, in buckets by length, and reuses them
+/// for slices of the same length. This suits variable-length payloads of a few recurring
+/// sizes. Empty slices, and slices of zero-sized types, do not allocate and are not kept.
+pub struct SliceArena<T> {
+    buckets: BTreeMap<usize, Vec<*mut T>>,
+    _marker: PhantomData<T>,
+}
+
+// The arena only holds allocations without values.
+unsafe impl<T: Send> Send for SliceArena<T> {}
+
+/// Slice elements written so far. If dropped, for example while unwinding, they are dropped
+/// and the allocation is freed.
+struct Filling<T> {
+    ptr: *mut T,
+    len: usize,
+    written: usize,
+}
+
+impl<T> Drop for Filling<T> {
+    fn drop(&mut self) {
+        unsafe {
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(self.ptr, self.written));
+            alloc::alloc::dealloc(self.ptr as *mut u8, Layout::array::<T>(self.len).unwrap());
+        }
+    }
+}
+
+impl<T> SliceArena<T> {
+    /// Create an empty arena. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self {
+            buckets: BTreeMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn keeps(len: usize) -> bool {
+        len != 0 && core::mem::size_of::<T>() != 0
+    }
+
+    /// Box the items of `iter` as a slice, reusing a free allocation of the same length if
+    /// there is one.
+    ///
+    /// # Panics
+    ///
+    /// If the iterator yields fewer items than its reported length. Extra items are ignored.
+    pub fn rebox_slice<I>(&mut self, iter: I) -> Box<[T]>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        let len = iter.len();
+        if !Self::keeps(len) {
+            return iter.take(len).collect();
+        }
+
+        let ptr = match self.buckets.get_mut(&len).and_then(Vec::pop) {
+            Some(ptr) => ptr,
+            None => {
+                let layout = Layout::array::<T>(len).expect("slice is too large");
+                let ptr = unsafe { alloc::alloc::alloc(layout) } as *mut T;
+                if ptr.is_null() {
+                    alloc::alloc::handle_alloc_error(layout);
+                }
+                ptr
+            }
+        };
+
+        let mut filling = Filling { ptr, len, written: 0 };
+        for item in iter.take(len) {
+            unsafe { filling.ptr.add(filling.written).write(item) };
+            filling.written += 1;
+        }
+        assert_eq!(filling.written, len, "iterator yielded fewer items than its length");
+        core::mem::forget(filling);
+        unsafe { Box::from_raw(core::ptr::slice_from_raw_parts_mut(ptr, len)) }
+    }
+
+    /// Drop the items of the slice in place, and keep the allocation for slices of the same
+    /// length.
+    pub fn unbox_slice(&mut self, b: Box<[T]>) {
+        let len = b.len();
+        if !Self::keeps(len) {
+            return;
+        }
+        let ptr = Box::into_raw(b) as *mut T;
+        // Frees the allocation if dropping an item panics.
+        let filling = Filling { ptr, len, written: len };
+        unsafe { core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(ptr, len)) };
+        core::mem::forget(filling);
+        self.buckets.entry(len).or_default().push(ptr);
+    }
+
+    /// Return the number of free slices, of all lengths.
+    pub fn capacity(&self) -> usize {
+        self.buckets.values().map(Vec::len).sum()
+    }
+
+    /// Return the number of free slices of the given length.
+    pub fn capacity_for(&self, len: usize) -> usize {
+        self.buckets.get(&len).map_or(0, Vec::len)
+    }
+
+    /// Trims the free slices of each length to the given number if there are more.
+    pub fn trim(&mut self, size: usize) {
+        for (&len, slots) in self.buckets.iter_mut() {
+            let layout = Layout::array::<T>(len).unwrap();
+            for ptr in slots.drain(size.min(slots.len())..) {
+                unsafe { alloc::alloc::dealloc(ptr as *mut u8, layout) }
+            }
+        }
+        self.buckets.retain(|_, slots| !slots.is_empty());
+    }
+}
+
+impl<T> Default for SliceArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for SliceArena<T> {
+    fn drop(&mut self) {
+        self.trim(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_arena() {
+        let mut arena = SliceArena::new();
+        let a = arena.rebox_slice(vec![1u32, 2, 3]);
+        let b = arena.rebox_slice((0..5).map(|i| i * 2));
+        assert_eq!(*a, [1, 2, 3]);
+        assert_eq!(b[4], 8);
+
+        let addr = a.as_ptr();
+        arena.unbox_slice(a);
+        arena.unbox_slice(b);
+        arena.unbox_slice(Box::new([]));
+        assert_eq!((arena.capacity(), arena.capacity_for(3)), (2, 1));
+
+        let c = arena.rebox_slice([7, 8, 9]);
+        assert_eq!(c.as_ptr(), addr);
+        arena.trim(0);
+        assert_eq!(arena.capacity(), 0);
+    }
+
+    #[test]
+    fn short_iterator() {
+        struct Liar(u32);
+        impl Iterator for Liar {
+            type Item = String;
+            fn next(&mut self) -> Option<String> {
+                self.0 = self.0.checked_sub(1)?;
+                Some(self.0.to_string())
+            }
+        }
+        impl ExactSizeIterator for Liar {
+            fn len(&self) -> usize {
+                self.0 as usize + 1
+            }
+        }
+
+        let mut arena = SliceArena::new();
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| arena.rebox_slice(Liar(2))));
+        assert!(r.is_err());
+    }
+}
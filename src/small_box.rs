@@ -0,0 +1,150 @@
+//! A box that keeps small values inline and only uses the arena for large ones.
+
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+
+use crate::BoxingArena;
+
+/// Storage for values of up to `N` bytes.
+#[repr(C, align(16))]
+struct Inline<const N: usize>([MaybeUninit<u8>; N]);
+
+enum Storage<T, const N: usize> {
+    Inline(Inline<N>),
+    Pooled(Box<T>),
+}
+
+/// A pointer-like container that stores `T` inline when it fits in `N` bytes (and needs at
+/// most 16-byte alignment), and in a box from a `BoxingArena` otherwise.
+///
+/// The choice only depends on `T` and `N`, so generic code can use it for all of its payload
+/// types and only pay for the arena with the large ones. Use `into_inner` to give the box
+/// back to the arena; a pooled `SmallPooledBox` that is simply dropped deallocates normally.
+pub struct SmallPooledBox<T, const N: usize> {
+    storage: Storage<T, N>,
+    _marker: PhantomData<T>,
+}
+
+impl<T, const N: usize> SmallPooledBox<T, N> {
+    const INLINE: bool =
+        mem::size_of::<T>() <= N && mem::align_of::<T>() <= mem::align_of::<Inline<N>>();
+
+    /// Store `v`, taking a box from `arena` if it does not fit inline.
+    #[track_caller]
+    pub fn new_in(v: T, arena: &mut BoxingArena<T>) -> Self {
+        let storage = if Self::INLINE {
+            let mut inline = Inline([MaybeUninit::uninit(); N]);
+            unsafe { core::ptr::write(inline.0.as_mut_ptr() as *mut T, v) };
+            Storage::Inline(inline)
+        } else {
+            Storage::Pooled(arena.rebox(v))
+        };
+
+        Self {
+            storage,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Return whether the value is stored inline.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.storage, Storage::Inline(_))
+    }
+
+    /// Take the value out, giving the box back to `arena` if there was one.
+    #[track_caller]
+    pub fn into_inner(self, arena: &mut BoxingArena<T>) -> T {
+        let this = mem::ManuallyDrop::new(self);
+        match unsafe { core::ptr::read(&this.storage) } {
+            Storage::Inline(inline) => unsafe { core::ptr::read(inline.0.as_ptr() as *const T) },
+            Storage::Pooled(b) => arena.unbox(b),
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for SmallPooledBox<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &self.storage {
+            Storage::Inline(inline) => unsafe { &*(inline.0.as_ptr() as *const T) },
+            Storage::Pooled(b) => b,
+        }
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallPooledBox<T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        match &mut self.storage {
+            Storage::Inline(inline) => unsafe { &mut *(inline.0.as_mut_ptr() as *mut T) },
+            Storage::Pooled(b) => b,
+        }
+    }
+}
+
+impl_borrow!([T, const N: usize] SmallPooledBox<T, N>, mut);
+impl_cmp!([T, const N: usize] SmallPooledBox<T, N>);
+
+impl<T, const N: usize> Drop for SmallPooledBox<T, N> {
+    fn drop(&mut self) {
+        if let Storage::Inline(inline) = &mut self.storage {
+            unsafe { core::ptr::drop_in_place(inline.0.as_mut_ptr() as *mut T) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_box() {
+        let mut small = BoxingArena::<u32>::new();
+        let mut s = SmallPooledBox::<_, 16>::new_in(1u32, &mut small);
+        assert!(s.is_inline());
+        *s += 1;
+        assert_eq!(s.into_inner(&mut small), 2);
+        assert_eq!(small.capacity(), 0);
+
+        let mut large = BoxingArena::<[u8; 64]>::new();
+        let l = SmallPooledBox::<_, 16>::new_in([7u8; 64], &mut large);
+        assert!(!l.is_inline());
+        assert_eq!(l[63], 7);
+        l.into_inner(&mut large);
+        assert_eq!(large.capacity(), 1);
+
+        // Inline values are dropped in place.
+        let rc = std::rc::Rc::new(());
+        let mut rcs = BoxingArena::new();
+        drop(SmallPooledBox::<_, 16>::new_in(rc.clone(), &mut rcs));
+        assert_eq!(std::rc::Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn borrow() {
+        fn len<S: AsRef<String>>(s: S) -> usize {
+            s.as_ref().len()
+        }
+
+        let mut arena = BoxingArena::new();
+        let mut s = SmallPooledBox::<_, 8>::new_in(String::from("abc"), &mut arena);
+        s.as_mut().push('d');
+        assert_eq!(len(&s), 4);
+        assert_eq!(len(s), 4);
+    }
+
+    #[test]
+    fn cmp() {
+        let mut arena = BoxingArena::new();
+        let mut set = std::collections::HashSet::new();
+        set.insert(SmallPooledBox::<_, 8>::new_in(2u64, &mut arena));
+        set.insert(SmallPooledBox::new_in(1, &mut arena));
+        assert!(set.contains(&SmallPooledBox::new_in(2, &mut arena)));
+
+        let mut sorted: Vec<_> = set.into_iter().collect();
+        sorted.sort();
+        assert_eq!(*sorted[0], 1);
+    }
+}
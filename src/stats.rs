@@ -0,0 +1,87 @@
+//! Counters kept by a `BoxingArena`, and the estimates derived from them.
+
+/// Running counters, updated on every arena operation.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Counters {
+    pub(crate) hits: u64,
+    pub(crate) misses: u64,
+    pub(crate) unboxes: u64,
+    pub(crate) returns: u64,
+}
+
+impl Counters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            hits: 0,
+            misses: 0,
+            unboxes: 0,
+            returns: 0,
+        }
+    }
+}
+
+/// A snapshot of an arena's counters, as returned by `BoxingArena::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Number of boxes handed out from an existing free allocation.
+    pub hits: u64,
+    /// Number of boxes that needed a fresh allocation.
+    pub misses: u64,
+    /// Number of boxes whose allocation was kept for reuse.
+    pub unboxes: u64,
+    /// Number of boxes given back to the arena, whether their allocation was kept, or freed
+    /// as over the maximum capacity, the memory budget or the capacity policy.
+    pub returns: u64,
+    /// Number of free boxes at the time of the snapshot.
+    pub capacity: usize,
+    /// The most free boxes the arena held at once, a hint for sizing `with_capacity`.
+    pub peak_capacity: usize,
+    /// Size in bytes of a single box allocation.
+    pub slot_size: usize,
+    /// Bytes taken by the free list bookkeeping itself.
+    pub metadata_bytes: usize,
+}
+
+impl ArenaStats {
+    /// Return the number of boxes handed out and not given back yet, as far as the counters
+    /// can tell. Boxes given to the arena that did not come from it are not told apart.
+    pub fn outstanding(&self) -> u64 {
+        (self.hits + self.misses).saturating_sub(self.returns)
+    }
+
+    /// Return the fraction of the boxes handed out that reused a free allocation, between 0
+    /// and 1, or 0 if no box was handed out yet.
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+
+    /// Estimate how much allocator work the arena has saved so far.
+    pub fn savings(&self) -> AllocationSavings {
+        let bytes_avoided = self.hits.saturating_mul(self.slot_size as u64);
+        let overhead_bytes = self.metadata_bytes as u64;
+
+        AllocationSavings {
+            // Each hit replaced a deallocation and a later allocation.
+            allocator_calls_avoided: self.hits.saturating_mul(2),
+            bytes_avoided,
+            overhead_bytes,
+            net_bytes: bytes_avoided as i64 - overhead_bytes as i64,
+        }
+    }
+}
+
+/// Estimate of the allocator traffic avoided by an arena, see `ArenaStats::savings`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocationSavings {
+    /// Number of `alloc` and `dealloc` calls that did not happen thanks to reuse.
+    pub allocator_calls_avoided: u64,
+    /// Bytes that did not have to be requested from the allocator.
+    pub bytes_avoided: u64,
+    /// Bytes spent on keeping the free list.
+    pub overhead_bytes: u64,
+    /// `bytes_avoided` minus `overhead_bytes`. Negative when the pool costs more than it saves.
+    pub net_bytes: i64,
+}
@@ -0,0 +1,100 @@
+//! An arena of strings, reusing their buffers.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{SliceArena, VecArena};
+
+/// An arena of empty `String`s that keep their capacity between uses, for code that formats
+/// many short-lived strings. It also keeps `Box<str>` allocations by length, for strings
+/// that are built once and then only read.
+#[derive(Default)]
+pub struct StringArena {
+    strings: VecArena<u8>,
+    boxed: SliceArena<u8>,
+}
+
+impl StringArena {
+    /// Create an empty arena. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return an empty string, reusing the buffer of a free one if there is one.
+    pub fn checkout(&mut self) -> String {
+        Self::empty(self.strings.checkout())
+    }
+
+    /// Return an empty string with a capacity of at least `min_capacity`, reusing the buffer
+    /// of a free one if there is one large enough.
+    pub fn checkout_with_capacity(&mut self, min_capacity: usize) -> String {
+        Self::empty(self.strings.checkout_with_capacity(min_capacity))
+    }
+
+    fn empty(bytes: Vec<u8>) -> String {
+        debug_assert!(bytes.is_empty());
+        // Empty, hence valid UTF-8.
+        unsafe { String::from_utf8_unchecked(bytes) }
+    }
+
+    /// Clear the string and keep its buffer for reuse.
+    pub fn checkin(&mut self, s: String) {
+        self.strings.checkin(s.into_bytes())
+    }
+
+    /// Copy `s` into a `Box<str>`, reusing a free one of the same length if there is one.
+    pub fn rebox_str(&mut self, s: &str) -> Box<str> {
+        let bytes = self.boxed.rebox_slice(s.bytes());
+        // A copy of a `str`.
+        unsafe { alloc::str::from_boxed_utf8_unchecked(bytes) }
+    }
+
+    /// Keep the allocation of a `Box<str>` for strings of the same length.
+    pub fn unbox_str(&mut self, s: Box<str>) {
+        self.boxed.unbox_slice(s.into_boxed_bytes())
+    }
+
+    /// Return the number of free strings and `Box<str>`s.
+    pub fn capacity(&self) -> usize {
+        self.strings.capacity() + self.boxed.capacity()
+    }
+
+    /// Trims the free strings of each capacity class, and the free `Box<str>`s of each
+    /// length, to the given number if there are more.
+    pub fn trim(&mut self, size: usize) {
+        self.strings.trim(size);
+        self.boxed.trim(size);
+    }
+
+    /// Set the capacity above which `checkin` drops strings instead of keeping them, see
+    /// `VecArena::set_max_retained_capacity`.
+    pub fn set_max_retained_capacity(&mut self, max: Option<usize>) {
+        self.strings.set_max_retained_capacity(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    #[test]
+    fn strings() {
+        let mut arena = StringArena::new();
+        let mut s = arena.checkout_with_capacity(32);
+        write!(s, "event-{}", 17).unwrap();
+        assert_eq!(s, "event-17");
+        let addr = s.as_ptr();
+        arena.checkin(s);
+        let s = arena.checkout();
+        assert_eq!((s.len(), s.as_ptr()), (0, addr));
+
+        let b = arena.rebox_str("name");
+        let addr = b.as_ptr();
+        arena.unbox_str(b);
+        let b = arena.rebox_str("nom!");
+        assert_eq!((&*b, b.as_ptr()), ("nom!", addr));
+        assert_eq!(arena.capacity(), 0);
+    }
+}
@@ -0,0 +1,88 @@
+//! The synchronization primitives of the arenas shared by threads, which are those of `loom`
+//! with the `loom` feature, so that their interleavings can be model checked.
+
+#[cfg(all(target_has_atomic = "64", not(feature = "loom")))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize};
+#[cfg(all(target_has_atomic = "64", feature = "loom"))]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize};
+
+#[cfg(feature = "std")]
+pub(crate) use mutex::{into_inner, lock, try_lock, Mutex};
+#[cfg(feature = "std")]
+pub(crate) use threads::thread_index;
+
+/// Numbering of the threads, in the order they first ask for their number.
+#[cfg(all(feature = "std", not(feature = "loom")))]
+mod threads {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+    std::thread_local! {
+        static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn thread_index() -> usize {
+        THREAD.with(|&thread| thread)
+    }
+}
+
+#[cfg(feature = "loom")]
+mod threads {
+    use loom::sync::atomic::{AtomicUsize, Ordering};
+
+    loom::lazy_static! {
+        static ref NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+    }
+
+    loom::thread_local! {
+        static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn thread_index() -> usize {
+        THREAD.with(|&thread| thread)
+    }
+}
+
+/// The mutex of `parking_lot`, with the `parking-lot` feature unless `loom` is used.
+#[cfg(all(feature = "parking-lot", not(feature = "loom")))]
+mod mutex {
+    pub(crate) use parking_lot::Mutex;
+
+    pub(crate) fn lock<T>(m: &Mutex<T>) -> impl core::ops::DerefMut<Target = T> + '_ {
+        m.lock()
+    }
+
+    pub(crate) fn try_lock<T>(m: &Mutex<T>) -> Option<impl core::ops::DerefMut<Target = T> + '_> {
+        m.try_lock()
+    }
+
+    pub(crate) fn into_inner<T>(m: Mutex<T>) -> T {
+        m.into_inner()
+    }
+}
+
+#[cfg(all(feature = "std", not(all(feature = "parking-lot", not(feature = "loom")))))]
+mod mutex {
+    #[cfg(feature = "loom")]
+    pub(crate) use loom::sync::Mutex;
+    #[cfg(not(feature = "loom"))]
+    pub(crate) use std::sync::Mutex;
+
+    /// A panic while the lock was held leaves the arena consistent, so poisoning is ignored.
+    pub(crate) fn lock<T>(m: &Mutex<T>) -> impl core::ops::DerefMut<Target = T> + '_ {
+        m.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn try_lock<T>(m: &Mutex<T>) -> Option<impl core::ops::DerefMut<Target = T> + '_> {
+        match m.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(std::sync::TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(std::sync::TryLockError::WouldBlock) => None,
+        }
+    }
+
+    pub(crate) fn into_inner<T>(m: Mutex<T>) -> T {
+        m.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,148 @@
+//! An arena behind a mutex, for sharing between threads.
+
+use std::ops::DerefMut;
+
+use crate::sync::{self, Mutex};
+use crate::{BoxingArena, Pool, SharedPool};
+
+/// A `BoxingArena` behind a mutex, with the same operations on `&self`, so that it can be
+/// shared by threads in an `Arc`. With the `parking-lot` feature, the mutex is the one of
+/// `parking_lot`. Values are dropped outside of the lock.
+pub struct SyncBoxingArena<T> {
+    arena: Mutex<BoxingArena<T>>,
+}
+
+impl<T> SyncBoxingArena<T> {
+    /// Create an empty arena.
+    pub fn new() -> Self {
+        Self::from(BoxingArena::new())
+    }
+
+    /// Create an arena with `size` free boxes.
+    pub fn with_capacity(size: usize) -> Self {
+        Self::from(BoxingArena::with_capacity(size))
+    }
+
+    /// Lock the arena, for the operations that are not provided on `&self`.
+    pub fn lock(&self) -> impl DerefMut<Target = BoxingArena<T>> + '_ {
+        sync::lock(&self.arena)
+    }
+
+    /// Lock the arena if no other thread holds it, or return `None`.
+    pub fn try_lock(&self) -> Option<impl DerefMut<Target = BoxingArena<T>> + '_> {
+        sync::try_lock(&self.arena)
+    }
+
+    /// Box a value, see `BoxingArena::rebox`.
+    #[track_caller]
+    pub fn rebox(&self, v: T) -> Box<T> {
+        self.lock().rebox(v)
+    }
+
+    /// Like `rebox` but only if there are free boxes, see `BoxingArena::try_rebox`.
+    #[track_caller]
+    pub fn try_rebox(&self, v: &mut Option<T>) -> Option<Box<T>> {
+        self.lock().try_rebox(v)
+    }
+
+    /// Like `rebox` but only if there are free boxes, see `BoxingArena::try_rebox_value`.
+    #[track_caller]
+    pub fn try_rebox_value(&self, v: T) -> Result<Box<T>, T> {
+        self.lock().try_rebox_value(v)
+    }
+
+    /// Unbox the value and keep the allocation, see `BoxingArena::unbox`.
+    #[track_caller]
+    pub fn unbox(&self, b: Box<T>) -> T {
+        self.lock().unbox(b)
+    }
+
+    /// Return the number of free boxes.
+    pub fn capacity(&self) -> usize {
+        self.lock().capacity()
+    }
+
+    /// Trims capacity to the given size if it is larger.
+    pub fn trim(&self, size: usize) {
+        self.lock().trim(size)
+    }
+
+    /// Return the arena.
+    pub fn into_inner(self) -> BoxingArena<T> {
+        sync::into_inner(self.arena)
+    }
+}
+
+impl<T> From<BoxingArena<T>> for SyncBoxingArena<T> {
+    fn from(arena: BoxingArena<T>) -> Self {
+        Self {
+            arena: Mutex::new(arena),
+        }
+    }
+}
+
+impl<T> Default for SyncBoxingArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Pool<T> for SyncBoxingArena<T> {
+    #[track_caller]
+    fn rebox(&mut self, v: T) -> Box<T> {
+        SyncBoxingArena::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&mut self, b: Box<T>) -> T {
+        SyncBoxingArena::unbox(self, b)
+    }
+
+    fn trim(&mut self, size: usize) {
+        SyncBoxingArena::trim(self, size)
+    }
+
+    fn capacity(&self) -> usize {
+        SyncBoxingArena::capacity(self)
+    }
+}
+
+impl<T> SharedPool<T> for SyncBoxingArena<T> {
+    #[track_caller]
+    fn rebox(&self, v: T) -> Box<T> {
+        SyncBoxingArena::rebox(self, v)
+    }
+
+    #[track_caller]
+    fn unbox(&self, b: Box<T>) -> T {
+        SyncBoxingArena::unbox(self, b)
+    }
+}
+
+#[cfg(all(test, not(feature = "loom")))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn sync_arena() {
+        let arena = Arc::new(SyncBoxingArena::with_capacity(4));
+        let workers: Vec<_> = (0..4u32)
+            .map(|i| {
+                let arena = arena.clone();
+                std::thread::spawn(move || {
+                    let b = arena.rebox(vec![i; 16]);
+                    arena.unbox(b).len()
+                })
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), 16);
+        }
+        assert_eq!(arena.capacity(), 4);
+
+        arena.lock().set_max_capacity(Some(2));
+        let arena = Arc::try_unwrap(arena).ok().unwrap().into_inner();
+        assert_eq!(arena.max_capacity(), Some(2));
+    }
+}
@@ -0,0 +1,465 @@
+//! Recording the operations done on a pool, and replaying them against another one.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::{clock, provenance, Pool};
+
+/// An operation in a `Trace`. Boxes are identified by the order in which they were handed
+/// out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceOp {
+    /// A value of `size` bytes was boxed.
+    Rebox {
+        /// The identifier of the new box.
+        id: u64,
+        /// The size of the value.
+        size: usize,
+    },
+    /// A box was given back to the pool. Boxes that did not come from the pool get an
+    /// identifier of their own.
+    Unbox {
+        /// The identifier of the box.
+        id: u64,
+        /// The size of the value.
+        size: usize,
+    },
+    /// The pool was trimmed to `size` free allocations.
+    Trim {
+        /// The number of free allocations to keep.
+        size: usize,
+    },
+}
+
+/// An operation in a `Trace`, with when it happened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Nanoseconds since the recording started, or 0 on targets without a clock.
+    pub timestamp_ns: u64,
+    /// What was done.
+    pub op: TraceOp,
+}
+
+/// A sequence of pool operations, as recorded by a `TraceRecorder`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    events: Vec<TraceEvent>,
+}
+
+const MAGIC: &[u8; 8] = b"BATRACE1";
+
+fn write_varint(w: &mut dyn Write, mut v: u64) -> io::Result<()> {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Read a varint, or return `None` at the end of the input.
+fn read_varint(r: &mut dyn Read) -> io::Result<Option<u64>> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        if r.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        v |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(v));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "varint too long"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Write an event, timestamped relative to the previous one at `last`.
+fn write_event(w: &mut dyn Write, event: &TraceEvent, last: u64) -> io::Result<()> {
+    let (kind, arg) = match event.op {
+        TraceOp::Rebox { size, .. } => (0, size as u64),
+        TraceOp::Unbox { id, .. } => (1, id),
+        TraceOp::Trim { size } => (2, size as u64),
+    };
+    write_varint(w, kind)?;
+    write_varint(w, event.timestamp_ns - last)?;
+    write_varint(w, arg)?;
+    if let TraceOp::Unbox { size, .. } = event.op {
+        write_varint(w, size as u64)?;
+    }
+    Ok(())
+}
+
+/// How a pool fared on a trace, as returned by `Trace::evaluate`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Boxes handed out from the free allocations of the pool.
+    pub hits: u64,
+    /// Boxes newly allocated.
+    pub misses: u64,
+    /// The most free allocations the pool held at once.
+    pub peak_capacity: usize,
+    /// The free allocations the pool held at the end.
+    pub final_capacity: usize,
+}
+
+impl Trace {
+    /// Return the recorded operations, oldest first.
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Write the trace in a compact binary form, that `read_from` reads back.
+    pub fn write_to(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        let mut last = 0;
+        for event in &self.events {
+            write_event(w, event, last)?;
+            last = event.timestamp_ns;
+        }
+        Ok(())
+    }
+
+    /// Read a trace written by `write_to`.
+    pub fn read_from(r: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a boxing-arena trace"));
+        }
+
+        let mut trace = Trace::default();
+        let mut timestamp_ns = 0u64;
+        let mut next_id = 0u64;
+        while let Some(kind) = read_varint(r)? {
+            let mut next = || read_varint(r)?.ok_or_else(|| invalid("truncated trace"));
+            let overflow = || invalid("trace overflows its counters");
+            timestamp_ns = next()?.checked_add(timestamp_ns).ok_or_else(overflow)?;
+            let op = match kind {
+                0 => {
+                    let id = next_id;
+                    next_id = id.checked_add(1).ok_or_else(overflow)?;
+                    TraceOp::Rebox {
+                        id,
+                        size: next()? as usize,
+                    }
+                }
+                1 => {
+                    let id = next()?;
+                    next_id = next_id.max(id.checked_add(1).ok_or_else(overflow)?);
+                    TraceOp::Unbox {
+                        id,
+                        size: next()? as usize,
+                    }
+                }
+                2 => TraceOp::Trim {
+                    size: next()? as usize,
+                },
+                _ => return Err(invalid("unknown trace operation")),
+            };
+            trace.events.push(TraceEvent { timestamp_ns, op });
+        }
+        Ok(trace)
+    }
+
+    /// Run the operations of the trace against `pool`, as fast as possible, with values made
+    /// by `make`. Returns how long it took, or zero on targets without a clock. Boxes still
+    /// live at the end are dropped.
+    pub fn replay<T, P, F>(&self, pool: &mut P, mut make: F) -> Duration
+    where
+        P: Pool<T>,
+        F: FnMut() -> T,
+    {
+        let mut live = BTreeMap::new();
+        let start = clock::now();
+        for event in &self.events {
+            match event.op {
+                TraceOp::Rebox { id, .. } => {
+                    live.insert(id, pool.rebox(make()));
+                }
+                TraceOp::Unbox { id, .. } => {
+                    let b = live.remove(&id).unwrap_or_else(|| Box::new(make()));
+                    drop(pool.unbox(b));
+                }
+                TraceOp::Trim { size } => pool.trim(size),
+            }
+        }
+        Duration::from_nanos(clock::elapsed_ns(start))
+    }
+
+    /// Run the operations of the trace against `pool`, like `replay`, and report how often
+    /// it reused an allocation and how many it held, for comparing capacity policies and
+    /// pool sizes offline. A box counts as a hit when the pool held fewer free allocations
+    /// after handing it out.
+    pub fn evaluate<T, P, F>(&self, pool: &mut P, mut make: F) -> ReplayReport
+    where
+        P: Pool<T>,
+        F: FnMut() -> T,
+    {
+        let mut live = BTreeMap::new();
+        let mut report = ReplayReport {
+            peak_capacity: pool.capacity(),
+            ..ReplayReport::default()
+        };
+        for event in &self.events {
+            let before = pool.capacity();
+            match event.op {
+                TraceOp::Rebox { id, .. } => {
+                    live.insert(id, pool.rebox(make()));
+                    match pool.capacity() < before {
+                        true => report.hits += 1,
+                        false => report.misses += 1,
+                    }
+                }
+                TraceOp::Unbox { id, .. } => {
+                    let b = live.remove(&id).unwrap_or_else(|| Box::new(make()));
+                    drop(pool.unbox(b));
+                }
+                TraceOp::Trim { size } => pool.trim(size),
+            }
+            report.peak_capacity = report.peak_capacity.max(pool.capacity());
+        }
+        report.final_capacity = pool.capacity();
+        report
+    }
+}
+
+/// Where a `TraceRecorder` puts the events.
+enum Sink {
+    Trace,
+    Writer {
+        w: Box<dyn Write + Send>,
+        /// The timestamp of the last event written.
+        last: u64,
+    },
+    /// Writing failed, with this error unless it was returned already.
+    Failed(Option<io::Error>),
+}
+
+/// A pool that records the operations done on the pool it wraps into a `Trace`.
+pub struct TraceRecorder<P> {
+    pool: P,
+    trace: Trace,
+    sink: Sink,
+    ids: BTreeMap<usize, u64>,
+    next_id: u64,
+    start: Option<Instant>,
+}
+
+impl<P> TraceRecorder<P> {
+    /// Start recording the operations done on `pool`.
+    pub fn new(pool: P) -> Self {
+        Self::with_sink(pool, Sink::Trace)
+    }
+
+    /// Start recording the operations done on `pool`, writing them to `w` as they are done
+    /// rather than keeping them, in the form of `Trace::write_to`, for recording long runs
+    /// in production. The trace of the recorder then stays empty.
+    pub fn with_writer(pool: P, mut w: Box<dyn Write + Send>) -> Self {
+        let sink = match w.write_all(MAGIC) {
+            Ok(()) => Sink::Writer { w, last: 0 },
+            Err(e) => Sink::Failed(Some(e)),
+        };
+        Self::with_sink(pool, sink)
+    }
+
+    fn with_sink(pool: P, sink: Sink) -> Self {
+        Self {
+            pool,
+            trace: Trace::default(),
+            sink,
+            ids: BTreeMap::new(),
+            next_id: 0,
+            start: clock::now(),
+        }
+    }
+
+    /// Return the wrapped pool.
+    pub fn pool(&self) -> &P {
+        &self.pool
+    }
+
+    /// Return the trace recorded so far.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Stop recording, and return the wrapped pool and the trace.
+    pub fn into_parts(self) -> (P, Trace) {
+        (self.pool, self.trace)
+    }
+
+    /// Flush the writer given to `with_writer`, returning the first error writing to it, after
+    /// which events are no longer written.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Trace => Ok(()),
+            Sink::Writer { w, .. } => w.flush(),
+            Sink::Failed(error) => Err(error
+                .take()
+                .unwrap_or_else(|| io::Error::other("writing the trace failed earlier"))),
+        }
+    }
+
+    fn push(&mut self, op: TraceOp) {
+        let timestamp_ns = clock::elapsed_ns(self.start);
+        let event = TraceEvent { timestamp_ns, op };
+        match &mut self.sink {
+            Sink::Trace => self.trace.events.push(event),
+            Sink::Writer { w, last } => match write_event(w, &event, *last) {
+                Ok(()) => *last = timestamp_ns,
+                Err(e) => self.sink = Sink::Failed(Some(e)),
+            },
+            Sink::Failed(_) => {}
+        }
+    }
+
+    fn new_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+}
+
+impl<T, P: Pool<T>> Pool<T> for TraceRecorder<P> {
+    #[track_caller]
+    fn rebox(&mut self, v: T) -> Box<T> {
+        let b = self.pool.rebox(v);
+        let id = self.new_id();
+        self.ids.insert(provenance::addr::<T>(&*b), id);
+        self.push(TraceOp::Rebox {
+            id,
+            size: std::mem::size_of::<T>(),
+        });
+        b
+    }
+
+    #[track_caller]
+    fn unbox(&mut self, b: Box<T>) -> T {
+        let id = match self.ids.remove(&provenance::addr::<T>(&*b)) {
+            Some(id) => id,
+            None => self.new_id(),
+        };
+        self.push(TraceOp::Unbox {
+            id,
+            size: std::mem::size_of::<T>(),
+        });
+        self.pool.unbox(b)
+    }
+
+    fn trim(&mut self, size: usize) {
+        self.push(TraceOp::Trim { size });
+        self.pool.trim(size)
+    }
+
+    fn capacity(&self) -> usize {
+        self.pool.capacity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoxingArena;
+
+    #[test]
+    fn record_replay() {
+        let mut recorder = TraceRecorder::new(BoxingArena::new());
+        let a = recorder.rebox(1u64);
+        let b = recorder.rebox(2u64);
+        recorder.unbox(b);
+        recorder.unbox(Box::new(3u64));
+        let c = recorder.rebox(4u64);
+        recorder.trim(0);
+        drop((a, c));
+
+        let (arena, trace) = recorder.into_parts();
+        assert_eq!(arena.stats().hits, 1);
+        let ops: Vec<_> = trace.events().iter().map(|e| e.op).collect();
+        assert_eq!(
+            ops,
+            [
+                TraceOp::Rebox { id: 0, size: 8 },
+                TraceOp::Rebox { id: 1, size: 8 },
+                TraceOp::Unbox { id: 1, size: 8 },
+                TraceOp::Unbox { id: 2, size: 8 },
+                TraceOp::Rebox { id: 3, size: 8 },
+                TraceOp::Trim { size: 0 },
+            ]
+        );
+
+        let mut file = vec![];
+        trace.write_to(&mut file).unwrap();
+        assert_eq!(Trace::read_from(&mut &file[..]).unwrap(), trace);
+        assert!(Trace::read_from(&mut &file[..file.len() - 1]).is_err());
+
+        let mut arena = BoxingArena::new();
+        trace.replay(&mut arena, || 0u64);
+        assert_eq!(arena.stats().hits, 1);
+        assert_eq!(arena.stats().unboxes, 2);
+        assert_eq!(arena.capacity(), 0);
+
+        let mut arena = BoxingArena::new();
+        arena.set_max_capacity(Some(1));
+        let report = trace.evaluate(&mut arena, || 0u64);
+        let expected = ReplayReport {
+            hits: 1,
+            misses: 2,
+            peak_capacity: 1,
+            final_capacity: 0,
+        };
+        assert_eq!(report, expected);
+    }
+
+    #[test]
+    fn read_overflow() {
+        let read = |events: &[u64]| {
+            let mut file = MAGIC.to_vec();
+            for &v in events {
+                write_varint(&mut file, v).unwrap();
+            }
+            Trace::read_from(&mut &file[..]).map_err(|e| e.kind())
+        };
+        let invalid = Err(io::ErrorKind::InvalidData);
+        assert_eq!(read(&[0, u64::MAX, 8]).unwrap().events().len(), 1);
+        assert_eq!(read(&[0, u64::MAX, 8, 2, 1, 0]), invalid);
+        assert_eq!(read(&[1, 0, u64::MAX, 8]), invalid);
+        assert!(read(&[1, 0]).is_err());
+    }
+
+    #[test]
+    fn record_to_writer() {
+        #[derive(Clone, Default)]
+        struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let file = Shared::default();
+        let mut recorder = TraceRecorder::with_writer(BoxingArena::new(), Box::new(file.clone()));
+        let a = recorder.rebox(1u64);
+        recorder.unbox(a);
+        recorder.flush().unwrap();
+        assert!(recorder.trace().events().is_empty());
+
+        let trace = Trace::read_from(&mut &file.0.lock().unwrap()[..]).unwrap();
+        let ops: Vec<_> = trace.events().iter().map(|e| e.op).collect();
+        let expected = [TraceOp::Rebox { id: 0, size: 8 }, TraceOp::Unbox { id: 0, size: 8 }];
+        assert_eq!(ops, expected);
+    }
+}
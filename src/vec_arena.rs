@@ -0,0 +1,135 @@
+//! An arena of vectors, reusing their capacity.
+
+use alloc::vec::Vec;
+
+/// An arena of empty `Vec<T>`s that keep their capacity between uses, for code that builds
+/// many short-lived vectors.
+///
+/// Free vectors are kept in buckets by power-of-two capacity classes, so that a request is
+/// served by a vector of a fitting size, rather than by whichever vector grew the most.
+pub struct VecArena<T> {
+    /// Bucket `i` holds vectors with a capacity in `2^i..2^(i+1)`.
+    buckets: Vec<Vec<Vec<T>>>,
+    max_retained_capacity: Option<usize>,
+}
+
+impl<T> VecArena<T> {
+    /// Create an empty arena. No allocation is made by this function.
+    pub fn new() -> Self {
+        Self {
+            buckets: Vec::new(),
+            max_retained_capacity: None,
+        }
+    }
+
+    fn class(capacity: usize) -> usize {
+        capacity.ilog2() as usize
+    }
+
+    /// Return an empty vector, reusing a free one of the smallest capacity class if there is
+    /// one.
+    pub fn checkout(&mut self) -> Vec<T> {
+        self.buckets.iter_mut().find_map(Vec::pop).unwrap_or_default()
+    }
+
+    /// Return an empty vector with a capacity of at least `min_capacity`, reusing a free one
+    /// if there is one large enough.
+    pub fn checkout_with_capacity(&mut self, min_capacity: usize) -> Vec<T> {
+        if min_capacity == 0 {
+            return self.checkout();
+        }
+        let class = Self::class(min_capacity);
+        if let Some(bucket) = self.buckets.get_mut(class) {
+            // Vectors of the same class may still be too small.
+            if let Some(i) = bucket.iter().rposition(|v| v.capacity() >= min_capacity) {
+                return bucket.swap_remove(i);
+            }
+        }
+        match self.buckets.iter_mut().skip(class + 1).find_map(Vec::pop) {
+            Some(v) => v,
+            None => Vec::with_capacity(min_capacity),
+        }
+    }
+
+    /// Clear the vector and keep it for reuse. Vectors without capacity, or with more than the
+    /// maximum retained capacity, are dropped.
+    pub fn checkin(&mut self, mut v: Vec<T>) {
+        let capacity = v.capacity();
+        if capacity == 0 || core::mem::size_of::<T>() == 0 {
+            return;
+        }
+        if let Some(max) = self.max_retained_capacity {
+            if capacity > max {
+                return;
+            }
+        }
+        v.clear();
+        let class = Self::class(capacity);
+        if self.buckets.len() <= class {
+            self.buckets.resize_with(class + 1, Vec::new);
+        }
+        self.buckets[class].push(v);
+    }
+
+    /// Set the capacity above which `checkin` drops vectors instead of keeping them, so that
+    /// an occasional very large vector does not stay around.
+    pub fn set_max_retained_capacity(&mut self, max: Option<usize>) {
+        self.max_retained_capacity = max;
+        if let Some(max) = max {
+            for bucket in self.buckets.iter_mut() {
+                bucket.retain(|v| v.capacity() <= max);
+            }
+        }
+    }
+
+    /// Return the capacity above which `checkin` drops vectors.
+    pub fn max_retained_capacity(&self) -> Option<usize> {
+        self.max_retained_capacity
+    }
+
+    /// Return the number of free vectors.
+    pub fn capacity(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    /// Trims the free vectors of each capacity class to the given number if there are more.
+    pub fn trim(&mut self, size: usize) {
+        for bucket in self.buckets.iter_mut() {
+            bucket.truncate(size);
+        }
+    }
+}
+
+impl<T> Default for VecArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vec_arena() {
+        let mut arena = VecArena::new();
+        let mut a = arena.checkout();
+        a.extend(0..100u32);
+        let (small, large) = (Vec::<u32>::with_capacity(4), Vec::<u32>::with_capacity(1000));
+        let addr = large.as_ptr();
+        arena.checkin(a);
+        arena.checkin(small);
+        arena.checkin(large);
+        assert_eq!(arena.capacity(), 3);
+
+        assert_eq!(arena.checkout().capacity(), 4);
+        let b = arena.checkout_with_capacity(500);
+        assert_eq!((b.as_ptr(), b.len()), (addr, 0));
+        assert!(arena.checkout_with_capacity(100).capacity() >= 100);
+        assert_eq!(arena.capacity(), 0);
+
+        arena.set_max_retained_capacity(Some(10));
+        arena.checkin(b);
+        assert_eq!(arena.capacity(), 0);
+    }
+}
@@ -0,0 +1,34 @@
+//! Wiping of the boxes given back to arenas, enabled by the `zeroize` feature.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Overwrite the box at `p` with zeros, in a way that is not optimized out even though the
+/// memory is not read again before it is reused or deallocated.
+#[inline]
+pub(crate) unsafe fn wipe<T>(p: *mut T) {
+    zeroize::zeroize_flat_type(p);
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Like `wipe`, for the `size` bytes at `p`.
+#[cfg(feature = "std")]
+pub(crate) unsafe fn wipe_bytes(p: *mut u8, size: usize) {
+    use core::mem::MaybeUninit;
+    use zeroize::Zeroize;
+
+    core::slice::from_raw_parts_mut(p as *mut MaybeUninit<u8>, size).zeroize();
+}
+
+#[cfg(all(test, not(feature = "poison")))]
+mod tests {
+    use crate::BoxingArena;
+
+    #[test]
+    fn wipe() {
+        let mut ba = BoxingArena::with_capacity(1);
+        let a = ba.rebox([0x5au8; 16]);
+        ba.unbox(a);
+        let b = ba.rebox_uninit();
+        assert_eq!(unsafe { b.assume_init_read() }, [0; 16]);
+    }
+}